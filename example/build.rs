@@ -0,0 +1,10 @@
+//! Enable the onnxruntime tests in `tests/onnxruntime.rs` if
+//! `ORT_DYLIB_PATH` points to a shared library of onnxruntime.
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(onnxruntime)");
+    println!("cargo::rerun-if-env-changed=ORT_DYLIB_PATH");
+    if std::env::var_os("ORT_DYLIB_PATH").is_some_and(|path| !path.is_empty()) {
+        println!("cargo::rustc-cfg=onnxruntime");
+    }
+}
@@ -1,3 +1,4 @@
+use std::ffi::CStr;
 use std::{convert::Infallible, marker::PhantomData, ops::Add};
 
 use ndarray::{ArrayD, ArrayViewD};
@@ -18,7 +19,7 @@ where
     type KernelCreateError = Infallible;
     type ComputeError = Infallible;
 
-    const NAME: &'static str = "CustomAdd";
+    const NAME: &'static CStr = c"CustomAdd";
 
     type OpInputs<'s> = (ArrayViewD<'s, T>, ArrayViewD<'s, T>);
     type OpOutputs = (ArrayD<T>,);
@@ -34,3 +35,23 @@ where
         Ok((&array_x + &array_y,))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{ArrayD, arr2};
+    use ort_custom_op::testing::TestKernel;
+
+    use super::*;
+
+    #[test]
+    fn custom_add() {
+        let kernel = TestKernel::<CustomAdd<i64>>::builder().build().unwrap();
+        let a = arr2(&[[1i64, 2], [3, 4]]).into_dyn();
+        let b = arr2(&[[10i64, 20], [30, 40]]).into_dyn();
+        let outputs = kernel
+            .compute(vec![Some(a.clone().into()), Some(b.clone().into())])
+            .unwrap();
+        let c: ArrayD<i64> = outputs[0].as_ref().unwrap().to_array().unwrap();
+        assert_eq!(c, a + b);
+    }
+}
@@ -1,4 +1,5 @@
 use std::convert::Infallible;
+use std::ffi::CStr;
 
 use anyhow::Error;
 use ndarray::{ArrayD, ArrayViewD};
@@ -20,7 +21,7 @@ impl CustomOp for AttrShowcase {
     type KernelCreateError = Error;
     type ComputeError = Infallible;
 
-    const NAME: &'static str = "AttrShowcase";
+    const NAME: &'static CStr = c"AttrShowcase";
 
     type OpInputs<'s> = (
         ArrayViewD<'s, f32>,
@@ -50,3 +51,65 @@ impl CustomOp for AttrShowcase {
         Ok((a, b, c))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr0, arr1};
+    use ort_custom_op::testing::{Tensor, TestKernel, owned_values};
+
+    use super::*;
+
+    #[test]
+    fn attr_showcase() {
+        let kernel = TestKernel::<AttrShowcase>::builder()
+            .attribute("float_attr", 1.5f32)
+            .attribute("int_attr", 42i64)
+            .attribute("string_attr", "bar")
+            .attribute("floats_attr", vec![1.5f32, 1.5])
+            .attribute("ints_attr", vec![42i64, 42])
+            .attribute(
+                "u8_tensor",
+                Tensor::from(arr1(&[102u8, 111, 111]).into_dyn()),
+            )
+            .build()
+            .unwrap();
+        let outputs = kernel
+            .compute(vec![
+                Some(arr0(0f32).into_dyn().into()),
+                Some(arr0(0i64).into_dyn().into()),
+                Some(arr0("foo").into_dyn().into()),
+            ])
+            .unwrap();
+        let outputs: Vec<_> = outputs.into_iter().map(Option::unwrap).collect();
+        assert_eq!(outputs[0].to_array::<f32>().unwrap(), arr0(1.5).into_dyn());
+        assert_eq!(outputs[1].to_array::<i64>().unwrap(), arr0(42).into_dyn());
+        assert_eq!(
+            outputs[2].to_strings().unwrap(),
+            arr0("foo + bar".to_string()).into_dyn()
+        );
+    }
+
+    #[test]
+    fn attr_showcase_releases_attributes() {
+        let builder = || {
+            TestKernel::<AttrShowcase>::builder()
+                .attribute("float_attr", 1.5f32)
+                .attribute("int_attr", 42i64)
+                .attribute("string_attr", "bar")
+                .attribute("floats_attr", vec![1.5f32, 1.5])
+                .attribute("ints_attr", vec![42i64, 42])
+                .attribute("u8_tensor", Tensor::from(arr1(&[0u8; 64]).into_dyn()))
+        };
+        let before = owned_values();
+        for _ in 0..1_000 {
+            builder().build().unwrap();
+        }
+        assert_eq!(owned_values(), before);
+    }
+
+    #[test]
+    fn attr_showcase_missing_attrs() {
+        let err = TestKernel::<AttrShowcase>::builder().build().unwrap_err();
+        assert!(err.to_string().starts_with("AttrShowcase:"), "{}", err);
+    }
+}
@@ -0,0 +1,80 @@
+use std::convert::Infallible;
+use std::ffi::CStr;
+
+use anyhow::Error;
+use ndarray::{ArrayD, ArrayViewD, Axis, IxDyn, concatenate};
+
+use ort_custom_op::prelude::*;
+
+/// Flatten all tensors of a `seq(tensor(int64))` and concatenate
+/// them into a single 1D tensor.
+pub struct ConcatSequence;
+
+impl CustomOp for ConcatSequence {
+    type KernelCreateError = Infallible;
+    type ComputeError = Error;
+
+    const NAME: &'static CStr = c"ConcatSequence";
+
+    type OpInputs<'s> = (Sequence<ArrayViewD<'s, i64>>,);
+    type OpOutputs = (ArrayD<i64>,);
+
+    fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Ok(ConcatSequence)
+    }
+
+    fn kernel_compute(
+        &self,
+        (Sequence(elements),): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        let flat: Vec<_> = elements
+            .iter()
+            .map(|arr| arr.view().into_shape(IxDyn(&[arr.len()])))
+            .collect::<Result<_, _>>()?;
+        if flat.is_empty() {
+            return Ok((ArrayD::zeros(IxDyn(&[0])),));
+        }
+        Ok((concatenate(Axis(0), &flat)?,))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr1, arr2};
+    use ort_custom_op::testing::{Input, TestKernel};
+
+    use super::*;
+
+    #[test]
+    fn concat_sequence() {
+        let kernel = TestKernel::<ConcatSequence>::builder().build().unwrap();
+        let elements = vec![
+            arr2(&[[1i64, 2], [3, 4]]).into_dyn().into(),
+            arr1(&[5i64, 6, 7]).into_dyn().into(),
+        ];
+        let outputs = kernel
+            .compute_inputs(vec![Some(Input::Sequence(elements))])
+            .unwrap();
+        assert_eq!(
+            outputs[0].as_ref().unwrap().to_array::<i64>().unwrap(),
+            arr1(&[1, 2, 3, 4, 5, 6, 7]).into_dyn()
+        );
+
+        let outputs = kernel
+            .compute_inputs(vec![Some(Input::Sequence(vec![]))])
+            .unwrap();
+        assert_eq!(
+            outputs[0].as_ref().unwrap().to_array::<i64>().unwrap(),
+            ArrayD::<i64>::zeros(IxDyn(&[0]))
+        );
+    }
+
+    #[test]
+    fn concat_sequence_rejects_tensor() {
+        let kernel = TestKernel::<ConcatSequence>::builder().build().unwrap();
+        let err = kernel
+            .compute(vec![Some(arr1(&[1i64]).into_dyn().into())])
+            .unwrap_err();
+        assert!(err.to_string().contains("Expected sequence"), "{}", err);
+    }
+}
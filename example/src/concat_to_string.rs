@@ -0,0 +1,57 @@
+use std::convert::Infallible;
+use std::ffi::CStr;
+
+use anyhow::{Error, bail};
+use ndarray::{Array1, ArrayD, ArrayViewD};
+
+use ort_custom_op::prelude::*;
+
+/// Flatten any number of tensors of mixed element types and
+/// concatenate their string representations into a 1D tensor.
+pub struct ConcatToString;
+
+impl CustomOp for ConcatToString {
+    type KernelCreateError = Infallible;
+    type ComputeError = Error;
+
+    const NAME: &'static CStr = c"ConcatToString";
+
+    type OpInputs<'s> = (Vec<Value<'s>>,);
+    type OpOutputs = (ArrayD<String>,);
+
+    fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Ok(ConcatToString)
+    }
+
+    fn kernel_compute(
+        &self,
+        (values,): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        let mut out = vec![];
+        for value in values {
+            match value {
+                Value::TensorBool(arr) => extend(&mut out, arr),
+                Value::TensorF32(arr) => extend(&mut out, arr),
+                Value::TensorF64(arr) => extend(&mut out, arr),
+                Value::TensorI16(arr) => extend(&mut out, arr),
+                Value::TensorI32(arr) => extend(&mut out, arr),
+                Value::TensorI64(arr) => extend(&mut out, arr),
+                Value::TensorI8(arr) => extend(&mut out, arr),
+                Value::TensorU16(arr) => extend(&mut out, arr),
+                Value::TensorU32(arr) => extend(&mut out, arr),
+                Value::TensorU64(arr) => extend(&mut out, arr),
+                Value::TensorU8(arr) => extend(&mut out, arr),
+                Value::TensorStr(arr) => extend(&mut out, arr),
+                Value::TensorF16(arr) => extend(&mut out, arr),
+                Value::TensorBF16(arr) => extend(&mut out, arr),
+                // Sequences, maps and any future value types
+                _ => bail!("Expected tensor inputs"),
+            }
+        }
+        Ok((Array1::from(out).into_dyn(),))
+    }
+}
+
+fn extend<T: ToString>(out: &mut Vec<String>, arr: ArrayViewD<'_, T>) {
+    out.extend(arr.iter().map(ToString::to_string));
+}
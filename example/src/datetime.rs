@@ -1,4 +1,4 @@
-use std::convert::Infallible;
+use std::ffi::CStr;
 
 use anyhow::Error;
 use chrono::NaiveDateTime;
@@ -8,33 +8,62 @@ use ort_custom_op::prelude::*;
 
 /// Parse input strings as datetimes using the provided format string.
 /// Outputs a tensor of unix timestamps as a float64. Invalid inputs
-/// are mapped to f64::NAN to make the operation infallible.
+/// are mapped to f64::NAN to make the operation infallible. Large
+/// inputs are parsed on the intra-op thread pool of the session.
+#[derive(OpAttributes)]
 pub struct ParseDateTime {
+    #[attr(validate = non_empty)]
     fmt: String,
 }
 
+fn non_empty(fmt: &str) -> Result<(), &'static str> {
+    if fmt.is_empty() {
+        return Err("format string must not be empty");
+    }
+    Ok(())
+}
+
 impl CustomOp for ParseDateTime {
     type KernelCreateError = Error;
-    type ComputeError = Infallible;
-    const NAME: &'static str = "ParseDateTime";
+    type ComputeError = Error;
+    const NAME: &'static CStr = c"ParseDateTime";
 
     type OpInputs<'s> = (ArrayViewD<'s, &'s str>,);
     type OpOutputs = (ArrayD<f64>,);
 
     fn kernel_create(info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
-        let fmt = info.get_attribute_string("fmt")?;
-        Ok(Self { fmt })
+        Self::from_kernel_info(info)
+    }
+
+    fn kernel_compute(
+        &self,
+        (array_in,): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        Ok((array_in.mapv(|s| self.parse(s)),))
     }
 
-    fn kernel_compute<'s>(
+    fn kernel_compute_with_context(
         &self,
-        (array_in,): (ArrayViewD<'s, &'s str>,),
+        ctx: &ComputeContext<'_>,
+        (array_in,): Self::OpInputs<'_>,
     ) -> Result<Self::OpOutputs, Self::ComputeError> {
-        let out = array_in.mapv(|s| {
-            NaiveDateTime::parse_from_str(s, &self.fmt)
-                .map(|dt| dt.and_utc().timestamp() as f64)
-                .unwrap_or_else(|_| f64::NAN)
-        });
+        let strings: Vec<_> = array_in.iter().copied().collect();
+        let chunks = ctx.parallel_for(strings.len(), |range| {
+            strings[range]
+                .iter()
+                .map(|s| self.parse(s))
+                .collect::<Vec<_>>()
+        })?;
+        let out = ArrayD::from_shape_vec(array_in.shape(), chunks.concat())?;
         Ok((out,))
     }
 }
+
+impl ParseDateTime {
+    /// Unix timestamp of `s`; NaN if it does not match the format.
+    fn parse(&self, s: &str) -> f64 {
+        NaiveDateTime::parse_from_str(s, &self.fmt)
+            .map(|dt| dt.and_utc().timestamp() as f64)
+            .unwrap_or(f64::NAN)
+    }
+}
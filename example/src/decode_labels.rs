@@ -0,0 +1,44 @@
+use std::ffi::CStr;
+
+use anyhow::{Error, anyhow};
+use ndarray::{ArrayD, ArrayViewD};
+
+use ort_custom_op::prelude::*;
+
+/// Map class indices to the labels given by the string tensor
+/// attribute `classes`.
+#[derive(OpAttributes)]
+pub struct DecodeLabels {
+    classes: ArrayD<String>,
+}
+
+impl CustomOp for DecodeLabels {
+    type KernelCreateError = Error;
+    type ComputeError = Error;
+
+    const NAME: &'static CStr = c"DecodeLabels";
+
+    type OpInputs<'s> = (ArrayViewD<'s, i64>,);
+    type OpOutputs = (ArrayD<String>,);
+
+    fn kernel_create(info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Self::from_kernel_info(info)
+    }
+
+    fn kernel_compute(
+        &self,
+        (indices,): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        let labels = indices
+            .iter()
+            .map(|idx| {
+                usize::try_from(*idx)
+                    .ok()
+                    .and_then(|idx| self.classes.get(idx))
+                    .cloned()
+                    .ok_or_else(|| anyhow!("Class index '{}' is out of range", idx))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((ArrayD::from_shape_vec(indices.shape(), labels)?,))
+    }
+}
@@ -0,0 +1,101 @@
+use std::ffi::CStr;
+
+use anyhow::{Error, bail};
+use ndarray::{ArrayD, ArrayViewD, arr1};
+
+use ort_custom_op::prelude::*;
+
+/// Output the names of the node and of its input and output as well
+/// as the static shape of the input as known at kernel creation.
+/// Dimensions which are not static are reported as -1.
+pub struct DescribeNode {
+    names: Vec<String>,
+    shape: Vec<i64>,
+}
+
+impl CustomOp for DescribeNode {
+    type KernelCreateError = Error;
+    type ComputeError = Error;
+
+    const NAME: &'static CStr = c"DescribeNode";
+
+    type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+    type OpOutputs = (ArrayD<String>, ArrayD<i64>);
+
+    fn kernel_create(info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        let names = vec![info.node_name()?, info.input_name(0)?, info.output_name(0)?];
+        let TypeInfo::Tensor {
+            element_type: ElementType::F32,
+            shape,
+        } = info.input_type_info(0)?
+        else {
+            bail!("Input must be a float tensor");
+        };
+        let shape = shape
+            .iter()
+            .map(|dim| match dim {
+                Dim::Fixed(size) => *size as i64,
+                Dim::Symbolic(_) | Dim::Unknown => -1,
+            })
+            .collect();
+        Ok(DescribeNode { names, shape })
+    }
+
+    fn kernel_compute(
+        &self,
+        _inputs: Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        Ok((arr1(&self.names).into_dyn(), arr1(&self.shape).into_dyn()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ort_custom_op::testing::TestKernel;
+
+    use super::*;
+
+    #[test]
+    fn describe_node() {
+        let input = TypeInfo::Tensor {
+            element_type: ElementType::F32,
+            shape: vec![Dim::Symbolic("batch".into()), Dim::Fixed(3), Dim::Unknown],
+        };
+        let kernel = TestKernel::<DescribeNode>::builder()
+            .node_name("describe")
+            .input("X", input)
+            .output("NAMES", TypeInfo::NonTensor)
+            .output("SHAPE", TypeInfo::NonTensor)
+            .build()
+            .unwrap();
+        let outputs = kernel
+            .compute(vec![Some(ArrayD::<f32>::zeros(vec![2, 3, 4]).into())])
+            .unwrap();
+        assert_eq!(
+            outputs[0].as_ref().unwrap().to_strings().unwrap(),
+            arr1(&["describe", "X", "NAMES"].map(String::from)).into_dyn()
+        );
+        assert_eq!(
+            outputs[1].as_ref().unwrap().to_array::<i64>().unwrap(),
+            arr1(&[-1, 3, -1]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn describe_node_rejects_non_float_input() {
+        let input = TypeInfo::Tensor {
+            element_type: ElementType::I64,
+            shape: vec![],
+        };
+        let err = TestKernel::<DescribeNode>::builder()
+            .input("X", input)
+            .output("NAMES", TypeInfo::NonTensor)
+            .build()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("must be a float tensor"),
+            "{}",
+            err
+        );
+    }
+}
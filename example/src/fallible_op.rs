@@ -1,24 +1,34 @@
+use std::ffi::CStr;
+
 use anyhow::{Error, bail};
 use ndarray::{ArrayD, ArrayViewD};
 
 use ort_custom_op::prelude::*;
 
 /// A custom operator that showcases error propagation from kernel creation and
-/// compute.
-pub struct FallibleOp;
+/// compute as well as logging.
+pub struct FallibleOp {
+    logger: Logger,
+}
 
 impl CustomOp for FallibleOp {
     type KernelCreateError = Error;
     type ComputeError = Error;
 
-    const NAME: &'static str = "FallibleOp";
+    const NAME: &'static CStr = c"FallibleOp";
 
     type OpInputs<'s> = (ArrayViewD<'s, bool>,);
     type OpOutputs = (ArrayD<bool>,);
 
     fn kernel_create(info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
-        let _ = dbg!(info.get_attribute_i64("required_attr"))?;
-        Ok(FallibleOp)
+        let logger = info.logger()?;
+        let required_attr = info.get_attribute_i64("required_attr")?;
+        ort_verbose!(
+            logger,
+            "Created kernel with required_attr={}",
+            required_attr
+        );
+        Ok(FallibleOp { logger })
     }
 
     fn kernel_compute(
@@ -28,6 +38,45 @@ impl CustomOp for FallibleOp {
         if (do_fail.mapv(|el| el as u8).sum() == 0) | do_fail.is_empty() {
             return Ok((do_fail.to_owned(),));
         }
+        ort_warn!(self.logger, "Failing due to non-zero input");
+        // Records of the `log` crate end up in the same sink
+        log::debug!("Input was {:?}", do_fail);
         bail!("Non-zero input found");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr0;
+    use ort_custom_op::testing::TestKernel;
+
+    use super::*;
+
+    #[test]
+    fn fail_create_kernel_missing_attr() {
+        let err = TestKernel::<FallibleOp>::builder().build().unwrap_err();
+        assert!(err.to_string().starts_with("FallibleOp:"), "{}", err);
+    }
+
+    #[test]
+    fn fail_compute() {
+        let kernel = TestKernel::<FallibleOp>::builder()
+            .attribute("required_attr", 1i64)
+            .build()
+            .unwrap();
+        let err = kernel
+            .compute(vec![Some(arr0(true).into_dyn().into())])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "FallibleOp: Non-zero input found");
+        assert!(
+            kernel
+                .logs()
+                .contains(&(Severity::Warning, "Failing due to non-zero input".into()))
+        );
+
+        // Don't fail depending on input
+        kernel
+            .compute(vec![Some(arr0(false).into_dyn().into())])
+            .unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+use std::convert::Infallible;
+use std::ffi::CStr;
+
+use ndarray::{ArrayD, ArrayViewD};
+
+use ort_custom_op::prelude::*;
+
+/// Replace NaN values with zeros. The optional second output is a
+/// mask of the replaced values. It is only produced if the input
+/// contains any NaN.
+pub struct FillNan;
+
+impl CustomOp for FillNan {
+    type KernelCreateError = Infallible;
+    type ComputeError = Infallible;
+
+    const NAME: &'static CStr = c"FillNan";
+
+    type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+    type OpOutputs = (ArrayD<f32>, Option<ArrayD<bool>>);
+
+    fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Ok(FillNan)
+    }
+
+    fn kernel_compute(
+        &self,
+        (array,): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        let mask = array.mapv(f32::is_nan);
+        let filled = array.mapv(|v| if v.is_nan() { 0.0 } else { v });
+        let mask = mask.iter().any(|v| *v).then_some(mask);
+        Ok((filled, mask))
+    }
+}
@@ -0,0 +1,104 @@
+use std::ffi::CStr;
+
+use anyhow::{Error, bail};
+use ndarray::{ArrayD, ArrayViewD, Zip};
+
+use ort_custom_op::prelude::*;
+
+/// A custom operator which adds its two inputs of equal shape by
+/// writing directly into the output tensor owned by onnxruntime.
+pub struct AddInPlace;
+
+impl CustomOp for AddInPlace {
+    type KernelCreateError = Error;
+    type ComputeError = Error;
+
+    const NAME: &'static CStr = c"AddInPlace";
+    const INFER_OUTPUT_SHAPES: bool = true;
+
+    type OpInputs<'s> = (ArrayViewD<'s, f32>, ArrayViewD<'s, f32>);
+    type OpOutputs = (ArrayD<f32>,);
+
+    fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Ok(AddInPlace)
+    }
+
+    fn kernel_compute(
+        &self,
+        (array_x, array_y): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        Ok((&array_x + &array_y,))
+    }
+
+    fn infer_output_shapes(ctx: &ShapeInferContext) -> Result<(), Error> {
+        // Both inputs have the shape of the output
+        let shape = ctx.input_shape(0)?;
+        ctx.set_output(0, ctx.input_element_type(0)?, &shape)
+    }
+
+    fn kernel_compute_in_place(
+        &self,
+        _ctx: &ComputeContext<'_>,
+        (array_x, array_y): Self::OpInputs<'_>,
+        outputs: &mut OutputBuffers<'_, Self::OpOutputs>,
+    ) -> Result<(), Self::ComputeError> {
+        if array_x.shape() != array_y.shape() {
+            bail!(
+                "Inputs must have equal shapes; found {:?} and {:?}",
+                array_x.shape(),
+                array_y.shape()
+            );
+        }
+        let mut out = outputs.allocate::<f32>(0, array_x.shape())?;
+        Zip::from(&mut out)
+            .and(&array_x)
+            .and(&array_y)
+            .for_each(|out, x, y| *out = x + y);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array2, ArrayD};
+    use ort_custom_op::testing::TestKernel;
+
+    use super::*;
+
+    #[test]
+    fn add_in_place() {
+        let kernel = TestKernel::<AddInPlace>::builder().build().unwrap();
+        let a = Array2::from_shape_fn((3, 5), |(i, j)| (i * 5 + j) as f32).into_dyn();
+        let outputs = kernel
+            .compute(vec![Some(a.clone().into()), Some(a.clone().into())])
+            .unwrap();
+        let res: ArrayD<f32> = outputs[0].as_ref().unwrap().to_array().unwrap();
+        assert_eq!(res, &a + &a);
+    }
+
+    #[test]
+    fn add_in_place_infers_output_shape() {
+        let input = TypeInfo::Tensor {
+            element_type: ElementType::F32,
+            shape: vec![Dim::Symbolic("batch".into()), Dim::Fixed(5)],
+        };
+        let outputs = TestKernel::<AddInPlace>::builder()
+            .input("X", input.clone())
+            .input("Y", input.clone())
+            .infer_output_shapes()
+            .unwrap();
+        assert_eq!(outputs, [Some(input)]);
+    }
+
+    #[test]
+    fn add_in_place_shape_mismatch() {
+        let kernel = TestKernel::<AddInPlace>::builder().build().unwrap();
+        let err = kernel
+            .compute(vec![
+                Some(ArrayD::<f32>::ones(vec![3, 5]).into()),
+                Some(ArrayD::<f32>::ones(vec![5, 3]).into()),
+            ])
+            .unwrap_err();
+        assert!(err.to_string().starts_with("AddInPlace:"), "{}", err);
+    }
+}
@@ -1,42 +1,42 @@
+use ort_custom_op::half::f16;
 use ort_custom_op::prelude::*;
 
 mod add;
 mod attr_showcase;
+mod concat_sequence;
+mod concat_to_string;
 mod datetime;
+mod decode_labels;
+mod describe_node;
 mod fallible_op;
+mod fill_nan;
+mod in_place_add;
+mod lookup_index;
+mod map_arg_max;
 mod optional_input;
 mod sum;
 mod variadic_identity;
+mod versioned;
 
-/// Static objects defining the custom operators
-const OP_ATTR_SHOWCASE: OrtCustomOp = build::<attr_showcase::AttrShowcase>();
-const OP_OPTIONAL_ADD: OrtCustomOp = build::<optional_input::OptionalAdd>();
-const OP_CUSTOM_ADD_F32: OrtCustomOp = build::<add::CustomAdd<f32>>();
-const OP_CUSTOM_ADD_F64: OrtCustomOp = build::<add::CustomAdd<f64>>();
-const OP_CUSTOM_SUM: OrtCustomOp = build::<sum::CustomSum>();
-const OP_PARSE_DATETIME: OrtCustomOp = build::<datetime::ParseDateTime>();
-const OP_VARIADIC_IDENTITY: OrtCustomOp = build::<variadic_identity::VariadicIdentity>();
-const OP_FALLIBLE: OrtCustomOp = build::<fallible_op::FallibleOp>();
-
-/// Public function which onnxruntime expects to be in the shared library
-#[unsafe(no_mangle)]
-pub extern "C" fn RegisterCustomOps(
-    options: &mut OrtSessionOptions,
-    api_base: &mut OrtApiBase,
-) -> *mut OrtStatus {
-    create_custom_op_domain(
-        options,
-        api_base,
-        "my.domain",
-        &[
-            &OP_ATTR_SHOWCASE,
-            &OP_CUSTOM_ADD_F32,
-            &OP_CUSTOM_ADD_F64,
-            &OP_CUSTOM_SUM,
-            &OP_PARSE_DATETIME,
-            &OP_VARIADIC_IDENTITY,
-            &OP_FALLIBLE,
-            &OP_OPTIONAL_ADD,
-        ],
-    )
+ort_custom_ops! {
+    domain "my.domain" => [
+        attr_showcase::AttrShowcase,
+        sum::CustomSum,
+        datetime::ParseDateTime,
+        variadic_identity::VariadicIdentity,
+        fallible_op::FallibleOp,
+        optional_input::OptionalAdd,
+        in_place_add::AddInPlace,
+        concat_sequence::ConcatSequence,
+        map_arg_max::MapArgMax,
+        fill_nan::FillNan,
+        concat_to_string::ConcatToString,
+        versioned::ScaleV1,
+        versioned::ScaleV2,
+        describe_node::DescribeNode,
+        lookup_index::LookupIndex,
+        decode_labels::DecodeLabels,
+        // One `CustomAdd` operator for each supported element type
+        add::CustomAdd<[f32, f64, f16, i32, i64]>,
+    ],
 }
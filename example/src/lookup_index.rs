@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+
+use anyhow::Error;
+use ndarray::{ArrayD, ArrayViewD};
+
+use ort_custom_op::prelude::*;
+
+/// Look up the index of each query in a vocabulary. Queries which are
+/// not part of the vocabulary are mapped to -1.
+///
+/// If the vocabulary is an initializer, the lookup table is built
+/// once during kernel creation rather than on every compute call.
+pub struct LookupIndex {
+    table: Option<HashMap<String, i64>>,
+}
+
+fn build_table<'a>(vocabulary: impl IntoIterator<Item = &'a str>) -> HashMap<String, i64> {
+    vocabulary
+        .into_iter()
+        .enumerate()
+        .map(|(idx, s)| (s.to_string(), idx as i64))
+        .collect()
+}
+
+impl CustomOp for LookupIndex {
+    type KernelCreateError = Error;
+    type ComputeError = Error;
+
+    const NAME: &'static CStr = c"LookupIndex";
+
+    type OpInputs<'s> = (ArrayViewD<'s, &'s str>, ArrayViewD<'s, &'s str>);
+    type OpOutputs = (ArrayD<i64>,);
+
+    fn kernel_create(info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        let table = info
+            .get_constant_input_strings(1)?
+            .map(|vocabulary| build_table(vocabulary.iter().map(String::as_str)));
+        Ok(LookupIndex { table })
+    }
+
+    fn kernel_compute(
+        &self,
+        (queries, vocabulary): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        let computed;
+        let table = match &self.table {
+            Some(table) => table,
+            None => {
+                computed = build_table(vocabulary.iter().copied());
+                &computed
+            }
+        };
+        let out = queries.mapv(|query| table.get(query).copied().unwrap_or(-1));
+        Ok((out,))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr1;
+    use ort_custom_op::testing::TestKernel;
+
+    use super::*;
+
+    #[test]
+    fn lookup_index() {
+        let vocabulary = arr1(&["a", "b", "c"]).into_dyn();
+        let queries = arr1(&["c", "x", "a"]).into_dyn();
+        for constant_vocabulary in [true, false] {
+            let mut builder = TestKernel::<LookupIndex>::builder();
+            if constant_vocabulary {
+                builder = builder.constant_input(1, vocabulary.clone());
+            }
+            let kernel = builder.build().unwrap();
+            let outputs = kernel
+                .compute(vec![
+                    Some(queries.clone().into()),
+                    Some(vocabulary.clone().into()),
+                ])
+                .unwrap();
+            assert_eq!(
+                outputs[0].as_ref().unwrap().to_array::<i64>().unwrap(),
+                arr1(&[2, -1, 0]).into_dyn()
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::ffi::CStr;
+
+use ndarray::{ArrayD, arr0};
+
+use ort_custom_op::prelude::*;
+
+/// Return the key with the largest value of a `map(string, float)`
+/// such as the class probabilities of a classifier. The result is an
+/// empty string for empty maps.
+pub struct MapArgMax;
+
+impl CustomOp for MapArgMax {
+    type KernelCreateError = Infallible;
+    type ComputeError = Infallible;
+
+    const NAME: &'static CStr = c"MapArgMax";
+
+    type OpInputs<'s> = (HashMap<&'s str, f32>,);
+    type OpOutputs = (ArrayD<String>,);
+
+    fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Ok(MapArgMax)
+    }
+
+    fn kernel_compute(
+        &self,
+        (map,): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        let key = map
+            .into_iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(key, _)| key.to_string())
+            .unwrap_or_default();
+        Ok((arr0(key).into_dyn(),))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr1;
+    use ort_custom_op::testing::{Input, Tensor, TestKernel};
+
+    use super::*;
+
+    fn map_input(keys: &[&str], values: &[f32]) -> Option<Input> {
+        Some(Input::Map {
+            keys: Tensor::from(arr1(keys).into_dyn()),
+            values: arr1(values).into_dyn().into(),
+        })
+    }
+
+    #[test]
+    fn map_arg_max() {
+        let kernel = TestKernel::<MapArgMax>::builder().build().unwrap();
+        for (input, expected) in [
+            (map_input(&["cat", "dog", "bird"], &[0.2, 0.7, 0.1]), "dog"),
+            (map_input(&[], &[]), ""),
+        ] {
+            let outputs = kernel.compute_inputs(vec![input]).unwrap();
+            assert_eq!(
+                outputs[0].as_ref().unwrap().to_strings().unwrap(),
+                arr0(expected.to_string()).into_dyn()
+            );
+        }
+    }
+
+    #[test]
+    fn map_arg_max_length_mismatch() {
+        let kernel = TestKernel::<MapArgMax>::builder().build().unwrap();
+        let err = kernel
+            .compute_inputs(vec![map_input(&["cat", "dog"], &[0.2])])
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Map has 2 keys but 1 values"),
+            "{}",
+            err
+        );
+    }
+}
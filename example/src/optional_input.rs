@@ -1,4 +1,5 @@
 use std::convert::Infallible;
+use std::ffi::CStr;
 
 use ndarray::{ArrayD, ArrayViewD};
 
@@ -11,7 +12,7 @@ impl CustomOp for OptionalAdd {
     type KernelCreateError = Infallible;
     type ComputeError = Infallible;
 
-    const NAME: &'static str = "OptionalAdd";
+    const NAME: &'static CStr = c"OptionalAdd";
 
     type OpInputs<'s> = (ArrayViewD<'s, f64>, Option<ArrayViewD<'s, f64>>);
     type OpOutputs = (ArrayD<f64>,);
@@ -30,3 +31,27 @@ impl CustomOp for OptionalAdd {
         },))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{ArrayD, arr1};
+    use ort_custom_op::testing::TestKernel;
+
+    use super::*;
+
+    #[test]
+    fn optional_input() {
+        let kernel = TestKernel::<OptionalAdd>::builder().build().unwrap();
+        let a = arr1(&[1.0, 2.0]).into_dyn();
+
+        let outputs = kernel
+            .compute(vec![Some(a.clone().into()), Some(a.clone().into())])
+            .unwrap();
+        let sum: ArrayD<f64> = outputs[0].as_ref().unwrap().to_array().unwrap();
+        assert_eq!(sum, &a + &a);
+
+        let outputs = kernel.compute(vec![Some(a.clone().into()), None]).unwrap();
+        let identity: ArrayD<f64> = outputs[0].as_ref().unwrap().to_array().unwrap();
+        assert_eq!(identity, a);
+    }
+}
@@ -7,15 +7,29 @@ use ort_custom_op::prelude::*;
 /// A custom operator which adds a variadic number of inputs
 pub struct CustomSum;
 
+/// Inputs of [`CustomSum`]; requires one or more inputs.
+#[derive(Inputs)]
+pub struct SumInputs<'s> {
+    /// First summand
+    first: ArrayViewD<'s, f32>,
+    /// Any number of further summands
+    rest: Vec<ArrayViewD<'s, f32>>,
+}
+
+/// Outputs of [`CustomSum`]
+#[derive(Outputs)]
+pub struct SumOutputs {
+    /// Element-wise sum of all inputs
+    sum: ArrayD<f32>,
+}
+
+#[custom_op]
 impl CustomOp for CustomSum {
     type KernelCreateError = Infallible;
     type ComputeError = Infallible;
 
-    const NAME: &'static str = "CustomSum";
-
-    // Require 1 or more inputs
-    type OpInputs<'s> = (ArrayViewD<'s, f32>, Vec<ArrayViewD<'s, f32>>);
-    type OpOutputs = (ArrayD<f32>,);
+    type OpInputs<'s> = SumInputs<'s>;
+    type OpOutputs = SumOutputs;
 
     fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
         Ok(CustomSum)
@@ -23,11 +37,11 @@ impl CustomOp for CustomSum {
 
     fn kernel_compute(
         &self,
-        inputs: Self::OpInputs<'_>,
+        SumInputs { first, rest }: Self::OpInputs<'_>,
     ) -> Result<Self::OpOutputs, Self::ComputeError> {
-        let (first, rest) = inputs;
-        Ok((rest
+        let sum = rest
             .into_iter()
-            .fold(first.into_owned(), |acc, arr| acc + arr),))
+            .fold(first.into_owned(), |acc, arr| acc + arr);
+        Ok(SumOutputs { sum })
     }
 }
@@ -1,4 +1,5 @@
 use std::convert::Infallible;
+use std::ffi::CStr;
 
 use ndarray::{ArrayD, ArrayViewD};
 
@@ -11,7 +12,7 @@ impl CustomOp for VariadicIdentity {
     type KernelCreateError = Infallible;
     type ComputeError = Infallible;
 
-    const NAME: &'static str = "VariadicIdentity";
+    const NAME: &'static CStr = c"VariadicIdentity";
 
     type OpInputs<'s> = (Vec<ArrayViewD<'s, f32>>,);
     type OpOutputs = (Vec<ArrayD<f32>>,);
@@ -27,3 +28,24 @@ impl CustomOp for VariadicIdentity {
         Ok((inputs.into_iter().map(|arr| arr.into_owned()).collect(),))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr1, arr2};
+    use ort_custom_op::testing::{Tensor, TestKernel};
+
+    use super::*;
+
+    #[test]
+    fn variadic_identity() {
+        let kernel = TestKernel::<VariadicIdentity>::builder().build().unwrap();
+        let inputs: Vec<Tensor> = vec![
+            arr1(&[1f32, 2.0]).into_dyn().into(),
+            arr2(&[[3f32], [4.0]]).into_dyn().into(),
+        ];
+        let outputs = kernel
+            .compute_with_output_count(inputs.iter().cloned().map(Some).collect(), 2)
+            .unwrap();
+        assert_eq!(outputs, inputs.into_iter().map(Some).collect::<Vec<_>>());
+    }
+}
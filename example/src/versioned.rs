@@ -0,0 +1,57 @@
+use std::convert::Infallible;
+use std::ffi::CStr;
+
+use ndarray::{ArrayD, ArrayViewD};
+
+use ort_custom_op::prelude::*;
+
+/// Version 1 of the `Scale` operator which doubles its input
+pub struct ScaleV1;
+
+/// Version 2 and later of the `Scale` operator which triples its
+/// input
+pub struct ScaleV2;
+
+impl CustomOp for ScaleV1 {
+    type KernelCreateError = Infallible;
+    type ComputeError = Infallible;
+
+    const NAME: &'static CStr = c"Scale";
+    const END_VERSION: i32 = 1;
+
+    type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+    type OpOutputs = (ArrayD<f32>,);
+
+    fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Ok(ScaleV1)
+    }
+
+    fn kernel_compute(
+        &self,
+        (array,): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        Ok((&array * 2.0,))
+    }
+}
+
+impl CustomOp for ScaleV2 {
+    type KernelCreateError = Infallible;
+    type ComputeError = Infallible;
+
+    const NAME: &'static CStr = c"Scale";
+    const SINCE_VERSION: i32 = 2;
+
+    type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+    type OpOutputs = (ArrayD<f32>,);
+
+    fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Ok(ScaleV2)
+    }
+
+    fn kernel_compute(
+        &self,
+        (array,): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        Ok((&array * 3.0,))
+    }
+}
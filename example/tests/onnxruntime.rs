@@ -0,0 +1,705 @@
+//! Run the operators of this crate in onnxruntime, mirroring the
+//! Python tests in `tests/python`.
+//!
+//! The tests need onnxruntime, which is loaded from `ORT_DYLIB_PATH`.
+//! They run whenever that variable is set at build time and are
+//! ignored otherwise:
+//!
+//! ```text
+//! ORT_DYLIB_PATH=/path/to/libonnxruntime.so cargo test -p example
+//! ```
+//!
+//! Without the variable, `cargo test -p example -- --ignored` runs
+//! them with onnxruntime found under its default library name.
+
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use ndarray::{ArrayD, IxDyn, arr0, arr1, arr2};
+use ort_custom_op::half::f16;
+use ort_custom_op::prelude::*;
+use ort_custom_op::testing::{AttributeValue, Input, Model, Node, Runtime, Session, Tensor};
+
+const DOMAIN: &str = "my.domain";
+
+/// Path of the shared library built from this crate. It is placed
+/// next to the test executables.
+fn shared_lib() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().join(format!(
+        "{}example{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    ))
+}
+
+/// Try to create a session of `model` with the operators of this
+/// crate. Panics if onnxruntime is not available.
+fn try_session(model: &Model) -> anyhow::Result<Session<'static>> {
+    let runtime = Runtime::get()
+        .unwrap()
+        .expect("onnxruntime not found; set `ORT_DYLIB_PATH`");
+    let register = Runtime::load_custom_ops(shared_lib()).unwrap();
+    runtime.session(&model.to_bytes(), register)
+}
+
+fn session(model: &Model) -> Session<'static> {
+    try_session(model).unwrap()
+}
+
+/// Model of a single node of `DOMAIN` whose inputs and outputs are
+/// tensors of unspecified shape.
+fn model(node: Node, inputs: &[(&str, ElementType)], outputs: &[(&str, ElementType)]) -> Model {
+    let model = Model::new().opset(DOMAIN, 1).node(node);
+    let model = inputs
+        .iter()
+        .fold(model, |model, &(name, ty)| model.input(name, ty, None));
+    outputs
+        .iter()
+        .fold(model, |model, &(name, ty)| model.output(name, ty, None))
+}
+
+fn assert_err_contains<T: Debug>(result: anyhow::Result<T>, pattern: &str) {
+    let err = result.unwrap_err();
+    assert!(err.to_string().contains(pattern), "{:#}", err);
+}
+
+fn custom_add_model(ty: ElementType) -> Model {
+    model(
+        Node::new(DOMAIN, "CustomAdd")
+            .inputs(&["A", "B"])
+            .outputs(&["C"]),
+        &[("A", ty), ("B", ty)],
+        &[("C", ty)],
+    )
+}
+
+fn check_custom_add<T>(zero: T, one: T)
+where
+    T: TensorElement + Copy + PartialEq + Debug,
+{
+    let sess = session(&custom_add_model(T::ELEMENT_TYPE));
+    let ones = ArrayD::from_elem(IxDyn(&[3, 5]), one);
+    let zeros = ArrayD::from_elem(IxDyn(&[3, 5]), zero);
+    let res = sess
+        .run(&[("A", ones.clone().into()), ("B", zeros.into())])
+        .unwrap();
+    assert_eq!(res[0].to_array::<T>().unwrap(), ones);
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn custom_add() {
+    check_custom_add(0f32, 1f32);
+    check_custom_add(0f64, 1f64);
+    check_custom_add(f16::ZERO, f16::ONE);
+    check_custom_add(0i32, 1i32);
+    check_custom_add(0i64, 1i64);
+}
+
+fn check_custom_add_panic<T>(one: T)
+where
+    T: TensorElement + Copy,
+{
+    // Incompatible shapes make ndarray panic inside the kernel
+    let sess = session(&custom_add_model(T::ELEMENT_TYPE));
+    let ones = ArrayD::from_elem(IxDyn(&[3, 5]), one);
+    let mismatched = ArrayD::from_elem(IxDyn(&[2, 4]), one);
+    let res = sess.run(&[("A", ones.clone().into()), ("B", mismatched.into())]);
+    assert_err_contains(res, "CustomAdd: panicked:");
+
+    // The session remains usable after a panic
+    sess.run(&[("A", ones.clone().into()), ("B", ones.into())])
+        .unwrap();
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn custom_add_panic() {
+    check_custom_add_panic(1f32);
+    check_custom_add_panic(1f64);
+    check_custom_add_panic(f16::ONE);
+    check_custom_add_panic(1i32);
+    check_custom_add_panic(1i64);
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn optional_input() {
+    for second_input_missing in [true, false] {
+        let ty = ElementType::F64;
+        let inputs: &[_] = if second_input_missing {
+            &[("A", ty)]
+        } else {
+            &[("A", ty), ("B", ty)]
+        };
+        let second_input_name = if second_input_missing { "" } else { "B" };
+        let node = Node::new(DOMAIN, "OptionalAdd")
+            .inputs(&["A", second_input_name])
+            .outputs(&["C"]);
+        let sess = session(&model(node, inputs, &[("C", ty)]));
+        let a = ArrayD::from_elem(IxDyn(&[3, 5]), 1f64);
+        let b = ArrayD::from_elem(IxDyn(&[3, 5]), 2f64);
+        let feed: Vec<_> = if second_input_missing {
+            vec![("A", a.clone().into())]
+        } else {
+            vec![("A", a.clone().into()), ("B", b.clone().into())]
+        };
+        let res = sess.run(&feed).unwrap();
+        let expected = if second_input_missing { a } else { a + b };
+        assert_eq!(res[0].to_array::<f64>().unwrap(), expected);
+    }
+}
+
+fn parse_datetime_model(fmt: Option<AttributeValue>) -> Model {
+    let mut node = Node::new(DOMAIN, "ParseDateTime")
+        .inputs(&["A"])
+        .outputs(&["B"]);
+    if let Some(fmt) = fmt {
+        node = node.attribute("fmt", fmt);
+    }
+    model(
+        node,
+        &[("A", ElementType::String)],
+        &[("B", ElementType::F64)],
+    )
+}
+
+fn parse_datetime_session() -> Session<'static> {
+    session(&parse_datetime_model(Some("%d.%m.%Y %H:%M %P %z".into())))
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn parse_datetime() {
+    let sess = parse_datetime_session();
+    let input = arr1(&["5.8.1994 8:00 am +0000", "5.8.2022 8:00 am +0000"]).into_dyn();
+    let res = sess.run(&[("A", input.into())]).unwrap();
+    assert_eq!(
+        res[0].to_array::<f64>().unwrap(),
+        arr1(&[776073600.0, 1659686400.0]).into_dyn()
+    );
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn parse_datetime_parallel() {
+    // Large enough to be split across the intra-op thread pool
+    let sess = parse_datetime_session();
+    let input = ArrayD::from_shape_fn(IxDyn(&[3, 3_000]), |idx| {
+        [
+            "5.8.1994 8:00 am +0000",
+            "invalid",
+            "5.8.2022 8:00 am +0000",
+        ][idx[1] % 3]
+    });
+    let res = sess.run(&[("A", input.into())]).unwrap();
+    let res = res[0].to_array::<f64>().unwrap();
+    assert_eq!(res.shape(), [3, 3_000]);
+    for (idx, value) in res.indexed_iter() {
+        match idx[1] % 3 {
+            0 => assert_eq!(*value, 776073600.0),
+            1 => assert!(value.is_nan()),
+            _ => assert_eq!(*value, 1659686400.0),
+        }
+    }
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn parse_datetime_invalid_fmt() {
+    // Missing attributes are recognized by the message of
+    // onnxruntime's error, which this checks, too
+    for (fmt, message) in [
+        (None, "ParseDateTime: Missing required attribute 'fmt'"),
+        (Some("".into()), "ParseDateTime: Invalid attribute 'fmt'"),
+        (
+            Some(1i64.into()),
+            "ParseDateTime: Failed to read attribute 'fmt'",
+        ),
+    ] {
+        let res = try_session(&parse_datetime_model(fmt));
+        assert_err_contains(res, message);
+    }
+}
+
+fn attr_showcase_model(u8_tensor: ArrayD<u8>) -> Model {
+    let node = Node::new(DOMAIN, "AttrShowcase")
+        .inputs(&["IN1", "IN2", "IN3"])
+        .outputs(&["OUT1", "OUT2", "OUT3"])
+        .attribute("float_attr", 1.5f32)
+        .attribute("int_attr", 42i64)
+        .attribute("string_attr", "bar")
+        .attribute("floats_attr", vec![1.5f32, 1.5])
+        .attribute("ints_attr", vec![42i64, 42])
+        .attribute("u8_tensor", Tensor::from(u8_tensor));
+    model(
+        node,
+        &[
+            ("IN1", ElementType::F32),
+            ("IN2", ElementType::I64),
+            ("IN3", ElementType::String),
+        ],
+        &[
+            ("OUT1", ElementType::F32),
+            ("OUT2", ElementType::I64),
+            ("OUT3", ElementType::String),
+        ],
+    )
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn attr_showcase() {
+    let sess = session(&attr_showcase_model(arr1(&[102u8, 111, 111]).into_dyn()));
+    let res = sess
+        .run(&[
+            ("IN1", arr1(&[0f32]).into_dyn().into()),
+            ("IN2", arr1(&[0i64]).into_dyn().into()),
+            ("IN3", arr1(&["foo"]).into_dyn().into()),
+        ])
+        .unwrap();
+    assert_eq!(
+        res[0].to_array::<f32>().unwrap(),
+        arr1(&[1.5f32]).into_dyn()
+    );
+    assert_eq!(res[1].to_array::<i64>().unwrap(), arr1(&[42]).into_dyn());
+    assert_eq!(
+        res[2].to_strings().unwrap(),
+        arr1(&["foo + bar".to_string()]).into_dyn()
+    );
+}
+
+/// Resident memory of this process in MiB.
+#[cfg(target_os = "linux")]
+fn resident_mib() -> f64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let kib: f64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    kib / 1024.0
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+#[cfg(target_os = "linux")]
+fn attr_showcase_no_leak() {
+    // A large tensor attribute makes a leak per kernel creation obvious
+    let attribute_mib = 1.0;
+    let model = attr_showcase_model(ArrayD::zeros(IxDyn(&[1 << 20])));
+
+    // Warm up allocator arenas and caches
+    for _ in 0..100 {
+        session(&model);
+    }
+    let n_sessions = 2_000;
+    let before = resident_mib();
+    for _ in 0..n_sessions {
+        session(&model);
+    }
+    // Leaking the attribute would amount to 2 GiB in total
+    let growth_per_session = (resident_mib() - before) / n_sessions as f64;
+    assert!(
+        growth_per_session < attribute_mib / 20.0,
+        "Grew by {growth_per_session} MiB per session"
+    );
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn custom_sum() {
+    let ty = ElementType::F32;
+    let node = Node::new(DOMAIN, "CustomSum")
+        .inputs(&["A", "B", "C"])
+        .outputs(&["D"]);
+    let sess = session(&model(
+        node,
+        &[("A", ty), ("B", ty), ("C", ty)],
+        &[("D", ty)],
+    ));
+    let ones = ArrayD::from_elem(IxDyn(&[3, 5]), 1f32);
+    let feed: Vec<_> = ["A", "B", "C"]
+        .into_iter()
+        .map(|name| (name, ones.clone().into()))
+        .collect();
+    let res = sess.run(&feed).unwrap();
+    assert_eq!(res[0].to_array::<f32>().unwrap(), ones * 3.0);
+}
+
+fn variadic_identity_session() -> Session<'static> {
+    let ty = ElementType::F32;
+    let node = Node::new(DOMAIN, "VariadicIdentity")
+        .inputs(&["A", "B"])
+        .outputs(&["C", "D"]);
+    session(&model(
+        node,
+        &[("A", ty), ("B", ty)],
+        &[("C", ty), ("D", ty)],
+    ))
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn variadic_identity() {
+    let sess = variadic_identity_session();
+    let a: Tensor = arr1(&[0f32]).into_dyn().into();
+    let b: Tensor = arr1(&[1f32]).into_dyn().into();
+    let res = sess.run(&[("A", a.clone()), ("B", b.clone())]).unwrap();
+    assert_eq!(res, [a, b]);
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn concat_sequence() {
+    // The sequence is built inside the graph from two tensor inputs
+    let ty = ElementType::I64;
+    let model = Model::new()
+        .opset("", 18)
+        .opset(DOMAIN, 1)
+        .node(
+            Node::new("", "SequenceConstruct")
+                .inputs(&["A", "B"])
+                .outputs(&["S"]),
+        )
+        .node(
+            Node::new(DOMAIN, "ConcatSequence")
+                .inputs(&["S"])
+                .outputs(&["C"]),
+        )
+        .input("A", ty, Some(&[Dim::Unknown, Dim::Unknown]))
+        .input("B", ty, Some(&[Dim::Unknown]))
+        .output("C", ty, Some(&[Dim::Unknown]));
+    let sess = session(&model);
+    let res = sess
+        .run(&[
+            ("A", arr2(&[[1i64, 2], [3, 4]]).into_dyn().into()),
+            ("B", arr1(&[5i64, 6, 7]).into_dyn().into()),
+        ])
+        .unwrap();
+    assert_eq!(
+        res[0].to_array::<i64>().unwrap(),
+        arr1(&[1, 2, 3, 4, 5, 6, 7]).into_dyn()
+    );
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn map_arg_max() {
+    let model = Model::new()
+        .opset(DOMAIN, 1)
+        .node(
+            Node::new(DOMAIN, "MapArgMax")
+                .inputs(&["A"])
+                .outputs(&["B"]),
+        )
+        .map_input("A", ElementType::String, ElementType::F32)
+        .output("B", ElementType::String, Some(&[]));
+    let sess = session(&model);
+    let keys = Tensor::from(arr1(&["cat", "dog", "bird"]).into_dyn());
+    let values = Tensor::from(arr1(&[0.2f32, 0.7, 0.1]).into_dyn());
+    let res = sess
+        .run_inputs(&[("A", Input::Map { keys, values })])
+        .unwrap();
+    assert_eq!(
+        res[0].to_strings().unwrap(),
+        arr0("dog".to_string()).into_dyn()
+    );
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn fill_nan() {
+    let ty = ElementType::F32;
+    let a: Tensor = arr1(&[1.0, f32::NAN, 3.0]).into_dyn().into();
+
+    let node = Node::new(DOMAIN, "FillNan")
+        .inputs(&["A"])
+        .outputs(&["B", "MASK"]);
+    let model_with_mask = model(
+        node,
+        &[("A", ty)],
+        &[("B", ty), ("MASK", ElementType::Bool)],
+    );
+    let sess = session(&model_with_mask);
+    let res = sess.run(&[("A", a.clone())]).unwrap();
+    assert_eq!(
+        res[0].to_array::<f32>().unwrap(),
+        arr1(&[1.0, 0.0, 3.0]).into_dyn()
+    );
+    assert_eq!(
+        res[1].to_array::<bool>().unwrap(),
+        arr1(&[false, true, false]).into_dyn()
+    );
+
+    let node = Node::new(DOMAIN, "FillNan").inputs(&["A"]).outputs(&["B"]);
+    let sess = session(&model(node, &[("A", ty)], &[("B", ty)]));
+    let res = sess.run(&[("A", a)]).unwrap();
+    assert_eq!(
+        res[0].to_array::<f32>().unwrap(),
+        arr1(&[1.0, 0.0, 3.0]).into_dyn()
+    );
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn concat_to_string() {
+    let node = Node::new(DOMAIN, "ConcatToString")
+        .inputs(&["A", "B", "C"])
+        .outputs(&["D"]);
+    let model = model(
+        node,
+        &[
+            ("A", ElementType::I64),
+            ("B", ElementType::Bool),
+            ("C", ElementType::String),
+        ],
+        &[("D", ElementType::String)],
+    );
+    let sess = session(&model);
+    let res = sess
+        .run(&[
+            ("A", arr1(&[1i64, 2]).into_dyn().into()),
+            ("B", arr1(&[true]).into_dyn().into()),
+            ("C", arr1(&["foo"]).into_dyn().into()),
+        ])
+        .unwrap();
+    assert_eq!(
+        res[0].to_strings().unwrap(),
+        arr1(&["1", "2", "true", "foo"])
+            .mapv(String::from)
+            .into_dyn()
+    );
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn versioned_op() {
+    for (domain_version, factor) in [(1, 2.0), (2, 3.0), (5, 3.0)] {
+        let ty = ElementType::F32;
+        let model = Model::new()
+            .opset(DOMAIN, domain_version)
+            .node(Node::new(DOMAIN, "Scale").inputs(&["A"]).outputs(&["B"]))
+            .input("A", ty, None)
+            .output("B", ty, None);
+        let sess = session(&model);
+        let a = arr1(&[1f32, 2.0]).into_dyn();
+        let res = sess.run(&[("A", a.clone().into())]).unwrap();
+        assert_eq!(res[0].to_array::<f32>().unwrap(), a * factor);
+    }
+}
+
+fn fallible_model(with_attr: bool) -> Model {
+    let mut node = Node::new(DOMAIN, "FallibleOp")
+        .inputs(&["fail"])
+        .outputs(&["out"]);
+    if with_attr {
+        node = node.attribute("required_attr", 1i64);
+    }
+    model(
+        node,
+        &[("fail", ElementType::Bool)],
+        &[("out", ElementType::Bool)],
+    )
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn fail_create_kernel_missing_attr() {
+    let res = try_session(&fallible_model(false));
+    assert_err_contains(res, "FallibleOp:");
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn fail_compute() {
+    let sess = session(&fallible_model(true));
+    let res = sess.run(&[("fail", arr0(true).into_dyn().into())]);
+    assert_err_contains(res, "FallibleOp:");
+
+    // Don't fail depending on input
+    sess.run(&[("fail", arr0(false).into_dyn().into())])
+        .unwrap();
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn fallible_op_logs_to_session_logger() {
+    let sess = session(&fallible_model(true));
+    sess.run(&[("fail", arr0(true).into_dyn().into())])
+        .unwrap_err();
+
+    // Message logged via `ort_warn!` and the `log` crate respectively
+    let logs = sess.logs();
+    for expected in ["Failing due to non-zero input", "Input was"] {
+        assert!(
+            logs.iter().any(|(_, message)| message.contains(expected)),
+            "{expected:?} not in {logs:?}"
+        );
+    }
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn describe_node() {
+    let model = Model::new()
+        .opset(DOMAIN, 1)
+        .node(
+            Node::new(DOMAIN, "DescribeNode")
+                .name("describe")
+                .inputs(&["A"])
+                .outputs(&["B", "C"]),
+        )
+        .input(
+            "A",
+            ElementType::F32,
+            Some(&[Dim::Symbolic("N".into()), Dim::Fixed(3), Dim::Unknown]),
+        )
+        .output("B", ElementType::String, Some(&[Dim::Fixed(3)]))
+        .output("C", ElementType::I64, Some(&[Dim::Fixed(3)]));
+    let sess = session(&model);
+    let a = ArrayD::<f32>::zeros(IxDyn(&[2, 3, 4]));
+    let res = sess.run(&[("A", a.into())]).unwrap();
+    assert_eq!(
+        res[0].to_strings().unwrap(),
+        arr1(&["describe", "A", "B"]).mapv(String::from).into_dyn()
+    );
+    assert_eq!(
+        res[1].to_array::<i64>().unwrap(),
+        arr1(&[-1, 3, -1]).into_dyn()
+    );
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn lookup_index() {
+    for constant_vocabulary in [true, false] {
+        let vocabulary = arr1(&["a", "b", "c"]).into_dyn();
+        let node = Node::new(DOMAIN, "LookupIndex")
+            .inputs(&["queries", "vocabulary"])
+            .outputs(&["out"]);
+        let mut model = Model::new()
+            .opset(DOMAIN, 1)
+            .node(node)
+            .input("queries", ElementType::String, None)
+            .output("out", ElementType::I64, None);
+        model = if constant_vocabulary {
+            model.initializer("vocabulary", vocabulary.clone())
+        } else {
+            model.input("vocabulary", ElementType::String, None)
+        };
+        let sess = session(&model);
+        let mut feed = vec![("queries", arr1(&["c", "x", "a"]).into_dyn().into())];
+        if !constant_vocabulary {
+            feed.push(("vocabulary", vocabulary.into()));
+        }
+        let res = sess.run(&feed).unwrap();
+        assert_eq!(
+            res[0].to_array::<i64>().unwrap(),
+            arr1(&[2, -1, 0]).into_dyn()
+        );
+    }
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn decode_labels() {
+    let classes = Tensor::from(arr1(&["cat", "dog", "bird"]).into_dyn());
+    let node = Node::new(DOMAIN, "DecodeLabels")
+        .inputs(&["A"])
+        .outputs(&["B"])
+        .attribute("classes", classes);
+    let sess = session(&model(
+        node,
+        &[("A", ElementType::I64)],
+        &[("B", ElementType::String)],
+    ));
+    let res = sess
+        .run(&[("A", arr1(&[2i64, 0, 1, 0]).into_dyn().into())])
+        .unwrap();
+    assert_eq!(
+        res[0].to_strings().unwrap(),
+        arr1(&["bird", "cat", "dog", "cat"])
+            .mapv(String::from)
+            .into_dyn()
+    );
+
+    let res = sess.run(&[("A", arr1(&[3i64]).into_dyn().into())]);
+    assert_err_contains(res, "DecodeLabels:");
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn zero_size_input_numeric() {
+    let sess = variadic_identity_session();
+    let empty: Tensor = arr1::<f32>(&[]).into_dyn().into();
+    let res = sess
+        .run(&[("A", empty.clone()), ("B", empty.clone())])
+        .unwrap();
+    assert_eq!(res, [empty.clone(), empty]);
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn zero_size_input_strings() {
+    let sess = parse_datetime_session();
+    let res = sess
+        .run(&[("A", arr1::<&str>(&[]).into_dyn().into())])
+        .unwrap();
+    assert_eq!(res[0].to_array::<f64>().unwrap(), arr1(&[]).into_dyn());
+}
+
+fn add_in_place_session() -> Session<'static> {
+    let ty = ElementType::F32;
+    let node = Node::new(DOMAIN, "AddInPlace")
+        .inputs(&["A", "B"])
+        .outputs(&["C"]);
+    session(&model(node, &[("A", ty), ("B", ty)], &[("C", ty)]))
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn add_in_place() {
+    let sess = add_in_place_session();
+    let a = arr2(&[[1f32, 2.0, 3.0], [4.0, 5.0, 6.0]]).into_dyn();
+    let b = arr2(&[[0.5f32, 0.5, 0.5], [1.0, 1.0, 1.0]]).into_dyn();
+    let res = sess
+        .run(&[("A", a.clone().into()), ("B", b.clone().into())])
+        .unwrap();
+    assert_eq!(res[0].to_array::<f32>().unwrap(), a + b);
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn add_in_place_infers_output_shape() {
+    let ty = ElementType::F32;
+    let shape = [Dim::Symbolic("N".into()), Dim::Fixed(5)];
+    let model = Model::new()
+        .opset(DOMAIN, 1)
+        .node(
+            Node::new(DOMAIN, "AddInPlace")
+                .inputs(&["A", "B"])
+                .outputs(&["C"]),
+        )
+        .input("A", ty, Some(&shape))
+        .input("B", ty, Some(&shape))
+        // The output is declared without a shape
+        .output("C", ty, None);
+    let sess = session(&model);
+    assert_eq!(sess.output_type_info(0).unwrap().shape(), Some(&shape[..]));
+}
+
+#[test]
+#[cfg_attr(not(onnxruntime), ignore = "requires onnxruntime; set ORT_DYLIB_PATH")]
+fn add_in_place_shape_mismatch() {
+    let sess = add_in_place_session();
+    let res = sess.run(&[
+        ("A", ArrayD::<f32>::ones(IxDyn(&[3, 5])).into()),
+        ("B", ArrayD::<f32>::ones(IxDyn(&[5, 3])).into()),
+    ]);
+    assert_err_contains(res, "AddInPlace:");
+}
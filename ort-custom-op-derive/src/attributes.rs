@@ -0,0 +1,101 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{DeriveInput, Expr, ExprLit, Field, Lit, LitStr, Path};
+
+use crate::util::{crate_path, named_fields, private};
+
+/// Options given via `#[attr(...)]` on a field.
+#[derive(Default)]
+struct FieldOptions {
+    name: Option<LitStr>,
+    default: Option<Expr>,
+    validate: Option<Path>,
+}
+
+impl FieldOptions {
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let mut options = Self::default();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("attr")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    options.name = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("default") {
+                    options.default = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("validate") {
+                    options.validate = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("expected `name`, `default` or `validate`"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let krate = crate_path(&input)?;
+    let private = private(&krate);
+    let fields = named_fields(&input)?;
+
+    let inits = fields
+        .iter()
+        .map(|field| {
+            let options = FieldOptions::parse(field)?;
+            let ident = &field.ident;
+            let ty = &field.ty;
+            let name = match options.name {
+                Some(name) => name.value(),
+                None => ident.as_ref().expect("fields are named").to_string(),
+            };
+            let read = match options.default {
+                // String literals are converted so that `default = "..."`
+                // works for `String` fields.
+                Some(Expr::Lit(ExprLit {
+                    lit: Lit::Str(lit), ..
+                })) => quote_spanned! {ty.span()=>
+                    info.get_attribute::<::std::option::Option<#ty>>(#name)?
+                        .unwrap_or_else(|| ::std::convert::From::from(#lit))
+                },
+                Some(default) => quote_spanned! {ty.span()=>
+                    info.get_attribute::<::std::option::Option<#ty>>(#name)?
+                        .unwrap_or_else(|| #default)
+                },
+                None => quote_spanned! {ty.span()=>
+                    info.get_attribute::<#ty>(#name)?
+                },
+            };
+            let validate = options.validate.map(|validate| {
+                quote_spanned! {validate.span()=>
+                    if let ::std::result::Result::Err(err) = #validate(&value) {
+                        #private::anyhow::bail!("Invalid attribute '{}': {}", #name, err);
+                    }
+                }
+            });
+            Ok(quote! {
+                #ident: {
+                    let value: #ty = #read;
+                    #validate
+                    value
+                },
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::prelude::OpAttributes for #ident #ty_generics
+        #where_clause
+        {
+            fn from_kernel_info(
+                info: &#krate::prelude::KernelInfo,
+            ) -> #private::anyhow::Result<Self> {
+                ::std::result::Result::Ok(Self {
+                    #(#inits)*
+                })
+            }
+        }
+    })
+}
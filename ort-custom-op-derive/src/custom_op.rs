@@ -0,0 +1,63 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::ffi::CString;
+
+use syn::{ImplItem, ItemImpl, LitCStr, LitStr, Type, parse_quote};
+
+pub(crate) fn expand(args: TokenStream, mut item: ItemImpl) -> syn::Result<TokenStream> {
+    let mut name: Option<LitStr> = None;
+    if !args.is_empty() {
+        let parser = syn::meta::parser(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported argument; expected `name = \"...\"`"))
+            }
+        });
+        syn::parse::Parser::parse2(parser, args)?;
+    }
+    if item.trait_.is_none() {
+        return Err(syn::Error::new_spanned(
+            &item.self_ty,
+            "expected an `impl CustomOp for ...` block",
+        ));
+    }
+
+    let has_name = item.items.iter().any(|item| match item {
+        ImplItem::Const(c) => c.ident == "NAME",
+        _ => false,
+    });
+    if !has_name {
+        let name = match name {
+            Some(name) => name,
+            None => type_name(&item.self_ty)?,
+        };
+        let Ok(c_name) = CString::new(name.value()) else {
+            return Err(syn::Error::new_spanned(
+                &name,
+                "operator names must not contain nul bytes",
+            ));
+        };
+        let name = LitCStr::new(&c_name, name.span());
+        item.items.insert(
+            0,
+            parse_quote!(const NAME: &'static ::std::ffi::CStr = #name;),
+        );
+    }
+    Ok(quote!(#item))
+}
+
+/// Name of the implementing type without its path or generics.
+fn type_name(ty: &Type) -> syn::Result<LitStr> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            let ident = &path.path.segments.last().expect("non-empty path").ident;
+            Ok(LitStr::new(&ident.to_string(), ident.span()))
+        }
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "cannot infer the operator name from this type; use `#[custom_op(name = \"...\")]`",
+        )),
+    }
+}
@@ -0,0 +1,145 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{DeriveInput, GenericParam};
+
+use crate::util::{
+    crate_path, has_type_params, named_fields, private, split_variadic, where_clause,
+};
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let krate = crate_path(&input)?;
+    let private = private(&krate);
+    let fields = named_fields(&input)?;
+    let (positional, variadic) = split_variadic(&fields)?;
+
+    let mut lifetimes = input.generics.params.iter().filter_map(|p| match p {
+        GenericParam::Lifetime(lt) => Some(&lt.lifetime),
+        _ => None,
+    });
+    let lt = match (lifetimes.next(), lifetimes.next()) {
+        (Some(lt), None) => lt,
+        _ => {
+            let msg = "expected exactly one lifetime parameter for the borrowed input data";
+            // Empty generics have no span of their own
+            return Err(if input.generics.params.is_empty() {
+                syn::Error::new_spanned(&input.ident, msg)
+            } else {
+                syn::Error::new_spanned(&input.generics, msg)
+            });
+        }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+    let mut where_clause = where_clause(&input);
+    let n_positional = positional.len();
+    let is_variadic = variadic.is_some();
+
+    let element_types = positional
+        .iter()
+        .map(|f| &f.ty)
+        .chain(variadic.map(|(_, elem)| elem));
+    if has_type_params(&input) {
+        for ty in element_types.clone() {
+            where_clause
+                .predicates
+                .push(syn::parse2(quote_spanned! {ty.span()=>
+                    #ty: #private::Input<#lt> + #private::OnnxTensorDtype
+                })?);
+        }
+    }
+
+    let positional_inits = positional.iter().map(|f| {
+        let field = &f.ident;
+        let ty = &f.ty;
+        let name = field.as_ref().map(ToString::to_string);
+        quote_spanned! {ty.span()=>
+            #field: <#ty as #private::Input<#lt>>::try_from_value(iter.next().unwrap())
+                .map_err(|err| err.context(format!("Input '{}'", #name)))?,
+        }
+    });
+    let variadic_init = variadic.map(|(f, elem)| {
+        let field = &f.ident;
+        let name = field.as_ref().map(ToString::to_string);
+        quote_spanned! {elem.span()=>
+            #field: iter
+                .map(<#elem as #private::Input<#lt>>::try_from_value)
+                .collect::<#private::anyhow::Result<::std::vec::Vec<#elem>>>()
+                .map_err(|err| err.context(format!("Input '{}'", #name)))?,
+        }
+    });
+    let arity_check = if is_variadic {
+        quote! {
+            if values.len() < #n_positional {
+                #private::anyhow::bail!(
+                    "expected at least {} inputs; found {}", #n_positional, values.len()
+                )
+            }
+        }
+    } else {
+        quote! {
+            if values.len() != #n_positional {
+                #private::anyhow::bail!("expected {} inputs; found {}", #n_positional, values.len())
+            }
+        }
+    };
+    let n_types = n_positional + usize::from(is_variadic);
+    let dtypes = element_types
+        .map(|ty| quote_spanned!(ty.span()=> <#ty as #private::OnnxTensorDtype>::dtype_id()));
+    let characteristics = positional
+        .iter()
+        .map(|f| &f.ty)
+        .map(|ty| quote_spanned!(ty.span()=> <#ty as #private::Input<#lt>>::characteristic()));
+    let variadic_homogeneous = if let Some((_, elem)) = variadic {
+        quote_spanned!(elem.span()=>
+            ::std::option::Option::Some(<#elem as #private::Input<#lt>>::HOMOGENEOUS)
+        )
+    } else {
+        quote!(::std::option::Option::None)
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::prelude::Inputs<#lt> for #ident #ty_generics
+        #where_clause
+        {
+            const VARIADIC_IS_HOMOGENEOUS: ::std::option::Option<bool> = #variadic_homogeneous;
+            const NUM_POSITIONAL: usize = #n_positional;
+
+            fn try_from_values(
+                values: ::std::vec::Vec<::std::option::Option<#krate::prelude::Value<#lt>>>,
+            ) -> #private::anyhow::Result<Self> {
+                #arity_check
+                let mut iter = values.into_iter();
+                ::std::result::Result::Ok(Self {
+                    #(#positional_inits)*
+                    #variadic_init
+                })
+            }
+
+            fn tensor_data_type(
+                idx: usize,
+            ) -> ::std::option::Option<#private::ONNXTensorElementDataType> {
+                let dtypes: [
+                    ::std::option::Option<#private::ONNXTensorElementDataType>;
+                    #n_types
+                ] = [#(#dtypes),*];
+                dtypes[idx]
+            }
+
+            fn characteristic(index: usize) -> #private::OrtCustomOpInputOutputCharacteristic {
+                let characteristics: [
+                    #private::OrtCustomOpInputOutputCharacteristic;
+                    #n_positional
+                ] = [#(#characteristics),*];
+                if index < #n_positional {
+                    characteristics[index]
+                } else if #is_variadic {
+                    #private::OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_VARIADIC
+                } else {
+                    panic!("Provided index '{}' is out of range", index)
+                }
+            }
+        }
+    })
+}
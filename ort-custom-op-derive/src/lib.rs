@@ -0,0 +1,158 @@
+//! Derive macros for the `ort_custom_op` crate.
+//!
+//! The macros are re-exported from `ort_custom_op::prelude` if the
+//! `derive` feature of that crate is enabled. Depending on this crate
+//! directly is not necessary.
+//!
+//! The code generated by the derive macros refers to the crate as
+//! `::ort_custom_op`. If it is renamed in `Cargo.toml` or only
+//! reachable through a re-export, its path may be given using
+//! `#[ort_custom_op(crate = path)]` on the struct:
+//!
+//! ```
+//! # use ndarray::ArrayD;
+//! mod reexport {
+//!     pub use ort_custom_op as ort;
+//! }
+//!
+//! #[derive(reexport::ort::prelude::Outputs)]
+//! #[ort_custom_op(crate = reexport::ort)]
+//! struct SumOutputs {
+//!     sum: ArrayD<f32>,
+//! }
+//! ```
+use proc_macro::TokenStream;
+use syn::{DeriveInput, ItemImpl, parse_macro_input};
+
+mod attributes;
+mod custom_op;
+mod inputs;
+mod outputs;
+mod util;
+
+/// Derive `Inputs` for a struct with named fields.
+///
+/// Each field is one input of the operator, in declaration order. The
+/// last field may be a `Vec` of inputs to make the operator variadic.
+/// The struct must have exactly one lifetime parameter which is the
+/// lifetime of the borrowed input data. There is no limit on the
+/// number of fields.
+///
+/// ```
+/// # use ndarray::ArrayViewD;
+/// # use ort_custom_op::prelude::*;
+/// #[derive(Inputs)]
+/// struct SumInputs<'s> {
+///     /// First summand
+///     first: ArrayViewD<'s, f32>,
+///     /// Any number of further summands
+///     rest: Vec<ArrayViewD<'s, f32>>,
+/// }
+/// ```
+#[proc_macro_derive(Inputs, attributes(ort_custom_op))]
+pub fn derive_inputs(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    inputs::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `Outputs` for a struct with named fields.
+///
+/// Each field is one output of the operator, in declaration order.
+/// The last field may be a `Vec` of outputs to make the operator
+/// variadic. There is no limit on the number of fields.
+///
+/// ```
+/// # use ndarray::ArrayD;
+/// # use ort_custom_op::prelude::*;
+/// #[derive(Outputs)]
+/// struct SumOutputs {
+///     /// Element-wise sum of all inputs
+///     sum: ArrayD<f32>,
+/// }
+/// ```
+#[proc_macro_derive(Outputs, attributes(ort_custom_op))]
+pub fn derive_outputs(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    outputs::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `OpAttributes` for a struct with named fields.
+///
+/// Each field is read from the node attribute of the same name. The
+/// following options are available via `#[attr(...)]`:
+///
+/// - `name = "..."`: Name of the attribute if it differs from the field name.
+/// - `default = ...`: Value used if the attribute is missing.
+/// - `validate = path`: Function `fn(&T) -> Result<(), E>` with `E: Display`
+///   which is called with the read value.
+///
+/// Fields of type `Option<T>` are `None` if the attribute is missing.
+///
+/// ```
+/// # use ort_custom_op::prelude::*;
+/// fn non_empty(s: &String) -> Result<(), &'static str> {
+///     if s.is_empty() { Err("must not be empty") } else { Ok(()) }
+/// }
+///
+/// #[derive(OpAttributes)]
+/// struct Attrs {
+///     #[attr(name = "fmt", validate = non_empty)]
+///     format: String,
+///     #[attr(default = 1.0)]
+///     alpha: f32,
+///     axes: Option<Vec<i64>>,
+/// }
+/// ```
+#[proc_macro_derive(OpAttributes, attributes(attr, ort_custom_op))]
+pub fn derive_op_attributes(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    attributes::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Attribute for `impl CustomOp for ...` blocks which defines
+/// `CustomOp::NAME` from the name of the implementing type.
+///
+/// The name may be set explicitly using `#[custom_op(name = "...")]`.
+/// An existing `NAME` in the impl block is left untouched.
+///
+/// ```
+/// # use std::convert::Infallible;
+/// # use ndarray::{ArrayD, ArrayViewD};
+/// # use ort_custom_op::prelude::*;
+/// struct Negate;
+///
+/// #[custom_op]
+/// impl CustomOp for Negate {
+///     type KernelCreateError = Infallible;
+///     type ComputeError = Infallible;
+///
+///     type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+///     type OpOutputs = (ArrayD<f32>,);
+///
+///     fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+///         Ok(Negate)
+///     }
+///
+///     fn kernel_compute(
+///         &self,
+///         (a,): Self::OpInputs<'_>,
+///     ) -> Result<Self::OpOutputs, Self::ComputeError> {
+///         Ok((-&a,))
+///     }
+/// }
+///
+/// assert_eq!(Negate::NAME, c"Negate");
+/// ```
+#[proc_macro_attribute]
+pub fn custom_op(args: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+    custom_op::expand(args.into(), item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,84 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::DeriveInput;
+use syn::spanned::Spanned;
+
+use crate::util::{
+    crate_path, has_type_params, named_fields, private, split_variadic, where_clause,
+};
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let krate = crate_path(&input)?;
+    let private = private(&krate);
+    let fields = named_fields(&input)?;
+    // The last field is written via `LastOutput` whether it is variadic
+    // or not.
+    let (mut positional, variadic) = split_variadic(&fields)?;
+    let last = match variadic {
+        Some((field, _)) => field,
+        None => positional.pop().expect("fields are non-empty"),
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+    let mut where_clause = where_clause(&input);
+    let last_ty = &last.ty;
+    if has_type_params(&input) {
+        for field in &positional {
+            let ty = &field.ty;
+            where_clause.predicates.push(syn::parse2(
+                quote_spanned! {ty.span()=> #ty: #private::Output},
+            )?);
+        }
+        where_clause.predicates.push(syn::parse2(
+            quote_spanned! {last_ty.span()=> #last_ty: #private::LastOutput},
+        )?);
+    }
+
+    let last_idx = positional.len();
+    let characteristics = positional
+        .iter()
+        .map(|f| &f.ty)
+        .map(|ty| quote_spanned!(ty.span()=> <#ty as #private::Output>::CHARACTERISTIC));
+    let output_types = positional
+        .iter()
+        .map(|f| &f.ty)
+        .map(|ty| quote_spanned!(ty.span()=> <#ty as #private::Output>::OUTPUT_TYPE));
+    let writes = positional.iter().enumerate().map(|(idx, f)| {
+        let field = &f.ident;
+        let ty = &f.ty;
+        quote_spanned!(ty.span()=> <#ty as #private::Output>::write_to_ort(self.#field, api, ctx, #idx)?;)
+    });
+    let last_field = &last.ident;
+    let last_characteristic =
+        quote_spanned!(last_ty.span()=> <#last_ty as #private::LastOutput>::CHARACTERISTIC);
+
+    Ok(quote! {
+        impl #impl_generics #krate::prelude::Outputs for #ident #ty_generics
+        #where_clause
+        {
+            const CHARACTERISTICS: &'static [#private::OrtCustomOpInputOutputCharacteristic] = &[
+                #(#characteristics,)*
+                #last_characteristic
+            ];
+            const VARIADIC_MIN_ARITY: usize =
+                <#last_ty as #private::LastOutput>::VARIADIC_MIN_ARITY;
+            const VARIADIC_IS_HOMOGENEOUS: bool =
+                <#last_ty as #private::LastOutput>::VARIADIC_IS_HOMOGENEOUS;
+
+            const OUTPUT_TYPES: &'static [#private::ElementType] = &[
+                #(#output_types,)*
+                <#last_ty as #private::LastOutput>::OUTPUT_TYPE
+            ];
+
+            fn write_to_ort(
+                self,
+                api: &#private::OrtApi,
+                ctx: &mut #private::OrtKernelContext,
+            ) -> #private::anyhow::Result<()> {
+                #(#writes)*
+                <#last_ty as #private::LastOutput>::write_to_ort(self.#last_field, api, ctx, #last_idx)
+            }
+        }
+    })
+}
@@ -0,0 +1,122 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    Data, DeriveInput, Field, Fields, GenericArgument, Path, PathArguments, Type, WhereClause,
+    parse_quote,
+};
+
+/// Path to the `ort_custom_op` crate used by generated code.
+///
+/// Defaults to `::ort_custom_op` and may be overridden using
+/// `#[ort_custom_op(crate = path)]` if the crate is renamed or only
+/// available through a re-export.
+pub(crate) fn crate_path(input: &DeriveInput) -> syn::Result<Path> {
+    let mut path = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("ort_custom_op"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                path = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `crate`"))
+            }
+        })?;
+    }
+    Ok(path.unwrap_or_else(|| parse_quote!(::ort_custom_op)))
+}
+
+/// Path to the hidden module of `ort_custom_op` used by generated code.
+pub(crate) fn private(krate: &Path) -> TokenStream {
+    quote!(#krate::__private)
+}
+
+/// Named fields of a struct in declaration order.
+pub(crate) fn named_fields(input: &DeriveInput) -> syn::Result<Vec<&Field>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &data.fields,
+                    "expected a struct with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "expected a struct with named fields",
+            ));
+        }
+    };
+    if fields.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "expected at least one field",
+        ));
+    }
+    Ok(fields)
+}
+
+/// Element type `T` if `ty` is syntactically a `Vec<T>`.
+pub(crate) fn vec_element(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Vec" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first() {
+        Some(GenericArgument::Type(ty)) if args.args.len() == 1 => Some(ty),
+        _ => None,
+    }
+}
+
+/// Variadic field along with its element type.
+pub(crate) type Variadic<'a> = (&'a Field, &'a Type);
+
+/// Split `fields` into positional fields and a trailing variadic
+/// field.
+pub(crate) fn split_variadic<'a>(
+    fields: &[&'a Field],
+) -> syn::Result<(Vec<&'a Field>, Option<Variadic<'a>>)> {
+    let (last, positional) = fields.split_last().expect("fields are non-empty");
+    for field in positional {
+        if vec_element(&field.ty).is_some() {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "only the last field may be variadic",
+            ));
+        }
+    }
+    Ok(match vec_element(&last.ty) {
+        Some(elem) => (positional.to_vec(), Some((*last, elem))),
+        None => (fields.to_vec(), None),
+    })
+}
+
+/// Does `input` have any type parameters?
+///
+/// Bounds on the field types are only added for generic structs.
+/// Otherwise, unsupported field types are reported directly at the
+/// offending field rather than where the struct is used.
+pub(crate) fn has_type_params(input: &DeriveInput) -> bool {
+    input.generics.type_params().next().is_some()
+}
+
+/// Where clause of `input` or an empty one.
+pub(crate) fn where_clause(input: &DeriveInput) -> WhereClause {
+    input
+        .generics
+        .where_clause
+        .clone()
+        .unwrap_or_else(|| parse_quote!(where))
+}
@@ -0,0 +1,9 @@
+use ort_custom_op::prelude::*;
+
+#[derive(OpAttributes)]
+struct Attrs {
+    #[attr(rename = "a")]
+    alpha: f32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use ort_custom_op::prelude::*;
+
+#[derive(OpAttributes)]
+struct Attrs {
+    alpha: f32,
+    beta: std::time::Duration,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use ort_custom_op::prelude::*;
+
+struct MyOp;
+
+#[custom_op]
+impl MyOp {}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use ndarray::ArrayD;
+use ort_custom_op::prelude::*;
+
+#[derive(Outputs)]
+#[ort_custom_op(path = ort_custom_op)]
+struct Outs {
+    a: ArrayD<f32>,
+}
+
+fn main() {}
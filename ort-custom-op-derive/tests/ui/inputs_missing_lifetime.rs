@@ -0,0 +1,9 @@
+use ndarray::ArrayD;
+use ort_custom_op::prelude::*;
+
+#[derive(Inputs)]
+struct MyInputs {
+    a: ArrayD<f32>,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use ndarray::ArrayViewD;
+use ort_custom_op::prelude::*;
+
+#[derive(Inputs)]
+struct MyInputs<'a, 'b> {
+    a: ArrayViewD<'a, f32>,
+    b: ArrayViewD<'b, f32>,
+}
+
+fn main() {}
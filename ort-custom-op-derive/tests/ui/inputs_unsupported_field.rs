@@ -0,0 +1,10 @@
+use ndarray::ArrayViewD;
+use ort_custom_op::prelude::*;
+
+#[derive(Inputs)]
+struct MyInputs<'s> {
+    a: ArrayViewD<'s, f32>,
+    b: f32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use ndarray::ArrayViewD;
+use ort_custom_op::prelude::*;
+
+#[derive(Inputs)]
+struct MyInputs<'s> {
+    rest: Vec<ArrayViewD<'s, f32>>,
+    last: ArrayViewD<'s, f32>,
+}
+
+fn main() {}
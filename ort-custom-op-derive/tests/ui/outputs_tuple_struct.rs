@@ -0,0 +1,7 @@
+use ndarray::ArrayD;
+use ort_custom_op::prelude::*;
+
+#[derive(Outputs)]
+struct MyOutputs(ArrayD<f32>);
+
+fn main() {}
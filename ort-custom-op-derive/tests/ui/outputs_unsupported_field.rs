@@ -0,0 +1,10 @@
+use ndarray::ArrayD;
+use ort_custom_op::prelude::*;
+
+#[derive(Outputs)]
+struct MyOutputs {
+    a: ArrayD<f32>,
+    b: f32,
+}
+
+fn main() {}
@@ -1,14 +1,121 @@
-use std::ffi::{CString, c_char};
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::mem::ManuallyDrop;
+use std::ptr::NonNull;
+use std::sync::OnceLock;
 
-use anyhow::{Result, bail};
+use anyhow::{Result, anyhow, bail};
 use ndarray::{ArrayD, ArrayViewD, ArrayViewMut, ArrayViewMutD};
 
+use crate::attributes::Attribute;
 use crate::bindings::*;
 use crate::error::ErrorStatus;
 use crate::inputs::Input;
-use crate::value::{BufferMaybeOwned, ValueBuffer};
+use crate::logger::Logger;
+use crate::shape_inference::Dim;
+use crate::value::{BufferMaybeOwned, InputBuffer, ValueBuffer};
+
+/// Latest version of the onnxruntime C API used by this crate.
+pub const API_VERSION: u32 = 17;
+/// Oldest version of the onnxruntime C API supported by this crate.
+pub const MIN_API_VERSION: u32 = 16;
+
+/// API negotiated with onnxruntime when registering the operators and
+/// its version. Some callbacks such as the shape inference are not
+/// passed the API by onnxruntime.
+static REGISTERED_API: OnceLock<(&'static OrtApi, u32)> = OnceLock::new();
+
+#[cfg(feature = "testing")]
+thread_local! {
+    /// Fake API of [`crate::testing`] which stands in for the
+    /// registered API on this thread.
+    static TESTING_API: std::cell::Cell<Option<(&'static OrtApi, u32)>> =
+        const { std::cell::Cell::new(None) };
+}
+
+fn registered() -> Option<(&'static OrtApi, u32)> {
+    #[cfg(feature = "testing")]
+    if let Some(registered) = TESTING_API.get() {
+        return Some(registered);
+    }
+    REGISTERED_API.get().copied()
+}
+
+/// Run `f` as if `api` of `version` had been registered.
+#[cfg(feature = "testing")]
+pub(crate) fn with_testing_api<R>(api: &'static OrtApi, version: u32, f: impl FnOnce() -> R) -> R {
+    struct Reset(Option<(&'static OrtApi, u32)>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            TESTING_API.set(self.0);
+        }
+    }
+
+    let _reset = Reset(TESTING_API.replace(Some((api, version))));
+    f()
+}
+
+/// API which was used to register custom operators, if any.
+pub(crate) fn registered_api() -> Option<&'static OrtApi> {
+    registered().map(|(api, _)| api)
+}
+
+/// Version of the onnxruntime C API negotiated when registering the
+/// operators, if any.
+///
+/// It is the latest version between [`MIN_API_VERSION`] and
+/// [`API_VERSION`] which is provided by the runtime.
+pub fn api_version() -> Option<u32> {
+    registered().map(|(_, version)| version)
+}
+
+/// Does the negotiated API provide the functions introduced in
+/// `version`?
+pub(crate) fn supports_api_version(version: u32) -> bool {
+    api_version().is_some_and(|negotiated| negotiated >= version)
+}
+
+/// Get the latest API between [`MIN_API_VERSION`] and [`API_VERSION`]
+/// provided by the runtime.
+///
+/// onnxruntime prints an error when asked for a version of the API it
+/// does not provide. onnxruntime 1.x provides the versions up to x,
+/// hence only these are requested if the runtime reports its version.
+pub(crate) fn negotiate_api(api_base: &OrtApiBase) -> Option<(&'static OrtApi, u32)> {
+    let get_api = api_base.GetApi?;
+    let latest = runtime_api_version(api_base).map_or(API_VERSION, |v| v.min(API_VERSION));
+    (MIN_API_VERSION..=latest)
+        .rev()
+        .find_map(|version| unsafe { get_api(version).as_ref() }.map(|api| (api, version)))
+}
+
+/// Latest version of the API provided by the runtime according to its
+/// version string such as `"1.16.3"`.
+fn runtime_api_version(api_base: &OrtApiBase) -> Option<u32> {
+    let version = unsafe { api_base.GetVersionString?().as_ref() }?;
+    let version = unsafe { CStr::from_ptr(version) }.to_str().ok()?;
+    match version.split('.').collect::<Vec<_>>()[..] {
+        ["1", minor, ..] => minor.parse().ok(),
+        _ => None,
+    }
+}
 
-pub const API_VERSION: u32 = 16;
+/// Negotiate the API with the runtime (see [`api_version`]) and keep
+/// it for the callbacks which are not passed the API.
+pub(crate) fn init_api(api_base: &OrtApiBase) -> Result<(&'static OrtApi, u32)> {
+    let Some((api, version)) = negotiate_api(api_base) else {
+        bail!(
+            "onnxruntime does not provide any version of the C API between {} and {}; onnxruntime 1.{} or later is required",
+            MIN_API_VERSION,
+            API_VERSION,
+            MIN_API_VERSION
+        )
+    };
+    let &(api, version) = REGISTERED_API.get_or_init(|| (api, version));
+    #[cfg(feature = "log")]
+    crate::logger::install_log_backend();
+    Ok((api, version))
+}
 
 #[derive(Debug)]
 pub struct KernelInfo<'s> {
@@ -16,6 +123,12 @@ pub struct KernelInfo<'s> {
     info: &'s OrtKernelInfo,
 }
 
+/// Element type of a tensor.
+///
+/// Further variants are available with the `half` feature and may be
+/// added in the future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ElementType {
     Bool,
     F32,
@@ -29,42 +142,84 @@ pub enum ElementType {
     U32,
     U64,
     String,
+    #[cfg(feature = "half")]
+    F16,
+    #[cfg(feature = "half")]
+    BF16,
 }
 
-macro_rules! bail_non_null {
-    ($ptr:expr) => {
-        if !$ptr.is_null() {
-            return $ptr;
+/// Type of an input or output of a node as known when creating the
+/// kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeInfo {
+    /// Tensor with the given element type and shape. Dimensions which
+    /// are not static are [`Dim::Symbolic`] or [`Dim::Unknown`].
+    Tensor {
+        element_type: ElementType,
+        shape: Vec<Dim>,
+    },
+    /// Any other type such as a sequence or a map.
+    NonTensor,
+}
+
+impl TypeInfo {
+    /// Shape of a tensor; `None` for other types.
+    pub fn shape(&self) -> Option<&[Dim]> {
+        match self {
+            TypeInfo::Tensor { shape, .. } => Some(shape),
+            TypeInfo::NonTensor => None,
         }
-    };
+    }
 }
 
-/// Create a new custom domain with the operators `ops`.
-pub fn create_custom_op_domain(
-    session_options: &mut OrtSessionOptions,
-    api_base: &mut OrtApiBase,
-    domain: &str,
-    ops: &[&'static OrtCustomOp],
-) -> OrtStatusPtr {
-    let api = unsafe { api_base.GetApi.unwrap()(API_VERSION).as_ref().unwrap() };
-
-    let fun_ptr = api.CreateCustomOpDomain.unwrap();
-    let mut domain_ptr: *mut OrtCustomOpDomain = std::ptr::null_mut();
-
-    // Copies and leaks!
-    let c_op_domain = CString::new(domain).unwrap().into_raw();
-    let domain = unsafe {
-        // Create domain
-        // According to docs: "Must be freed with OrtApi::ReleaseCustomOpDomain"
-        bail_non_null!(fun_ptr(c_op_domain, &mut domain_ptr));
-        domain_ptr.as_mut().unwrap()
-    };
-    // Add ops to domain
-    for op in ops {
-        bail_non_null!(add_op_to_domain(api, domain, op));
+/// Owned `OrtValue` which is released when dropped.
+#[derive(Debug)]
+pub(crate) struct OwnedValue<'s> {
+    api: &'s OrtApi,
+    value: NonNull<OrtValue>,
+}
+
+impl<'s> OwnedValue<'s> {
+    /// Take ownership of `value`. Returns an error if `value` is null.
+    ///
+    /// # Safety
+    ///
+    /// `value` must have been handed out by onnxruntime and must be
+    /// released with `OrtApi::ReleaseValue`.
+    pub(crate) unsafe fn from_raw(api: &'s OrtApi, value: *mut OrtValue) -> Result<Self> {
+        let Some(value) = NonNull::new(value) else {
+            bail!("onnxruntime returned a null value");
+        };
+        Ok(Self { api, value })
+    }
+
+    /// Mutable reference to the underlying value.
+    #[cfg(feature = "testing-runtime")]
+    pub(crate) fn as_mut(&mut self) -> &mut OrtValue {
+        unsafe { self.value.as_mut() }
+    }
+
+    /// Shared reference to the underlying value.
+    pub(crate) fn as_ref(&self) -> &OrtValue {
+        unsafe { self.value.as_ref() }
+    }
+
+    /// Shared reference to the underlying value which is not tied to
+    /// `self`.
+    ///
+    /// # Safety
+    ///
+    /// The returned reference must not outlive `self`. The value is
+    /// allocated by onnxruntime and does not move if `self` is moved.
+    pub(crate) unsafe fn as_ref_unbound<'a>(&self) -> &'a OrtValue {
+        unsafe { &*self.value.as_ptr() }
+    }
+}
+
+impl Drop for OwnedValue<'_> {
+    fn drop(&mut self) {
+        unsafe { self.api.ReleaseValue.unwrap()(self.value.as_ptr()) }
     }
-    // Add domain to session options
-    unsafe { api.AddCustomOpDomain.unwrap()(session_options, domain_ptr) }
 }
 
 /// Explicit struct around OrtTypeAndShapeInfo pointer since we are
@@ -99,10 +254,67 @@ impl OrtValue {
         Ok(num)
     }
 
+    /// Number of elements if this value is a sequence or map.
+    fn get_value_count(&self, api: &OrtApi) -> Result<usize> {
+        let fun = api.GetValueCount.unwrap();
+        let mut out = 0;
+        api.status_to_result(unsafe { fun(self, &mut out) })?;
+        Ok(out)
+    }
+
+    /// Get the element at `index` of a sequence or the keys (`index
+    /// == 0`) or values (`index == 1`) of a map.
+    fn get_value<'a>(&self, api: &'a OrtApi, index: usize) -> Result<OwnedValue<'a>> {
+        let mut allocator: *mut OrtAllocator = std::ptr::null_mut();
+        api.status_to_result(unsafe {
+            api.GetAllocatorWithDefaultOptions.unwrap()(&mut allocator)
+        })?;
+
+        let fun = api.GetValue.unwrap();
+        let mut out: *mut OrtValue = std::ptr::null_mut();
+        api.status_to_result(unsafe { fun(self, index as _, allocator, &mut out) })?;
+        unsafe { OwnedValue::from_raw(api, out) }
+    }
+
+    /// Load a tensor along with its type and shape.
+    unsafe fn load_tensor<'s>(
+        &'s self,
+        api: &OrtApi,
+    ) -> Result<ValueBuffer<BufferMaybeOwned<'s>, Vec<usize>>> {
+        let (dtype, shape) = {
+            let info = self.get_tensor_type_and_shape(api)?;
+            (info.get_element_type()?, info.shape()?)
+        };
+        // Unsafe invariant: dtype must match value
+        unsafe { self.load_tensor_buffer(api, dtype, shape) }
+    }
+
+    /// Load the tensor at `index` of a sequence or map (see
+    /// [`Self::get_value`]). The element is pushed to `owned`.
+    ///
+    /// # Safety
+    ///
+    /// The returned buffer borrows from the last element of `owned`
+    /// and must be dropped before it.
+    unsafe fn load_tensor_element<'s>(
+        &self,
+        api: &'s OrtApi,
+        index: usize,
+        owned: &mut Vec<OwnedValue<'s>>,
+    ) -> Result<ValueBuffer<BufferMaybeOwned<'s>, Vec<usize>>> {
+        let element = self.get_value(api, index)?;
+        let element_ref = unsafe { element.as_ref_unbound() };
+        if element_ref.onnx_type(api)? != ONNXType_ONNX_TYPE_TENSOR {
+            bail!("Element '{}' is not a tensor", index)
+        }
+        owned.push(element);
+        unsafe { element_ref.load_tensor(api) }
+    }
+
     /// Load tensor buffer data. It is the callers responsibility that
     /// the `dtype` matches the loaded data.
     unsafe fn load_tensor_buffer<'s>(
-        &'s mut self,
+        &'s self,
         api: &OrtApi,
         dtype: ElementType,
         shape: Vec<usize>,
@@ -120,8 +332,29 @@ impl OrtValue {
         Ok(ArrayViewMut::from(data).into_shape(shape.as_slice())?)
     }
 
+    /// Get slice for this Value. This function does not validate the type.
+    pub(crate) unsafe fn get_data<'s, T>(&'s self, api: &OrtApi) -> Result<&'s [T]> {
+        let (ptr, element_count) = unsafe { self.get_data_ptr(api)? };
+        if element_count == 0 {
+            // Zero-sized tensor
+            return Ok(&[]);
+        }
+        Ok(unsafe { std::slice::from_raw_parts(ptr as *const T, element_count) })
+    }
+
     /// Get mutable slice for this Value. This function does not validate the type.
     pub(crate) unsafe fn get_data_mut<'s, T>(&'s mut self, api: &OrtApi) -> Result<&'s mut [T]> {
+        let (ptr, element_count) = unsafe { self.get_data_ptr(api)? };
+        if element_count == 0 {
+            // Zero-sized tensor
+            return Ok([].as_mut_slice());
+        }
+        Ok(unsafe { std::slice::from_raw_parts_mut(ptr as *mut T, element_count) })
+    }
+
+    /// Pointer to the data of this tensor along with its number of
+    /// elements. The pointer is null for zero-sized tensors.
+    unsafe fn get_data_ptr(&self, api: &OrtApi) -> Result<(*mut c_void, usize)> {
         if self.onnx_type(api)? != ONNXType_ONNX_TYPE_TENSOR {
             bail!("OrtValue is not a tensor")
         }
@@ -130,19 +363,17 @@ impl OrtValue {
             info.get_tensor_shape_element_count()?
         };
         if element_count == 0 {
-            // Zero-sized tensor
-            return Ok([].as_mut_slice());
+            return Ok((std::ptr::null_mut(), 0));
         }
+        // The C API only provides a mutable getter. Whether the data
+        // may be written to is up to the caller.
         let fun = api.GetTensorMutableData.unwrap();
-        let mut ptr: *mut _ = std::ptr::null_mut();
-        let data = unsafe {
-            fun(self, &mut ptr);
-            std::slice::from_raw_parts_mut(ptr as *mut T, element_count)
-        };
-        Ok(data)
+        let mut ptr = std::ptr::null_mut();
+        api.status_to_result(unsafe { fun(self as *const _ as *mut _, &mut ptr) })?;
+        Ok((ptr, element_count))
     }
 
-    fn get_tensor_type_and_shape<'s>(
+    pub(crate) fn get_tensor_type_and_shape<'s>(
         &'s self,
         api: &'s OrtApi,
     ) -> Result<TensorTypeAndShapeInfo<'s>> {
@@ -195,11 +426,19 @@ impl OrtValue {
 }
 
 impl OrtKernelContext {
+    /// Logger of the current run.
+    pub(crate) fn get_logger(&self, api: &'static OrtApi) -> Result<Logger> {
+        let mut logger = std::ptr::null();
+        let status = unsafe { api.KernelContext_GetLogger.unwrap()(self, &mut logger) };
+        api.status_to_result(status)?;
+        unsafe { Logger::from_raw(api, logger) }
+    }
+
     #[allow(non_upper_case_globals)]
     pub(crate) fn get_input_values<'s>(
         &'s self,
-        api: &OrtApi,
-    ) -> Result<Vec<Option<ValueBuffer<BufferMaybeOwned<'s>, Vec<usize>>>>> {
+        api: &'s OrtApi,
+    ) -> Result<Vec<Option<InputBuffer<'s>>>> {
         let n_inputs = self.get_input_count(api)?;
         let mut inputs = Vec::with_capacity(n_inputs);
         for idx in 0..n_inputs {
@@ -208,6 +447,8 @@ impl OrtKernelContext {
         Ok(inputs)
     }
 
+    /// Write `array` to the output `index` unless the output does not
+    /// exist (see [`Self::get_output`]).
     pub(crate) fn fill_string_tensor(
         &mut self,
         api: &OrtApi,
@@ -224,7 +465,9 @@ impl OrtKernelContext {
         let vec_of_ptrs = pointers.into_raw_vec();
         let ptr_of_ptrs = vec_of_ptrs.as_ptr();
         let n_items = array.len();
-        let val = unsafe { self.get_output(api, index, &shape_i64) }?;
+        let Some(val) = unsafe { self.get_output(api, index, &shape_i64) }? else {
+            return Ok(());
+        };
 
         let fun = api.FillStringTensor.unwrap();
         api.status_to_result(unsafe { fun(val, ptr_of_ptrs, n_items) })?;
@@ -238,30 +481,42 @@ impl OrtKernelContext {
         Ok(out)
     }
 
+    pub(crate) fn get_output_count(&self, api: &OrtApi) -> Result<usize> {
+        let fun = api.KernelContext_GetOutputCount.unwrap();
+        let mut out: usize = 0;
+        api.status_to_result(unsafe { fun(self, &mut out) })?;
+        Ok(out)
+    }
+
     /// Get `OrtValue` for output with index `idx`.
+    ///
+    /// Returns `None` if the output does not exist, which is the case
+    /// for optional outputs which are not consumed by the graph.
     pub(crate) unsafe fn get_output<'s>(
         &'s mut self,
         api: &OrtApi,
         idx: usize,
         shape: &[i64],
-    ) -> Result<&'s mut OrtValue> {
+    ) -> Result<Option<&'s mut OrtValue>> {
+        if idx >= self.get_output_count(api)? {
+            return Ok(None);
+        }
         let fun = api.KernelContext_GetOutput.unwrap();
 
         let mut value: *mut OrtValue = std::ptr::null_mut();
         api.status_to_result(unsafe { fun(self, idx, shape.as_ptr(), shape.len(), &mut value) })?;
-        match unsafe { value.as_mut() } {
-            None => anyhow::bail!("failed to get input"),
-            Some(r) => Ok(r),
-        }
+        // A successful status with a null pointer means that the
+        // output is not consumed.
+        Ok(unsafe { value.as_mut() })
     }
 
     /// Get `OrtValue` for input with index `idx`.
     #[allow(non_upper_case_globals)]
     fn get_input_value<'s>(
         &'s self,
-        api: &OrtApi,
+        api: &'s OrtApi,
         idx: usize,
-    ) -> Result<Option<ValueBuffer<BufferMaybeOwned<'s>, Vec<usize>>>> {
+    ) -> Result<Option<InputBuffer<'s>>> {
         let fun = api.KernelContext_GetInput.unwrap();
 
         let mut value: *const OrtValue = std::ptr::null();
@@ -272,21 +527,39 @@ impl OrtKernelContext {
             // an optional input.
             return Ok(None);
         }
-        // Code crime!
-        let value = unsafe { &mut *(value as *mut OrtValue) };
+        // The input is owned by onnxruntime and only read
+        let value = unsafe { &*value };
         #[allow(non_upper_case_globals)]
         match value.onnx_type(api)? {
             ONNXType_ONNX_TYPE_TENSOR => {
-                let (dtype, shape) = {
-                    let info = value.get_tensor_type_and_shape(api)?;
-                    (info.get_element_type()?, info.shape()?)
-                };
-                // Unsafe invariant: dtype must match value
-                Ok(Some(unsafe {
-                    value.load_tensor_buffer(api, dtype, shape)?
-                }))
+                let buf = unsafe { value.load_tensor(api)? };
+                Ok(Some(InputBuffer::new(buf, vec![])))
             }
-            _ => bail!("Only tensor inputs are supported."),
+            ONNXType_ONNX_TYPE_SEQUENCE => {
+                let n_elements = value.get_value_count(api)?;
+                let mut owned = Vec::with_capacity(n_elements);
+                let elements = (0..n_elements)
+                    .map(|idx| unsafe { value.load_tensor_element(api, idx, &mut owned) })
+                    .collect::<Result<_>>()
+                    .map_err(|err| err.context("Only sequences of tensors are supported."))?;
+                Ok(Some(InputBuffer::new(
+                    ValueBuffer::Sequence(elements),
+                    owned,
+                )))
+            }
+            ONNXType_ONNX_TYPE_MAP => {
+                let mut owned = Vec::with_capacity(2);
+                let keys = unsafe { value.load_tensor_element(api, 0, &mut owned)? };
+                let values = unsafe { value.load_tensor_element(api, 1, &mut owned)? };
+                Ok(Some(InputBuffer::new(
+                    ValueBuffer::Map {
+                        keys: Box::new(keys),
+                        values: Box::new(values),
+                    },
+                    owned,
+                )))
+            }
+            _ => bail!("Only tensor, sequence and map inputs are supported."),
         }
     }
 }
@@ -296,6 +569,103 @@ impl<'info> KernelInfo<'info> {
         KernelInfo { api, info }
     }
 
+    /// Logger of the session. It may be stored in the kernel and used
+    /// during compute.
+    pub fn logger(&self) -> Result<Logger> {
+        let mut logger = std::ptr::null();
+        let status = unsafe { self.api.KernelInfo_GetLogger.unwrap()(self.info, &mut logger) };
+        self.api.status_to_result(status)?;
+        unsafe { Logger::from_raw(self.api, logger) }
+    }
+
+    /// Name of the node.
+    pub fn node_name(&self) -> Result<String> {
+        let fun = self.api.KernelInfo_GetNodeName.unwrap();
+        self.read_string(|out, size| unsafe { fun(self.info, out, size) })
+    }
+
+    /// Number of inputs of the node.
+    pub fn input_count(&self) -> Result<usize> {
+        let fun = self.api.KernelInfo_GetInputCount.unwrap();
+        let mut out = 0;
+        self.api
+            .status_to_result(unsafe { fun(self.info, &mut out) })?;
+        Ok(out)
+    }
+
+    /// Number of outputs of the node.
+    pub fn output_count(&self) -> Result<usize> {
+        let fun = self.api.KernelInfo_GetOutputCount.unwrap();
+        let mut out = 0;
+        self.api
+            .status_to_result(unsafe { fun(self.info, &mut out) })?;
+        Ok(out)
+    }
+
+    /// Name of the input `idx` in the graph.
+    pub fn input_name(&self, idx: usize) -> Result<String> {
+        let fun = self.api.KernelInfo_GetInputName.unwrap();
+        self.read_string(|out, size| unsafe { fun(self.info, idx, out, size) })
+    }
+
+    /// Name of the output `idx` in the graph.
+    pub fn output_name(&self, idx: usize) -> Result<String> {
+        let fun = self.api.KernelInfo_GetOutputName.unwrap();
+        self.read_string(|out, size| unsafe { fun(self.info, idx, out, size) })
+    }
+
+    /// Type and static shape of the input `idx`.
+    pub fn input_type_info(&self, idx: usize) -> Result<TypeInfo> {
+        let fun = self.api.KernelInfo_GetInputTypeInfo.unwrap();
+        let mut type_info = std::ptr::null_mut();
+        self.api
+            .status_to_result(unsafe { fun(self.info, idx, &mut type_info) })?;
+        unsafe { read_type_info(self.api, type_info) }
+    }
+
+    /// Type and static shape of the output `idx`.
+    pub fn output_type_info(&self, idx: usize) -> Result<TypeInfo> {
+        let fun = self.api.KernelInfo_GetOutputTypeInfo.unwrap();
+        let mut type_info = std::ptr::null_mut();
+        self.api
+            .status_to_result(unsafe { fun(self.info, idx, &mut type_info) })?;
+        unsafe { read_type_info(self.api, type_info) }
+    }
+
+    /// Read a string through `fun` which is called twice; first to
+    /// query the size including the nul byte, then to fill the buffer.
+    fn read_string(&self, fun: impl Fn(*mut c_char, *mut usize) -> OrtStatusPtr) -> Result<String> {
+        let mut size = 0;
+        self.api
+            .status_to_result(fun(std::ptr::null_mut(), &mut size))?;
+        let mut buf = vec![0u8; size];
+        self.api
+            .status_to_result(fun(buf.as_mut_ptr() as *mut c_char, &mut size))?;
+        Ok(CString::from_vec_with_nul(buf)?.into_string()?)
+    }
+
+    /// Read the attribute `name` as any supported [`Attribute`] type.
+    ///
+    /// The returned error states whether the attribute is missing or
+    /// could not be read as `T`. Reading an `Option<T>` yields `None`
+    /// for missing attributes.
+    pub fn get_attribute<T>(&self, name: &str) -> Result<T>
+    where
+        T: Attribute,
+    {
+        match T::read(self, name) {
+            Ok(value) => Ok(value),
+            Err(err)
+                if err
+                    .downcast_ref::<ErrorStatus>()
+                    .is_some_and(ErrorStatus::is_missing_attribute) =>
+            {
+                T::missing().ok_or_else(|| anyhow!("Missing required attribute '{}'", name))
+            }
+            Err(err) => Err(anyhow!("Failed to read attribute '{}': {}", name, err)),
+        }
+    }
+
     /// Read a `f32` attribute.
     pub fn get_attribute_f32(&self, name: &str) -> Result<f32> {
         let name = CString::new(name)?;
@@ -396,13 +766,41 @@ impl<'info> KernelInfo<'info> {
         Ok(buf)
     }
 
-    /// Get array from attribute Tensor. String tensors are not yet supported.
+    /// Get array from attribute Tensor. Use
+    /// [`Self::get_attribute_tensor_strings`] for string tensors.
     pub fn get_attribute_tensor<T>(&self, name: &str) -> Result<ArrayD<T>>
     where
-        T: Copy,
+        T: Clone,
         for<'s> ArrayViewD<'s, T>: Input<'s>,
     {
-        // Todo: What is going on with the allocator here?
+        let value = self.get_attribute_tensor_value(name)?;
+        let buf = unsafe { value.as_ref().load_tensor(self.api)? };
+        let buf = buf.normalize_buffers();
+
+        // Tensor-loading code for attributes and inputs is
+        // shared. Inputs may be optional which is why we need the
+        // `Some` wrapper below.
+        let view = <ArrayViewD<'_, T>>::try_from_value(Some(buf.as_value()?))?;
+        Ok(view.to_owned())
+    }
+
+    /// Get array of strings from attribute Tensor.
+    ///
+    /// Note that attributes of type `STRINGS` (as opposed to string
+    /// tensors) cannot be read since the C API of onnxruntime provides
+    /// no function to do so.
+    pub fn get_attribute_tensor_strings(&self, name: &str) -> Result<ArrayD<String>> {
+        let value = self.get_attribute_tensor_value(name)?;
+        let buf = unsafe { value.as_ref().load_tensor(self.api)? };
+        let buf = buf.normalize_buffers();
+        let view = <ArrayViewD<'_, &str>>::try_from_value(Some(buf.as_value()?))?;
+        Ok(view.map(|s| s.to_string()))
+    }
+
+    fn get_attribute_tensor_value(&self, name: &str) -> Result<OwnedValue<'static>> {
+        // The tensor is allocated with the default CPU allocator. The
+        // allocator itself is owned by onnxruntime and must not be
+        // released, but the returned value must be.
         let get_alloc = self.api.GetAllocatorWithDefaultOptions.unwrap();
         let mut alloc = std::ptr::null_mut();
 
@@ -410,32 +808,113 @@ impl<'info> KernelInfo<'info> {
 
         let name = CString::new(name)?;
         let fun = self.api.KernelInfoGetAttribute_tensor.unwrap();
-        let value = {
-            let mut value = std::ptr::null_mut();
-            unsafe {
-                self.api
-                    .status_to_result(fun(self.info, name.as_ptr(), alloc, &mut value))?;
-                &mut *value
-            }
-        };
-        let (dtype, shape) = {
-            let info = value.get_tensor_type_and_shape(self.api)?;
-            (info.get_element_type()?, info.shape()?)
+        let mut value = std::ptr::null_mut();
+        unsafe {
+            self.api
+                .status_to_result(fun(self.info, name.as_ptr(), alloc, &mut value))?;
+            OwnedValue::from_raw(self.api, value)
+        }
+    }
+
+    /// Read the input `index` if it is a constant such as an
+    /// initializer. Returns `None` if the input is not constant.
+    ///
+    /// This allows kernels to precompute state from such inputs once
+    /// during kernel creation rather than on every compute call.
+    pub fn get_constant_input<T>(&self, index: usize) -> Result<Option<ArrayD<T>>>
+    where
+        T: Clone,
+        for<'s> ArrayViewD<'s, T>: Input<'s>,
+    {
+        let Some(value) = self.get_constant_input_value(index)? else {
+            return Ok(None);
         };
+        let buf = unsafe { value.load_tensor(self.api)? };
+        let buf = buf.normalize_buffers();
+        let view = <ArrayViewD<'_, T>>::try_from_value(Some(buf.as_value()?))?;
+        Ok(Some(view.to_owned()))
+    }
 
-        // Unsafe invariant: dtype must match value
-        let buf = unsafe { value.load_tensor_buffer(self.api, dtype, shape)? };
+    /// Read the string input `index` if it is a constant. See
+    /// [`Self::get_constant_input`].
+    pub fn get_constant_input_strings(&self, index: usize) -> Result<Option<ArrayD<String>>> {
+        let Some(value) = self.get_constant_input_value(index)? else {
+            return Ok(None);
+        };
+        let buf = unsafe { value.load_tensor(self.api)? };
         let buf = buf.normalize_buffers();
+        let view = <ArrayViewD<'_, &str>>::try_from_value(Some(buf.as_value()?))?;
+        Ok(Some(view.map(|s| s.to_string())))
+    }
 
-        // Tensor-loading code for attributes and inputs is
-        // shared. Inputs may be optional which is why we need the
-        // `Some` wrapper below.
-        let view = <ArrayViewD<'_, T>>::try_from_value(Some(buf.as_value()?))?;
-        Ok(view.to_owned())
+    /// The returned value is owned by the kernel info.
+    fn get_constant_input_value(&self, index: usize) -> Result<Option<&OrtValue>> {
+        let fun = self.api.KernelInfoGetConstantInput_tensor.unwrap();
+        let mut is_constant = 0;
+        let mut value = std::ptr::null();
+        self.api
+            .status_to_result(unsafe { fun(self.info, index, &mut is_constant, &mut value) })?;
+        if is_constant == 0 {
+            return Ok(None);
+        }
+        Ok(unsafe { value.as_ref() })
     }
 }
 
+/// Read and release `type_info`.
+pub(crate) unsafe fn read_type_info(api: &OrtApi, type_info: *mut OrtTypeInfo) -> Result<TypeInfo> {
+    if type_info.is_null() {
+        bail!("onnxruntime returned a null type info")
+    }
+    let read = || -> Result<TypeInfo> {
+        let mut onnx_type = ONNXType_ONNX_TYPE_UNKNOWN;
+        api.status_to_result(unsafe {
+            api.GetOnnxTypeFromTypeInfo.unwrap()(type_info, &mut onnx_type)
+        })?;
+        if onnx_type != ONNXType_ONNX_TYPE_TENSOR {
+            return Ok(TypeInfo::NonTensor);
+        }
+        let mut tensor_info = std::ptr::null();
+        api.status_to_result(unsafe {
+            api.CastTypeInfoToTensorInfo.unwrap()(type_info, &mut tensor_info)
+        })?;
+        // Owned by `type_info`
+        let tensor_info = ManuallyDrop::new(unsafe {
+            TensorTypeAndShapeInfo::from_raw(api, tensor_info as *mut _)?
+        });
+        Ok(TypeInfo::Tensor {
+            element_type: tensor_info.get_element_type()?,
+            shape: tensor_info.get_shape()?,
+        })
+    };
+    let type_info_res = read();
+    unsafe { api.ReleaseTypeInfo.unwrap()(type_info) };
+    type_info_res
+}
+
 impl<'s> TensorTypeAndShapeInfo<'s> {
+    /// Create a new, empty object.
+    pub(crate) fn new(api: &'s OrtApi) -> Result<Self> {
+        let mut info = std::ptr::null_mut();
+        api.status_to_result(unsafe { api.CreateTensorTypeAndShapeInfo.unwrap()(&mut info) })?;
+        unsafe { Self::from_raw(api, info) }
+    }
+
+    /// Take ownership of `info` which is released when dropped.
+    pub(crate) unsafe fn from_raw(
+        api: &'s OrtApi,
+        info: *mut OrtTensorTypeAndShapeInfo,
+    ) -> Result<Self> {
+        match unsafe { info.as_mut() } {
+            Some(info) => Ok(Self { api, info }),
+            None => bail!("onnxruntime returned a null tensor type and shape info"),
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const OrtTensorTypeAndShapeInfo {
+        &*self.info
+    }
+
     pub fn shape(&self) -> Result<Vec<usize>> {
         Ok(self
             .get_dimensions()?
@@ -455,6 +934,66 @@ impl<'s> TensorTypeAndShapeInfo<'s> {
         Ok(out)
     }
 
+    /// Dimensions including the names of symbolic dimensions.
+    pub(crate) fn get_shape(&self) -> Result<Vec<Dim>> {
+        let dims = self.get_dimensions()?;
+        let symbolic = self.get_symbolic_dimensions()?;
+        Ok(dims
+            .into_iter()
+            .zip(symbolic)
+            .map(|(dim, name)| match dim {
+                0.. => Dim::Fixed(dim as usize),
+                _ if !name.is_empty() => Dim::Symbolic(name),
+                _ => Dim::Unknown,
+            })
+            .collect())
+    }
+
+    /// Names of the symbolic dimensions; empty for other dimensions.
+    pub(crate) fn get_symbolic_dimensions(&self) -> Result<Vec<String>> {
+        let mut n_dim = 0;
+        unsafe { self.api.GetDimensionsCount.unwrap()(self.info, &mut n_dim) };
+        let mut names = vec![std::ptr::null(); n_dim];
+        self.api.status_to_result(unsafe {
+            self.api.GetSymbolicDimensions.unwrap()(self.info, names.as_mut_ptr(), n_dim)
+        })?;
+        Ok(names
+            .into_iter()
+            .map(|ptr| {
+                if ptr.is_null() {
+                    String::new()
+                } else {
+                    unsafe { CStr::from_ptr(ptr) }
+                        .to_string_lossy()
+                        .into_owned()
+                }
+            })
+            .collect())
+    }
+
+    pub(crate) fn set_element_type(&mut self, element_type: ElementType) -> Result<()> {
+        self.api.status_to_result(unsafe {
+            self.api.SetTensorElementType.unwrap()(self.info, element_type.to_ort_encoding())
+        })?;
+        Ok(())
+    }
+
+    pub(crate) fn set_dimensions(&mut self, dims: &[i64]) -> Result<()> {
+        self.api.status_to_result(unsafe {
+            self.api.SetDimensions.unwrap()(self.info, dims.as_ptr(), dims.len())
+        })?;
+        Ok(())
+    }
+
+    /// Set the names of symbolic dimensions; empty for other dimensions.
+    pub(crate) fn set_symbolic_dimensions(&mut self, names: &[CString]) -> Result<()> {
+        let mut ptrs: Vec<_> = names.iter().map(|name| name.as_ptr()).collect();
+        self.api.status_to_result(unsafe {
+            self.api.SetSymbolicDimensions.unwrap()(self.info, ptrs.as_mut_ptr(), ptrs.len())
+        })?;
+        Ok(())
+    }
+
     fn get_tensor_shape_element_count(&self) -> Result<usize> {
         let mut element_count = 0;
         self.api.status_to_result(unsafe {
@@ -479,7 +1018,7 @@ impl<'s> Drop for TensorTypeAndShapeInfo<'s> {
 }
 
 impl ElementType {
-    pub fn to_ort_encoding(&self) -> u32 {
+    pub fn to_ort_encoding(self) -> u32 {
         match self {
             Self::Bool => ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_BOOL,
 
@@ -497,6 +1036,11 @@ impl ElementType {
             Self::U64 => ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT64,
 
             Self::String => ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_STRING,
+
+            #[cfg(feature = "half")]
+            Self::F16 => ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT16,
+            #[cfg(feature = "half")]
+            Self::BF16 => ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_BFLOAT16,
         }
     }
 
@@ -519,25 +1063,21 @@ impl ElementType {
             ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT64 => Self::U64,
 
             ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_STRING => Self::String,
+
+            #[cfg(feature = "half")]
+            ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT16 => Self::F16,
+            #[cfg(feature = "half")]
+            ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_BFLOAT16 => Self::BF16,
             _ => bail!("Unsupported tensor element type: '{}'", type_number),
         })
     }
 }
 
-fn add_op_to_domain(
-    api: &OrtApi,
-    domain: &mut OrtCustomOpDomain,
-    op: &'static OrtCustomOp,
-) -> OrtStatusPtr {
-    let fun_ptr = api.CustomOpDomain_Add.unwrap();
-    unsafe { fun_ptr(domain, op) }
-}
-
 impl OrtApi {
     /// Wraps a status pointer into a result.
     ///
     ///A null pointer is mapped to the `Ok(())`.
-    fn status_to_result(&self, ptr: OrtStatusPtr) -> Result<(), ErrorStatus> {
+    pub(crate) fn status_to_result(&self, ptr: OrtStatusPtr) -> Result<(), ErrorStatus> {
         if ptr.is_null() {
             Ok(())
         } else {
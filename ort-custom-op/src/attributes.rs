@@ -0,0 +1,98 @@
+use anyhow::Result;
+use ndarray::{ArrayD, ArrayViewD};
+
+use crate::api::KernelInfo;
+use crate::inputs::Input;
+
+/// Types which can be read from a node attribute via
+/// [`KernelInfo::get_attribute`].
+///
+/// Attributes of type `STRINGS` cannot be read since the C API of
+/// onnxruntime only provides array getters for `FLOATS` and `INTS`.
+/// Use a string tensor attribute read as `ArrayD<String>` instead.
+pub trait Attribute: Sized {
+    /// Read the attribute `name`.
+    fn read(info: &KernelInfo, name: &str) -> Result<Self>;
+
+    /// Value used if the attribute is not defined on the node or
+    /// `None` if the attribute is required.
+    fn missing() -> Option<Self> {
+        None
+    }
+}
+
+/// Structs which are created from the attributes of a node.
+///
+/// Usually implemented via `#[derive(OpAttributes)]` which is
+/// available with the `derive` feature. Each field is read through
+/// [`KernelInfo::get_attribute`] and may be configured with
+/// `#[attr(...)]`:
+///
+/// - `name = "..."`: Name of the attribute if it differs from the field name.
+/// - `default = ...`: Value used if the attribute is missing.
+/// - `validate = path`: Function `fn(&T) -> Result<(), E>` with `E: Display`
+///   which is called with the read value.
+///
+/// Fields of type `Option<T>` are `None` if the attribute is missing.
+pub trait OpAttributes: Sized {
+    fn from_kernel_info(info: &KernelInfo) -> Result<Self>;
+}
+
+impl Attribute for f32 {
+    fn read(info: &KernelInfo, name: &str) -> Result<Self> {
+        info.get_attribute_f32(name)
+    }
+}
+
+impl Attribute for i64 {
+    fn read(info: &KernelInfo, name: &str) -> Result<Self> {
+        info.get_attribute_i64(name)
+    }
+}
+
+impl Attribute for String {
+    fn read(info: &KernelInfo, name: &str) -> Result<Self> {
+        info.get_attribute_string(name)
+    }
+}
+
+impl Attribute for Vec<f32> {
+    fn read(info: &KernelInfo, name: &str) -> Result<Self> {
+        info.get_attribute_f32s(name)
+    }
+}
+
+impl Attribute for Vec<i64> {
+    fn read(info: &KernelInfo, name: &str) -> Result<Self> {
+        info.get_attribute_i64s(name)
+    }
+}
+
+impl<T> Attribute for ArrayD<T>
+where
+    T: Clone,
+    for<'s> ArrayViewD<'s, T>: Input<'s>,
+{
+    fn read(info: &KernelInfo, name: &str) -> Result<Self> {
+        info.get_attribute_tensor(name)
+    }
+}
+
+impl Attribute for ArrayD<String> {
+    fn read(info: &KernelInfo, name: &str) -> Result<Self> {
+        info.get_attribute_tensor_strings(name)
+    }
+}
+
+impl<T> Attribute for Option<T>
+where
+    T: Attribute,
+{
+    fn read(info: &KernelInfo, name: &str) -> Result<Self> {
+        T::read(info, name).map(Some)
+    }
+
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+}
@@ -226,6 +226,11 @@ pub struct OrtOpAttr {
 pub struct OrtLogger {
     _unused: [u8; 0],
 }
+#[repr(C)]
+#[derive(Debug)]
+pub struct OrtShapeInferContext {
+    _unused: [u8; 0],
+}
 pub type OrtStatusPtr = *mut OrtStatus;
 #[doc = " \\brief Memory allocation interface\n\n Structure of function pointers that defines a memory allocator. This can be created and filled in by the user for custom allocators.\n\n When an allocator is passed to any function, be sure that the allocator object is not destroyed until the last allocated object using it is freed."]
 #[repr(C)]
@@ -2728,6 +2733,75 @@ pub struct OrtApi {
             resource: *mut *mut ::std::os::raw::c_void,
         ) -> OrtStatusPtr,
     >,
+    pub SetUserLoggingFunction: ::std::option::Option<
+        unsafe extern "C" fn(
+            options: *mut OrtSessionOptions,
+            user_logging_function: OrtLoggingFunction,
+            user_logging_param: *mut ::std::os::raw::c_void,
+        ) -> OrtStatusPtr,
+    >,
+    pub ShapeInferContext_GetInputCount: ::std::option::Option<
+        unsafe extern "C" fn(context: *const OrtShapeInferContext, out: *mut usize) -> OrtStatusPtr,
+    >,
+    pub ShapeInferContext_GetInputTypeShape: ::std::option::Option<
+        unsafe extern "C" fn(
+            context: *const OrtShapeInferContext,
+            index: usize,
+            info: *mut *mut OrtTensorTypeAndShapeInfo,
+        ) -> OrtStatusPtr,
+    >,
+    pub ShapeInferContext_GetAttribute: ::std::option::Option<
+        unsafe extern "C" fn(
+            context: *const OrtShapeInferContext,
+            attr_name: *const ::std::os::raw::c_char,
+            attr: *mut *const OrtOpAttr,
+        ) -> OrtStatusPtr,
+    >,
+    pub ShapeInferContext_SetOutputTypeShape: ::std::option::Option<
+        unsafe extern "C" fn(
+            context: *const OrtShapeInferContext,
+            index: usize,
+            info: *const OrtTensorTypeAndShapeInfo,
+        ) -> OrtStatusPtr,
+    >,
+    pub SetSymbolicDimensions: ::std::option::Option<
+        unsafe extern "C" fn(
+            info: *mut OrtTensorTypeAndShapeInfo,
+            dim_params: *mut *const ::std::os::raw::c_char,
+            dim_params_length: usize,
+        ) -> OrtStatusPtr,
+    >,
+    pub ReadOpAttr: ::std::option::Option<
+        unsafe extern "C" fn(
+            op_attr: *const OrtOpAttr,
+            type_: OrtOpAttrType,
+            data: *mut ::std::os::raw::c_void,
+            len: usize,
+            out: *mut usize,
+        ) -> OrtStatusPtr,
+    >,
+    pub SetDeterministicCompute: ::std::option::Option<
+        unsafe extern "C" fn(options: *mut OrtSessionOptions, value: bool) -> OrtStatusPtr,
+    >,
+    pub KernelContext_ParallelFor: ::std::option::Option<
+        unsafe extern "C" fn(
+            context: *const OrtKernelContext,
+            fn_: ::std::option::Option<
+                unsafe extern "C" fn(arg1: *mut ::std::os::raw::c_void, arg2: usize),
+            >,
+            total: usize,
+            num_batch: usize,
+            usr_data: *mut ::std::os::raw::c_void,
+        ) -> OrtStatusPtr,
+    >,
+    pub SessionOptionsAppendExecutionProvider_OpenVINO_V2: ::std::option::Option<
+        unsafe extern "C" fn(
+            options: *mut OrtSessionOptions,
+            provider_options_keys: *const *const ::std::os::raw::c_char,
+            provider_options_values: *const *const ::std::os::raw::c_char,
+            num_keys: usize,
+        ) -> OrtStatusPtr,
+    >,
 }
 #[test]
 fn bindgen_test_layout_OrtApi() {
@@ -2735,7 +2809,7 @@ fn bindgen_test_layout_OrtApi() {
     let ptr = UNINIT.as_ptr();
     assert_eq!(
         ::std::mem::size_of::<OrtApi>(),
-        2128usize,
+        2208usize,
         concat!("Size of: ", stringify!(OrtApi))
     );
     assert_eq!(
@@ -5575,6 +5649,118 @@ fn bindgen_test_layout_OrtApi() {
             stringify!(KernelContext_GetResource)
         )
     );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).SetUserLoggingFunction) as usize - ptr as usize },
+        2128usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(SetUserLoggingFunction)
+        )
+    );
+    assert_eq!(
+        unsafe {
+            ::std::ptr::addr_of!((*ptr).ShapeInferContext_GetInputCount) as usize - ptr as usize
+        },
+        2136usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(ShapeInferContext_GetInputCount)
+        )
+    );
+    assert_eq!(
+        unsafe {
+            ::std::ptr::addr_of!((*ptr).ShapeInferContext_GetInputTypeShape) as usize - ptr as usize
+        },
+        2144usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(ShapeInferContext_GetInputTypeShape)
+        )
+    );
+    assert_eq!(
+        unsafe {
+            ::std::ptr::addr_of!((*ptr).ShapeInferContext_GetAttribute) as usize - ptr as usize
+        },
+        2152usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(ShapeInferContext_GetAttribute)
+        )
+    );
+    assert_eq!(
+        unsafe {
+            ::std::ptr::addr_of!((*ptr).ShapeInferContext_SetOutputTypeShape) as usize
+                - ptr as usize
+        },
+        2160usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(ShapeInferContext_SetOutputTypeShape)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).SetSymbolicDimensions) as usize - ptr as usize },
+        2168usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(SetSymbolicDimensions)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).ReadOpAttr) as usize - ptr as usize },
+        2176usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(ReadOpAttr)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).SetDeterministicCompute) as usize - ptr as usize },
+        2184usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(SetDeterministicCompute)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).KernelContext_ParallelFor) as usize - ptr as usize },
+        2192usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(KernelContext_ParallelFor)
+        )
+    );
+    assert_eq!(
+        unsafe {
+            ::std::ptr::addr_of!((*ptr).SessionOptionsAppendExecutionProvider_OpenVINO_V2) as usize
+                - ptr as usize
+        },
+        2200usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtApi),
+            "::",
+            stringify!(SessionOptionsAppendExecutionProvider_OpenVINO_V2)
+        )
+    );
 }
 pub const OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED:
     OrtCustomOpInputOutputCharacteristic = 0;
@@ -5659,6 +5845,18 @@ pub struct OrtCustomOp {
             context: *mut OrtKernelContext,
         ) -> OrtStatusPtr,
     >,
+    pub InferOutputShapeFn: ::std::option::Option<
+        unsafe extern "C" fn(
+            op: *const OrtCustomOp,
+            arg1: *mut OrtShapeInferContext,
+        ) -> OrtStatusPtr,
+    >,
+    pub GetStartVersion: ::std::option::Option<
+        unsafe extern "C" fn(op: *const OrtCustomOp) -> ::std::os::raw::c_int,
+    >,
+    pub GetEndVersion: ::std::option::Option<
+        unsafe extern "C" fn(op: *const OrtCustomOp) -> ::std::os::raw::c_int,
+    >,
 }
 #[test]
 fn bindgen_test_layout_OrtCustomOp() {
@@ -5666,7 +5864,7 @@ fn bindgen_test_layout_OrtCustomOp() {
     let ptr = UNINIT.as_ptr();
     assert_eq!(
         ::std::mem::size_of::<OrtCustomOp>(),
-        152usize,
+        176usize,
         concat!("Size of: ", stringify!(OrtCustomOp))
     );
     assert_eq!(
@@ -5866,4 +6064,34 @@ fn bindgen_test_layout_OrtCustomOp() {
             stringify!(KernelComputeV2)
         )
     );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).InferOutputShapeFn) as usize - ptr as usize },
+        152usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtCustomOp),
+            "::",
+            stringify!(InferOutputShapeFn)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).GetStartVersion) as usize - ptr as usize },
+        160usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtCustomOp),
+            "::",
+            stringify!(GetStartVersion)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).GetEndVersion) as usize - ptr as usize },
+        168usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(OrtCustomOp),
+            "::",
+            stringify!(GetEndVersion)
+        )
+    );
 }
@@ -0,0 +1,406 @@
+use std::any::Any;
+use std::ops::{Deref, DerefMut, Range};
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::api::supports_api_version;
+use crate::bindings::{
+    OrtAllocator, OrtAllocatorType_OrtArenaAllocator, OrtApi, OrtKernelContext,
+    OrtMemType_OrtMemTypeDefault, OrtMemoryInfo,
+};
+use crate::logger::Logger;
+
+/// Context of a single compute call passed to
+/// [`crate::prelude::CustomOp::kernel_compute_with_context`].
+///
+/// Gives access to resources which onnxruntime provides for the
+/// current run.
+#[derive(Debug)]
+pub struct ComputeContext<'ctx> {
+    api: &'static OrtApi,
+    ctx: &'ctx OrtKernelContext,
+}
+
+impl<'ctx> ComputeContext<'ctx> {
+    pub(crate) fn from_ort(api: &'static OrtApi, ctx: &'ctx OrtKernelContext) -> Self {
+        Self { api, ctx }
+    }
+
+    /// Logger of the current run.
+    pub fn logger(&self) -> Result<Logger> {
+        self.ctx.get_logger(self.api)
+    }
+
+    /// Number of outputs of the node including optional outputs
+    /// which are not consumed by the graph.
+    pub fn output_count(&self) -> Result<usize> {
+        self.ctx.get_output_count(self.api)
+    }
+
+    /// Allocator for temporary CPU memory provided by the session.
+    pub fn allocator(&self) -> Result<Allocator<'ctx>> {
+        let mut mem_info: *mut OrtMemoryInfo = std::ptr::null_mut();
+        let status = unsafe {
+            self.api.CreateCpuMemoryInfo.unwrap()(
+                OrtAllocatorType_OrtArenaAllocator,
+                OrtMemType_OrtMemTypeDefault,
+                &mut mem_info,
+            )
+        };
+        self.api.status_to_result(status)?;
+
+        let mut allocator: *mut OrtAllocator = std::ptr::null_mut();
+        let status = unsafe {
+            self.api.KernelContext_GetAllocator.unwrap()(self.ctx, mem_info, &mut allocator)
+        };
+        unsafe { self.api.ReleaseMemoryInfo.unwrap()(mem_info) };
+        self.api.status_to_result(status)?;
+
+        let Some(allocator) = NonNull::new(allocator) else {
+            bail!("onnxruntime returned a null allocator")
+        };
+        Ok(Allocator {
+            api: self.api,
+            allocator,
+            _ctx: Default::default(),
+        })
+    }
+
+    /// Resource `resource_id` of the execution provider such as a
+    /// compute stream. The meaning of the ids and of the returned
+    /// pointer is defined by the execution provider. Returns `None` if
+    /// the resource is not available.
+    pub fn resource(
+        &self,
+        resource_version: i32,
+        resource_id: i32,
+    ) -> Result<Option<NonNull<c_void>>> {
+        let mut resource = std::ptr::null_mut();
+        let status = unsafe {
+            self.api.KernelContext_GetResource.unwrap()(
+                self.ctx,
+                resource_version,
+                resource_id,
+                &mut resource,
+            )
+        };
+        self.api.status_to_result(status)?;
+        Ok(NonNull::new(resource))
+    }
+
+    /// Split `0..total` into contiguous ranges and call `f` for each
+    /// of them on the intra-op thread pool of the session. The
+    /// results are returned in the order of the ranges.
+    ///
+    /// The number of ranges does not depend on the machine; the
+    /// thread pool, which follows the intra-op thread count of the
+    /// session, decides how many of them run at the same time. A
+    /// single range runs on the calling thread if the negotiated API
+    /// (see [`crate::prelude::api_version`]) does not provide
+    /// `KernelContext_ParallelFor`. A panic in `f` is resumed on the
+    /// calling thread once all ranges have finished.
+    pub fn parallel_for<T, F>(&self, total: usize, f: F) -> Result<Vec<T>>
+    where
+        T: Send,
+        F: Fn(Range<usize>) -> T + Sync,
+    {
+        if total == 0 {
+            return Ok(vec![]);
+        }
+        // `KernelContext_ParallelFor` is only available since version
+        // 17. The field lies beyond the end of older API tables and
+        // must not be read before checking the version.
+        let fun = if supports_api_version(17) {
+            self.api.KernelContext_ParallelFor
+        } else {
+            None
+        };
+        let Some(fun) = fun else {
+            return Ok(vec![f(0..total)]);
+        };
+
+        let n_ranges = total.min(MAX_PARALLEL_RANGES);
+        let ranges: Vec<_> = (0..n_ranges)
+            .map(|idx| idx * total / n_ranges..(idx + 1) * total / n_ranges)
+            .collect();
+        let job = ParallelJob {
+            f: &f,
+            ranges: &ranges,
+            results: ranges.iter().map(|_| Mutex::new(None)).collect(),
+            panic: Mutex::new(None),
+        };
+        let status = unsafe {
+            fun(
+                self.ctx,
+                Some(run_chunk::<T, F>),
+                ranges.len(),
+                0,
+                &job as *const _ as *mut c_void,
+            )
+        };
+        self.api.status_to_result(status)?;
+
+        if let Some(payload) = job.panic.into_inner().unwrap() {
+            panic::resume_unwind(payload);
+        }
+        job.results
+            .into_iter()
+            .map(|result| {
+                result
+                    .into_inner()
+                    .unwrap()
+                    .ok_or_else(|| anyhow!("onnxruntime did not run all ranges"))
+            })
+            .collect()
+    }
+}
+
+/// Number of ranges into which [`ComputeContext::parallel_for`]
+/// splits its indices. It bounds the number of threads which can be
+/// used but is large enough to balance the load of any thread pool
+/// found in practice.
+const MAX_PARALLEL_RANGES: usize = 256;
+
+/// State shared by the threads of [`ComputeContext::parallel_for`].
+struct ParallelJob<'a, T, F> {
+    f: &'a F,
+    ranges: &'a [Range<usize>],
+    results: Vec<Mutex<Option<T>>>,
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+/// Run the range `idx` of the [`ParallelJob`] behind `data`.
+unsafe extern "C" fn run_chunk<T, F>(data: *mut c_void, idx: usize)
+where
+    F: Fn(Range<usize>) -> T,
+{
+    let job = unsafe { &*(data as *const ParallelJob<T, F>) };
+    // Unwinding across the FFI boundary would abort the process
+    match panic::catch_unwind(AssertUnwindSafe(|| (job.f)(job.ranges[idx].clone()))) {
+        Ok(result) => *job.results[idx].lock().unwrap() = Some(result),
+        Err(payload) => {
+            job.panic.lock().unwrap().get_or_insert(payload);
+        }
+    }
+}
+
+/// Allocator provided by onnxruntime for the current compute call.
+#[derive(Debug)]
+pub struct Allocator<'ctx> {
+    api: &'static OrtApi,
+    allocator: NonNull<OrtAllocator>,
+    _ctx: std::marker::PhantomData<&'ctx OrtKernelContext>,
+}
+
+impl Allocator<'_> {
+    /// Allocate a zero-initialized buffer of `len` bytes which is
+    /// freed when dropped.
+    pub fn allocate(&self, len: usize) -> Result<AllocatedBuffer<'_>> {
+        let mut ptr = std::ptr::null_mut();
+        let status = unsafe {
+            self.api.AllocatorAlloc.unwrap()(self.allocator.as_ptr(), len.max(1), &mut ptr)
+        };
+        self.api.status_to_result(status)?;
+        let Some(ptr) = NonNull::new(ptr as *mut u8) else {
+            bail!("Failed to allocate {} bytes", len)
+        };
+        // Memory provided by onnxruntime is uninitialized
+        unsafe { ptr.as_ptr().write_bytes(0, len) };
+        Ok(AllocatedBuffer {
+            allocator: self,
+            ptr,
+            len,
+        })
+    }
+}
+
+impl Drop for Allocator<'_> {
+    fn drop(&mut self) {
+        unsafe { self.api.ReleaseAllocator.unwrap()(self.allocator.as_ptr()) }
+    }
+}
+
+/// Memory obtained from an [`Allocator`].
+#[derive(Debug)]
+pub struct AllocatedBuffer<'a> {
+    allocator: &'a Allocator<'a>,
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Deref for AllocatedBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AllocatedBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AllocatedBuffer<'_> {
+    fn drop(&mut self) {
+        let Allocator { api, allocator, .. } = self.allocator;
+        let status =
+            unsafe { api.AllocatorFree.unwrap()(allocator.as_ptr(), self.ptr.as_ptr() as *mut _) };
+        // Nothing sensible to do about a failure while dropping
+        let _ = api.status_to_result(status);
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::convert::Infallible;
+    use std::ffi::CStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use ndarray::{Array1, ArrayD, ArrayViewD, arr1};
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::testing::TestKernel;
+
+    /// Copy the input in ranges and report for each range whether it
+    /// ran on another thread than the compute call.
+    struct ChunkedCopy;
+
+    impl CustomOp for ChunkedCopy {
+        type KernelCreateError = Infallible;
+        type ComputeError = anyhow::Error;
+
+        const NAME: &'static CStr = c"ChunkedCopy";
+
+        type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+        type OpOutputs = (ArrayD<f32>, ArrayD<bool>);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(ChunkedCopy)
+        }
+
+        fn kernel_compute(
+            &self,
+            _inputs: Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            unreachable!("Computed with context")
+        }
+
+        fn kernel_compute_with_context(
+            &self,
+            ctx: &ComputeContext<'_>,
+            (a,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            let caller = std::thread::current().id();
+            let values = a.as_slice().unwrap();
+            let chunks = ctx.parallel_for(values.len(), |range| {
+                let thread = std::thread::current().id();
+                (values[range].to_vec(), thread != caller)
+            })?;
+            let (values, on_pool): (Vec<_>, Vec<_>) = chunks.into_iter().unzip();
+            Ok((
+                Array1::from(values.concat()).into_dyn(),
+                Array1::from(on_pool).into_dyn(),
+            ))
+        }
+    }
+
+    /// Number of ranges of [`PanicInFirstRange`] which finished.
+    static FINISHED_RANGES: AtomicUsize = AtomicUsize::new(0);
+
+    struct PanicInFirstRange;
+
+    impl CustomOp for PanicInFirstRange {
+        type KernelCreateError = Infallible;
+        type ComputeError = anyhow::Error;
+
+        const NAME: &'static CStr = c"PanicInFirstRange";
+
+        type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+        type OpOutputs = (ArrayD<f32>,);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(PanicInFirstRange)
+        }
+
+        fn kernel_compute(
+            &self,
+            _inputs: Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            unreachable!("Computed with context")
+        }
+
+        fn kernel_compute_with_context(
+            &self,
+            ctx: &ComputeContext<'_>,
+            (a,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            ctx.parallel_for(a.len(), |range| {
+                if range.start == 0 {
+                    panic!("first range");
+                }
+                FINISHED_RANGES.fetch_add(1, Ordering::SeqCst);
+            })?;
+            Ok((a.to_owned(),))
+        }
+    }
+
+    fn input(len: usize) -> crate::testing::Tensor {
+        Array1::from_shape_fn(len, |idx| idx as f32)
+            .into_dyn()
+            .into()
+    }
+
+    #[test]
+    fn parallel_for_runs_ranges_on_thread_pool() {
+        let kernel = TestKernel::<ChunkedCopy>::builder().build().unwrap();
+        let outputs = kernel.compute(vec![Some(input(1_000))]).unwrap();
+        let values = outputs[0].as_ref().unwrap().to_array::<f32>().unwrap();
+        let on_pool = outputs[1].as_ref().unwrap().to_array::<bool>().unwrap();
+        assert_eq!(values, input(1_000).to_array::<f32>().unwrap());
+        assert_eq!(on_pool.len(), MAX_PARALLEL_RANGES);
+        assert!(on_pool.iter().all(|&on_pool| on_pool));
+    }
+
+    #[test]
+    fn parallel_for_with_fewer_indices_than_ranges() {
+        let kernel = TestKernel::<ChunkedCopy>::builder().build().unwrap();
+        for len in [0, 1, 3] {
+            let outputs = kernel.compute(vec![Some(input(len))]).unwrap();
+            let values = outputs[0].as_ref().unwrap().to_array::<f32>().unwrap();
+            let on_pool = outputs[1].as_ref().unwrap().to_array::<bool>().unwrap();
+            assert_eq!(values, input(len).to_array::<f32>().unwrap());
+            assert_eq!(on_pool.len(), len);
+        }
+    }
+
+    #[test]
+    fn parallel_for_is_serial_before_api_17() {
+        let kernel = TestKernel::<ChunkedCopy>::builder()
+            .api_version(16)
+            .build()
+            .unwrap();
+        let outputs = kernel.compute(vec![Some(input(1_000))]).unwrap();
+        let values = outputs[0].as_ref().unwrap().to_array::<f32>().unwrap();
+        let on_pool = outputs[1].as_ref().unwrap().to_array::<bool>().unwrap();
+        assert_eq!(values, input(1_000).to_array::<f32>().unwrap());
+        assert_eq!(on_pool, arr1(&[false]).into_dyn());
+    }
+
+    #[test]
+    fn parallel_for_resumes_panic_after_all_ranges() {
+        let kernel = TestKernel::<PanicInFirstRange>::builder().build().unwrap();
+        let err = kernel.compute(vec![Some(input(1_000))]).unwrap_err();
+        assert_eq!(err.to_string(), "PanicInFirstRange: panicked: first range");
+        assert_eq!(
+            FINISHED_RANGES.load(Ordering::SeqCst),
+            MAX_PARALLEL_RANGES - 1
+        );
+    }
+}
@@ -1,14 +1,21 @@
-use std::ffi::CString;
+use std::any::Any;
+use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
 
-use crate::api::{API_VERSION, KernelInfo};
+use crate::api::{KernelInfo, MIN_API_VERSION, registered_api, supports_api_version};
 use crate::bindings::{
-    ONNXTensorElementDataType, OrtApi, OrtCustomOp, OrtCustomOpInputOutputCharacteristic,
-    OrtErrorCode_ORT_RUNTIME_EXCEPTION, OrtKernelContext, OrtKernelInfo, OrtMemType,
-    OrtMemType_OrtMemTypeDefault, OrtStatus,
+    ONNXTensorElementDataType, ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_UNDEFINED,
+    OrtApi, OrtCustomOp, OrtCustomOpInputOutputCharacteristic,
+    OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED, OrtErrorCode_ORT_RUNTIME_EXCEPTION,
+    OrtKernelContext, OrtKernelInfo, OrtMemType, OrtMemType_OrtMemTypeDefault,
+    OrtShapeInferContext, OrtStatus,
 };
+use crate::compute_context::ComputeContext;
 pub use crate::inputs::Inputs;
-pub use crate::outputs::Outputs;
+use crate::logger::{self, Logger};
+pub use crate::outputs::{OutputBuffers, Outputs};
+use crate::shape_inference::ShapeInferContext;
 
 /// Trait defining the behavior of a custom operator.
 pub trait CustomOp {
@@ -16,11 +23,25 @@ pub trait CustomOp {
     type KernelCreateError;
     /// Error type of the compute operation
     type ComputeError;
-    /// Name of the operator
-    const NAME: &'static str;
+    /// Name of the operator such as `c"MyOp"`. It is handed to
+    /// onnxruntime as is and thus needs to be nul-terminated.
+    const NAME: &'static CStr;
     /// Minimum number of variadic inputs. Any non-zero value requires
     /// that the last input is variadic.
     const VARIADIC_MIN_ARITY: usize = 0;
+    /// First opset version of the operator's domain supported by this
+    /// implementation.
+    const SINCE_VERSION: i32 = 1;
+    /// Last opset version (inclusive) of the operator's domain
+    /// supported by this implementation. Several implementations of
+    /// the same operator may be registered in one domain if their
+    /// version ranges do not overlap. Version ranges require
+    /// onnxruntime 1.17 or later.
+    const END_VERSION: i32 = i32::MAX;
+    /// Register [`CustomOp::infer_output_shapes`] with onnxruntime.
+    /// Otherwise, the shapes of all outputs are unknown to
+    /// onnxruntime.
+    const INFER_OUTPUT_SHAPES: bool = false;
 
     type OpInputs<'s>: Inputs<'s>;
     type OpOutputs: Outputs;
@@ -30,10 +51,76 @@ pub trait CustomOp {
     where
         Self: Sized;
 
+    /// Compute the outputs from the inputs.
     fn kernel_compute(
         &self,
         inputs: Self::OpInputs<'_>,
     ) -> Result<Self::OpOutputs, Self::ComputeError>;
+
+    /// Compute the outputs from the inputs given the context of the
+    /// current call.
+    ///
+    /// Operators which need the [`ComputeContext`], e.g. to run work
+    /// on the intra-op thread pool, override this method. The default
+    /// implementation ignores the context and calls
+    /// [`CustomOp::kernel_compute`].
+    fn kernel_compute_with_context(
+        &self,
+        _ctx: &ComputeContext<'_>,
+        inputs: Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        self.kernel_compute(inputs)
+    }
+
+    /// Set the element types and shapes of the outputs given those of
+    /// the inputs. Only called if [`CustomOp::INFER_OUTPUT_SHAPES`] is
+    /// `true`.
+    fn infer_output_shapes(_ctx: &ShapeInferContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Compute the outputs by writing them directly into the output
+    /// tensors owned by onnxruntime.
+    ///
+    /// The default implementation calls
+    /// [`CustomOp::kernel_compute_with_context`] and copies the
+    /// returned arrays into the output tensors. Operators may override
+    /// this method to avoid that copy by filling the views obtained
+    /// from [`OutputBuffers::allocate`] instead. Every required output
+    /// must be allocated in that case; otherwise the compute call
+    /// fails.
+    fn kernel_compute_in_place(
+        &self,
+        ctx: &ComputeContext<'_>,
+        inputs: Self::OpInputs<'_>,
+        outputs: &mut OutputBuffers<'_, Self::OpOutputs>,
+    ) -> Result<(), Self::ComputeError> {
+        outputs.set_owned(self.kernel_compute_with_context(ctx, inputs)?);
+        Ok(())
+    }
+}
+
+/// Build an array of [`OrtCustomOp`] objects for an operator which is
+/// generic over its element type, one for each of the given types.
+///
+/// onnxruntime picks the matching implementation based on the types
+/// of the inputs. The array may be registered together with other
+/// operators using [`crate::prelude::create_custom_op_domain`].
+///
+/// ```ignore
+/// static OPS_CUSTOM_ADD: [OrtCustomOp; 4] = build_for_types!(CustomAdd, [f32, f64, i32, i64]);
+/// ```
+#[macro_export]
+macro_rules! build_for_types {
+    ($($op:ident)::+, [$($ty:ty),+ $(,)?]) => {
+        $crate::build_for_types!(@array ($($op)::+), [$($ty),+])
+    };
+    (@array $op:tt, [$($ty:ty),+]) => {
+        [$($crate::build_for_types!(@one $op, $ty)),+]
+    };
+    (@one ($($op:tt)*), $ty:ty) => {
+        $crate::prelude::build::<$($op)*<$ty>>()
+    };
 }
 
 /// Function to build static instances of [`OrtCustomOp`].
@@ -52,12 +139,17 @@ where
     if T::VARIADIC_MIN_ARITY > 0 && <T::OpInputs<'_>>::VARIADIC_IS_HOMOGENEOUS.is_none() {
         panic!("Specified non-zero `MIN_VARIADIC_ARITY` but the operators inputs are not variadic.")
     }
-
+    if T::SINCE_VERSION < 1 || T::SINCE_VERSION > T::END_VERSION {
+        panic!("`SINCE_VERSION` must be positive and not larger than `END_VERSION`.")
+    }
     OrtCustomOp {
         // This is the API version, not the version of the
-        // operator. It is currently not clear to me how one defines a
-        // version for an operator.
-        version: API_VERSION,
+        // operator. The latter is given by `GetStartVersion` and
+        // `GetEndVersion`. onnxruntime rejects operators with a
+        // version above its own. Registration raises it to the
+        // negotiated version, which makes onnxruntime read the fields
+        // introduced in version 17.
+        version: MIN_API_VERSION,
         CreateKernel: None, // Some(create_kernel::<T>),
         GetName: Some(get_name::<T>),
         GetExecutionProviderType: Some(get_execution_provider_type),
@@ -76,6 +168,13 @@ where
         GetVariadicOutputHomogeneity: Some(get_variadic_output_homogeneity::<T>),
         CreateKernelV2: Some(create_kernel_fallible::<T>),
         KernelComputeV2: Some(kernel_compute_fallible::<T>),
+        InferOutputShapeFn: if T::INFER_OUTPUT_SHAPES {
+            Some(infer_output_shape::<T>)
+        } else {
+            None
+        },
+        GetStartVersion: Some(get_start_version::<T>),
+        GetEndVersion: Some(get_end_version::<T>),
     }
 }
 
@@ -88,16 +187,70 @@ macro_rules! bail_on_error {
         match $res {
             Ok(val) => val,
             Err(err) => {
-                // msg is copied inside `CreateStatus`; no need to leak
-                let msg = CString::new(format!("{}: {}", T::NAME, err)).unwrap();
-                return unsafe {
-                    $api.CreateStatus.unwrap()(OrtErrorCode_ORT_RUNTIME_EXCEPTION, msg.as_ptr())
-                };
+                return create_status($api, &format!("{}: {}", T::NAME.to_string_lossy(), err));
             }
         }
     };
 }
 
+/// Create a new status object with the given error message.
+pub(crate) fn create_status(api: &OrtApi, msg: &str) -> *mut OrtStatus {
+    // msg is copied inside `CreateStatus`; no need to leak
+    let msg = CString::new(msg.replace('\0', "\\0")).unwrap_or_default();
+    unsafe { api.CreateStatus.unwrap()(OrtErrorCode_ORT_RUNTIME_EXCEPTION, msg.as_ptr()) }
+}
+
+/// Extract the message of a caught panic.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic payload"
+    }
+}
+
+/// Run `f` and turn a panic into a failed status.
+///
+/// Unwinding across the FFI boundary would abort the process.
+fn catch_panic<T>(api: &OrtApi, f: impl FnOnce() -> *mut OrtStatus) -> *mut OrtStatus
+where
+    T: CustomOp,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        create_status(
+            api,
+            &format!(
+                "{}: panicked: {}",
+                T::NAME.to_string_lossy(),
+                panic_message(&*payload)
+            ),
+        )
+    })
+}
+
+/// Run `f` and return `fallback` if it panics.
+///
+/// Used for trampolines which have no way of reporting an error to
+/// onnxruntime. The panic is logged to the current logger, if any.
+fn catch_panic_or<T, R>(fallback: R, f: impl FnOnce() -> R) -> R
+where
+    T: CustomOp,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        if let Some(logger) = Logger::current() {
+            crate::ort_error!(
+                logger,
+                "{}: panicked: {}",
+                T::NAME.to_string_lossy(),
+                panic_message(&*payload)
+            );
+        }
+        fallback
+    })
+}
+
 /// Helper struct which contains a reference to the api object. We use
 /// it to shuttle a reference to the Api object from the
 /// kernel-creation time to the compute method which would otherwise
@@ -105,13 +258,15 @@ macro_rules! bail_on_error {
 struct WrappedKernel<T> {
     user_kernel: T,
     api: &'static OrtApi,
+    /// Logger of the session; current while the kernel is dropped.
+    logger: Option<Logger>,
 }
 
 extern "C" fn get_name<T>(_op: *const OrtCustomOp) -> *const c_char
 where
     T: CustomOp,
 {
-    CString::new(T::NAME).unwrap().into_raw()
+    T::NAME.as_ptr()
 }
 
 extern "C" fn get_execution_provider_type(_op: *const OrtCustomOp) -> *const c_char {
@@ -122,32 +277,46 @@ extern "C" fn get_input_type<T>(_op: *const OrtCustomOp, index: usize) -> ONNXTe
 where
     T: CustomOp,
 {
-    <T::OpInputs<'_>>::tensor_data_type(index)
-        .unwrap_or_else(|| panic!("Input '{}' is not a tensor", index))
+    catch_panic_or::<T, _>(
+        ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_UNDEFINED,
+        || {
+            // Non-tensor inputs such as sequences are declared with an
+            // undefined element type.
+            <T::OpInputs<'_>>::tensor_data_type(index)
+                .unwrap_or(ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_UNDEFINED)
+        },
+    )
 }
 
 extern "C" fn get_input_type_count<T>(_op: *const OrtCustomOp) -> usize
 where
     T: CustomOp,
 {
-    // A possibly variadic input counts as a single input
-    let is_variadic = <T::OpInputs<'_>>::VARIADIC_IS_HOMOGENEOUS.is_some();
-    <T::OpInputs<'_>>::NUM_POSITIONAL + if is_variadic { 1 } else { 0 }
+    catch_panic_or::<T, _>(0, || {
+        // A possibly variadic input counts as a single input
+        let is_variadic = <T::OpInputs<'_>>::VARIADIC_IS_HOMOGENEOUS.is_some();
+        <T::OpInputs<'_>>::NUM_POSITIONAL + if is_variadic { 1 } else { 0 }
+    })
 }
 
 extern "C" fn get_output_type<T>(_op: *const OrtCustomOp, index: usize) -> ONNXTensorElementDataType
 where
     T: CustomOp,
 {
-    T::OpOutputs::OUTPUT_TYPES[index].to_ort_encoding()
+    catch_panic_or::<T, _>(
+        ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_UNDEFINED,
+        || T::OpOutputs::OUTPUT_TYPES[index].to_ort_encoding(),
+    )
 }
 
 extern "C" fn get_output_type_count<T>(_op: *const OrtCustomOp) -> usize
 where
     T: CustomOp,
 {
-    // A possibly variadic output counts as a single output
-    <T::OpOutputs as Outputs>::OUTPUT_TYPES.len()
+    catch_panic_or::<T, _>(0, || {
+        // A possibly variadic output counts as a single output
+        <T::OpOutputs as Outputs>::OUTPUT_TYPES.len()
+    })
 }
 
 unsafe extern "C" fn create_kernel_fallible<T>(
@@ -161,15 +330,25 @@ where
     T::KernelCreateError: std::fmt::Display,
 {
     let api = unsafe { &*ort_api };
-    let info = KernelInfo::from_ort(api, unsafe { &*ort_info });
-    let user_kernel = bail_on_error!(api, T::kernel_create(&info));
-    let wrapped_kernel = WrappedKernel { user_kernel, api };
+    catch_panic::<T>(api, || {
+        let info = KernelInfo::from_ort(api, unsafe { &*ort_info });
+        let logger = info.logger().ok();
+        let user_kernel = bail_on_error!(
+            api,
+            logger::with_current(logger, || T::kernel_create(&info))
+        );
+        let wrapped_kernel = WrappedKernel {
+            user_kernel,
+            api,
+            logger,
+        };
 
-    // Kernel is later destroyed in `kernel_destroy`
-    unsafe {
-        *kernel = Box::leak(Box::new(wrapped_kernel)) as *mut _ as *mut c_void;
-    }
-    std::ptr::null_mut()
+        // Kernel is later destroyed in `kernel_destroy`
+        unsafe {
+            *kernel = Box::leak(Box::new(wrapped_kernel)) as *mut _ as *mut c_void;
+        }
+        std::ptr::null_mut()
+    })
 }
 
 unsafe extern "C" fn kernel_compute_fallible<T>(
@@ -180,35 +359,72 @@ where
     T: CustomOp,
     T::ComputeError: std::fmt::Display,
 {
-    let WrappedKernel::<T> { user_kernel, api } = unsafe { &mut *(op_kernel as *mut _) };
-
-    let context = unsafe { context_ptr.as_mut::<'_>() }.unwrap();
-    let outputs = {
-        let bufs = bail_on_error!(api, context.get_input_values(api));
-        // Owned buffers
-        let bufs: Vec<_> = bufs
-            .iter()
-            .map(|el| el.as_ref().map(|some_buf| some_buf.normalize_buffers()))
-            .collect();
-        // Create arrays borrowing from owned buffers
-        let input_values: anyhow::Result<Vec<Option<_>>> = bufs
-            .iter()
-            .map(|el| el.as_ref().map(|some_buf| some_buf.as_value()).transpose())
-            .collect();
-        let input_values = bail_on_error!(api, input_values);
-        let tuple = bail_on_error!(api, T::OpInputs::try_from_values(input_values));
-        bail_on_error!(api, user_kernel.kernel_compute(tuple))
-    };
+    // Compute may be called concurrently; only hand out shared references.
+    let WrappedKernel::<T> {
+        user_kernel, api, ..
+    } = unsafe { &*(op_kernel as *const _) };
 
-    outputs.write_to_ort(api, context);
-    std::ptr::null_mut()
+    catch_panic::<T>(api, || {
+        let context = unsafe { context_ptr.as_ref::<'_>() }.unwrap();
+        logger::with_current(context.get_logger(api).ok(), || {
+            // The outputs are written through the raw pointer while the input
+            // arrays are still borrowed from the context.
+            let mut outputs = unsafe { OutputBuffers::<T::OpOutputs>::new(api, context_ptr) };
+            {
+                let bufs = bail_on_error!(api, context.get_input_values(api));
+                // Owned buffers
+                let bufs: Vec<_> = bufs
+                    .iter()
+                    .map(|el| el.as_ref().map(|some_buf| some_buf.normalize_buffers()))
+                    .collect();
+                // Create arrays borrowing from owned buffers
+                let input_values: anyhow::Result<Vec<Option<_>>> = bufs
+                    .iter()
+                    .map(|el| el.as_ref().map(|some_buf| some_buf.as_value()).transpose())
+                    .collect();
+                let input_values = bail_on_error!(api, input_values);
+                let tuple = bail_on_error!(api, T::OpInputs::try_from_values(input_values));
+                let ctx = ComputeContext::from_ort(api, context);
+                bail_on_error!(
+                    api,
+                    user_kernel.kernel_compute_in_place(&ctx, tuple, &mut outputs)
+                );
+            }
+
+            bail_on_error!(api, outputs.finish());
+            std::ptr::null_mut()
+        })
+    })
+}
+
+unsafe extern "C" fn infer_output_shape<T>(
+    _op: *const OrtCustomOp,
+    ctx: *mut OrtShapeInferContext,
+) -> *mut OrtStatus
+where
+    T: CustomOp,
+{
+    // The callback is not passed the API. It is only installed on
+    // operators which were registered and thus recorded the API.
+    // Shape inference is only available since version 17.
+    let Some(api) = registered_api().filter(|_| supports_api_version(17)) else {
+        return std::ptr::null_mut();
+    };
+    catch_panic::<T>(api, || {
+        let ctx = ShapeInferContext::from_ort(api, unsafe { ctx.as_ref() }.unwrap());
+        bail_on_error!(api, T::infer_output_shapes(&ctx));
+        std::ptr::null_mut()
+    })
 }
 
 unsafe extern "C" fn kernel_destroy<T>(op_kernel: *mut c_void)
 where
     T: CustomOp,
 {
-    drop(unsafe { Box::from_raw(op_kernel as *mut WrappedKernel<T>) });
+    let kernel = unsafe { Box::from_raw(op_kernel as *mut WrappedKernel<T>) };
+    logger::with_current(kernel.logger, || {
+        catch_panic_or::<T, _>((), || drop(kernel));
+    })
 }
 
 extern "C" fn get_input_characteristic<T>(
@@ -218,7 +434,10 @@ extern "C" fn get_input_characteristic<T>(
 where
     T: CustomOp,
 {
-    <T::OpInputs<'_>>::characteristic(index)
+    catch_panic_or::<T, _>(
+        OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED,
+        || <T::OpInputs<'_>>::characteristic(index),
+    )
 }
 
 extern "C" fn get_output_characteristic<T>(
@@ -228,7 +447,10 @@ extern "C" fn get_output_characteristic<T>(
 where
     T: CustomOp,
 {
-    T::OpOutputs::CHARACTERISTICS[index]
+    catch_panic_or::<T, _>(
+        OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED,
+        || T::OpOutputs::CHARACTERISTICS[index],
+    )
 }
 
 extern "C" fn get_mem_type_default(_op: *const OrtCustomOp, _index: usize) -> OrtMemType {
@@ -239,29 +461,100 @@ extern "C" fn get_variadic_input_homogeneity<T>(_op: *const OrtCustomOp) -> ::st
 where
     T: CustomOp,
 {
-    i32::from(
-        <T::OpInputs<'_>>::VARIADIC_IS_HOMOGENEOUS
-            .expect("'get_variadic_input_homogeneity' was called for operator with fixed arity."),
-    )
+    catch_panic_or::<T, _>(0, || {
+        i32::from(
+            <T::OpInputs<'_>>::VARIADIC_IS_HOMOGENEOUS.expect(
+                "'get_variadic_input_homogeneity' was called for operator with fixed arity.",
+            ),
+        )
+    })
+}
+
+extern "C" fn get_start_version<T>(_op: *const OrtCustomOp) -> ::std::os::raw::c_int
+where
+    T: CustomOp,
+{
+    T::SINCE_VERSION
+}
+
+extern "C" fn get_end_version<T>(_op: *const OrtCustomOp) -> ::std::os::raw::c_int
+where
+    T: CustomOp,
+{
+    T::END_VERSION
 }
 
 extern "C" fn get_variadic_input_min_arity<T>(_op: *const OrtCustomOp) -> ::std::os::raw::c_int
 where
     T: CustomOp,
 {
-    T::VARIADIC_MIN_ARITY as _
+    catch_panic_or::<T, _>(0, || T::VARIADIC_MIN_ARITY as _)
 }
 
 extern "C" fn get_variadic_output_homogeneity<T>(_op: *const OrtCustomOp) -> ::std::os::raw::c_int
 where
     T: CustomOp,
 {
-    i32::from(T::OpOutputs::VARIADIC_IS_HOMOGENEOUS)
+    catch_panic_or::<T, _>(0, || i32::from(T::OpOutputs::VARIADIC_IS_HOMOGENEOUS))
 }
 
 extern "C" fn get_variadic_output_min_arity<T>(_op: *const OrtCustomOp) -> ::std::os::raw::c_int
 where
     T: CustomOp,
 {
-    T::OpOutputs::VARIADIC_MIN_ARITY as _
+    catch_panic_or::<T, _>(0, || T::OpOutputs::VARIADIC_MIN_ARITY as _)
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::convert::Infallible;
+    use std::ffi::CStr;
+
+    use ndarray::{ArrayD, ArrayViewD};
+
+    use crate::prelude::*;
+    use crate::testing::TestKernel;
+
+    /// Operator whose kernel panics when it is dropped.
+    struct PanicOnDrop;
+
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            panic!("dropped");
+        }
+    }
+
+    impl CustomOp for PanicOnDrop {
+        type KernelCreateError = Infallible;
+        type ComputeError = Infallible;
+
+        const NAME: &'static CStr = c"PanicOnDrop";
+
+        type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+        type OpOutputs = (ArrayD<f32>,);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(PanicOnDrop)
+        }
+
+        fn kernel_compute(
+            &self,
+            (a,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            Ok((a.to_owned(),))
+        }
+    }
+
+    #[test]
+    fn panic_in_kernel_destroy_is_logged() {
+        let kernel = TestKernel::<PanicOnDrop>::builder().build().unwrap();
+        let logs = kernel.destroy();
+        assert_eq!(
+            logs,
+            [(
+                Severity::Error,
+                "PanicOnDrop: panicked: dropped".to_string()
+            )]
+        );
+    }
 }
@@ -0,0 +1,724 @@
+use std::borrow::Borrow;
+use std::ffi::{CStr, CString, c_void};
+use std::ptr::NonNull;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{Result, bail};
+
+use crate::api::{init_api, registered_api};
+use crate::bindings::{
+    OrtApi, OrtApiBase, OrtCustomOp, OrtCustomOpDomain, OrtKernelInfo, OrtSessionOptions,
+    OrtStatus, OrtStatusPtr,
+};
+use crate::custom_op::create_status;
+
+/// Domain of custom operators to be registered with
+/// [`register_custom_op_domains`].
+///
+/// ```ignore
+/// let domain = CustomOpDomain::new("my.domain")
+///     .with_op(&OP_A)
+///     .with_ops(&OPS_B);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CustomOpDomain {
+    name: String,
+    ops: Vec<&'static OrtCustomOp>,
+}
+
+impl CustomOpDomain {
+    /// Empty domain with the given name.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ops: vec![],
+        }
+    }
+
+    /// Add the operator `op` to the domain.
+    pub fn with_op(mut self, op: &'static OrtCustomOp) -> Self {
+        self.ops.push(op);
+        self
+    }
+
+    /// Add several operators to the domain such as those created with
+    /// [`crate::build_for_types`].
+    pub fn with_ops<I>(mut self, ops: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<&'static OrtCustomOp>,
+    {
+        self.ops.extend(ops.into_iter().map(|op| *op.borrow()));
+        self
+    }
+
+    /// Is `registered` a domain created from the same name and
+    /// operators?
+    fn matches(&self, registered: &RegisteredDomain) -> bool {
+        self.name == registered.name
+            && self.ops.len() == registered.ops.len()
+            && self
+                .ops
+                .iter()
+                .zip(&registered.ops)
+                .all(|(a, b)| std::ptr::eq(*a, *b))
+    }
+
+    /// Create the onnxruntime object of this domain. The operators are
+    /// added as copies which declare the negotiated API `version` (see
+    /// [`versioned_ops`]).
+    fn create(self, api: &'static OrtApi, version: u32) -> Result<RegisteredDomain> {
+        let Ok(name) = CString::new(self.name.as_str()) else {
+            bail!("Domain names must not contain nul bytes.")
+        };
+        let mut ptr = std::ptr::null_mut();
+        // The name is copied by onnxruntime
+        let status = unsafe { api.CreateCustomOpDomain.unwrap()(name.as_ptr(), &mut ptr) };
+        api.status_to_result(status)?;
+        let Some(ptr) = NonNull::new(ptr) else {
+            bail!("onnxruntime returned a null domain")
+        };
+        // Released again if adding an operator fails
+        let domain = OwnedDomain { api, ptr };
+        let versioned = versioned_ops(&self.ops, version)?;
+        for op in versioned.iter() {
+            let status = unsafe { api.CustomOpDomain_Add.unwrap()(domain.ptr.as_ptr(), op) };
+            api.status_to_result(status)?;
+        }
+        Ok(RegisteredDomain {
+            name: self.name,
+            ops: self.ops,
+            domain,
+            _versioned: versioned,
+        })
+    }
+}
+
+/// `OrtCustomOpDomain` which is released when dropped.
+#[derive(Debug)]
+struct OwnedDomain {
+    api: &'static OrtApi,
+    ptr: NonNull<OrtCustomOpDomain>,
+}
+
+// Domains are not modified once all operators are added.
+unsafe impl Send for OwnedDomain {}
+
+impl Drop for OwnedDomain {
+    fn drop(&mut self) {
+        if let Some(release) = self.api.ReleaseCustomOpDomain {
+            unsafe { release(self.ptr.as_ptr()) }
+        }
+    }
+}
+
+/// Domain which was added to session options.
+#[derive(Debug)]
+struct RegisteredDomain {
+    name: String,
+    ops: Vec<&'static OrtCustomOp>,
+    domain: OwnedDomain,
+    /// Copies of `ops` which were added to the domain. Declared after
+    /// `domain` so that they outlive it.
+    _versioned: Box<[OrtCustomOp]>,
+}
+
+/// Domains added to session options so far.
+///
+/// onnxruntime refers to a domain for as long as any session created
+/// from the options exists, which cannot be observed from within the
+/// library. Hence, each distinct domain is created once, shared
+/// between all session options and released when the library is
+/// unloaded.
+static REGISTERED_DOMAINS: Mutex<Vec<RegisteredDomain>> = Mutex::new(Vec::new());
+
+/// Add the custom operator `domains` to `session_options`.
+///
+/// The version of the C API is negotiated with the runtime (see
+/// [`crate::prelude::api_version`]). An error is returned if the
+/// runtime is too old. Use [`result_to_status`] to report it to
+/// onnxruntime. Operators declaring opset version ranges require
+/// version 17; with older runtimes they fail when their kernel is
+/// created while the other operators remain usable.
+pub fn register_custom_op_domains<I>(
+    session_options: &mut OrtSessionOptions,
+    api_base: &OrtApiBase,
+    domains: I,
+) -> Result<()>
+where
+    I: IntoIterator<Item = CustomOpDomain>,
+{
+    let domains: Vec<_> = domains.into_iter().collect();
+    let (api, version) = init_api(api_base)?;
+
+    let mut registered = REGISTERED_DOMAINS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    for domain in domains {
+        let ptr = match registered.iter().find(|r| domain.matches(r)) {
+            Some(r) => r.domain.ptr,
+            None => {
+                let created = domain.create(api, version)?;
+                let ptr = created.domain.ptr;
+                registered.push(created);
+                ptr
+            }
+        };
+        let status = unsafe { api.AddCustomOpDomain.unwrap()(session_options, ptr.as_ptr()) };
+        api.status_to_result(status)?;
+    }
+    Ok(())
+}
+
+/// Convert the result of [`register_custom_op_domains`] into the
+/// status expected by onnxruntime from `RegisterCustomOps`.
+pub fn result_to_status(api_base: &OrtApiBase, result: Result<()>) -> *mut OrtStatus {
+    let Err(err) = result else {
+        return std::ptr::null_mut();
+    };
+    // Version 1 of the API is provided by every runtime
+    let api = registered_api().or_else(|| unsafe { api_base.GetApi?(1).as_ref() });
+    match api {
+        Some(api) => create_status(api, &err.to_string()),
+        // A status cannot be created without any API
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Create a new custom domain with the operators `ops`.
+///
+/// `ops` may be a slice of references such as `&[&OP_A, &OP_B]` or any
+/// other iterator over static operators, for instance one chaining
+/// arrays created with [`crate::build_for_types`]. This is a shorthand
+/// for [`register_custom_op_domains`] with a single domain.
+pub fn create_custom_op_domain<I>(
+    session_options: &mut OrtSessionOptions,
+    api_base: &mut OrtApiBase,
+    domain: &str,
+    ops: I,
+) -> *mut OrtStatus
+where
+    I: IntoIterator,
+    I::Item: Borrow<&'static OrtCustomOp>,
+{
+    let domain = CustomOpDomain::new(domain).with_ops(ops);
+    let result = register_custom_op_domains(session_options, api_base, [domain]);
+    result_to_status(api_base, result)
+}
+
+/// Define the statics of the given operators and export the function
+/// through which onnxruntime registers them.
+///
+/// The function is named `RegisterCustomOps` as expected by
+/// `RegisterCustomOpsLibrary`. Another name may be given as the first
+/// item, for instance to load operators which are linked into the
+/// application through `RegisterCustomOpsUsingFunction`. An operator
+/// which is generic over its element type may be given as
+/// `Op<[T1, T2, ...]>` to register one instance per type (see
+/// [`crate::build_for_types`]).
+///
+/// ```ignore
+/// ort_custom_ops! {
+///     domain "my.domain" => [CustomAdd<[f32, i64]>, CustomSum],
+///     domain "my.other.domain" => [ParseDateTime],
+/// }
+///
+/// ort_custom_ops! {
+///     fn RegisterMyOps;
+///     domain "my.domain" => [CustomSum],
+/// }
+/// ```
+#[macro_export]
+macro_rules! ort_custom_ops {
+    (
+        fn $entry:ident;
+        $(domain $domain:expr => [$($ops:tt)*]),+ $(,)?
+    ) => {
+        /// Entry point through which onnxruntime registers the
+        /// custom operators of this library.
+        #[unsafe(no_mangle)]
+        pub extern "C" fn $entry(
+            options: &mut $crate::prelude::OrtSessionOptions,
+            api_base: &$crate::prelude::OrtApiBase,
+        ) -> *mut $crate::prelude::OrtStatus {
+            let domains = [$(
+                $crate::ort_custom_ops!(
+                    @ops ($crate::prelude::CustomOpDomain::new($domain)) $($ops)*
+                )
+            ),+];
+            let result = $crate::prelude::register_custom_op_domains(options, api_base, domains);
+            $crate::prelude::result_to_status(api_base, result)
+        }
+    };
+    ($(domain $domain:expr => [$($ops:tt)*]),+ $(,)?) => {
+        $crate::ort_custom_ops! {
+            fn RegisterCustomOps;
+            $(domain $domain => [$($ops)*]),+
+        }
+    };
+    // Add the remaining operators to the domain one at a time
+    (@ops ($domain:expr) $($op:ident)::+ <[$($ty:ty),+ $(,)?]> $(, $($rest:tt)*)?) => {
+        $crate::ort_custom_ops!(
+            @ops ($domain.with_ops({
+                static OPS: &[$crate::prelude::OrtCustomOp] =
+                    &$crate::build_for_types!($($op)::+, [$($ty),+]);
+                OPS
+            }))
+            $($($rest)*)?
+        )
+    };
+    (@ops ($domain:expr) $op:ty $(, $($rest:tt)*)?) => {
+        $crate::ort_custom_ops!(
+            @ops ($domain.with_op({
+                static OP: $crate::prelude::OrtCustomOp = $crate::prelude::build::<$op>();
+                &OP
+            }))
+            $($($rest)*)?
+        )
+    };
+    (@ops ($domain:expr)) => {
+        $domain
+    };
+}
+
+/// Copies of `ops` which declare the API `version`.
+///
+/// Opset version ranges were introduced in version 17 and are ignored
+/// by older runtimes, which would see several implementations of the
+/// same operator. Before version 17, the implementations of an
+/// operator which declare a range are thus replaced by a single one
+/// whose kernel creation fails with an error naming the operator. They
+/// are dropped if the domain has an implementation without a range.
+fn versioned_ops(ops: &[&'static OrtCustomOp], version: u32) -> Result<Box<[OrtCustomOp]>> {
+    let versioned = ops.iter().map(|op| OrtCustomOp { version, ..**op });
+    if version >= 17 {
+        return Ok(versioned.collect());
+    }
+    let mut supported = Vec::with_capacity(ops.len());
+    let mut unsupported = vec![];
+    for op in versioned {
+        if declares_version_range(&op) {
+            unsupported.push(op);
+        } else {
+            supported.push(op);
+        }
+    }
+    for op in unsupported {
+        let name = op_name(&op)?;
+        let is_registered = supported
+            .iter()
+            .map(op_name)
+            .any(|registered| registered.is_ok_and(|registered| registered == name));
+        if !is_registered {
+            supported.push(OrtCustomOp {
+                CreateKernelV2: Some(create_unsupported_kernel),
+                ..op
+            });
+        }
+    }
+    Ok(supported.into_boxed_slice())
+}
+
+fn declares_version_range(op: &OrtCustomOp) -> bool {
+    let start = op.GetStartVersion.map_or(1, |fun| unsafe { fun(op) });
+    let end = op.GetEndVersion.map_or(i32::MAX, |fun| unsafe { fun(op) });
+    start != 1 || end != i32::MAX
+}
+
+fn op_name(op: &OrtCustomOp) -> Result<&'static str> {
+    let Some(fun) = op.GetName else {
+        bail!("Operator has no name")
+    };
+    // Names of operators are static
+    Ok(unsafe { CStr::from_ptr(fun(op)) }.to_str()?)
+}
+
+/// Error of operators which declare opset version ranges with runtimes
+/// which ignore them.
+fn unsupported_message(op: &OrtCustomOp) -> String {
+    format!(
+        "Operator '{}' declares a range of opset versions which requires onnxruntime 1.17 or later",
+        op_name(op).unwrap_or_default()
+    )
+}
+
+/// `CreateKernelV2` of operators which are not supported by the
+/// negotiated API; see [`versioned_ops`].
+unsafe extern "C" fn create_unsupported_kernel(
+    op: *const OrtCustomOp,
+    api: *const OrtApi,
+    _info: *const OrtKernelInfo,
+    _kernel: *mut *mut c_void,
+) -> OrtStatusPtr {
+    // onnxruntime passes the operator and its API
+    let (op, api) = unsafe { (&*op, &*api) };
+    create_status(api, &unsupported_message(op))
+}
+
+/// Release all domains. Called when the library is unloaded.
+extern "C" fn release_domains() {
+    let domains = std::mem::take(
+        &mut *REGISTERED_DOMAINS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    );
+    drop(domains);
+}
+
+/// Destructor of the shared library. On other platforms the domains
+/// are left to the operating system.
+#[used]
+#[cfg_attr(
+    any(target_os = "linux", target_os = "android", target_os = "freebsd"),
+    unsafe(link_section = ".fini_array")
+)]
+#[cfg_attr(
+    target_vendor = "apple",
+    unsafe(link_section = "__DATA,__mod_term_func")
+)]
+static RELEASE_DOMAINS_ON_UNLOAD: extern "C" fn() = release_domains;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::convert::Infallible;
+    use std::ffi::c_char;
+    use std::marker::PhantomData;
+    use std::sync::{LazyLock, MutexGuard};
+
+    use ndarray::{ArrayD, ArrayViewD};
+
+    use super::*;
+    use crate::api::{API_VERSION, KernelInfo, MIN_API_VERSION, negotiate_api};
+    use crate::bindings::OrtStatusPtr;
+    use crate::custom_op::{CustomOp, build};
+    use crate::inputs::Inputs;
+    use crate::outputs::Outputs;
+
+    thread_local! {
+        /// Number of domains created and added by this thread.
+        static CREATED_DOMAINS: Cell<usize> = const { Cell::new(0) };
+        static ADDED_DOMAINS: Cell<usize> = const { Cell::new(0) };
+        static ADDED_OPS: Cell<usize> = const { Cell::new(0) };
+        /// API version declared by the last operator added to a domain.
+        static ADDED_OP_VERSION: Cell<u32> = const { Cell::new(0) };
+    }
+
+    struct Identity;
+
+    impl CustomOp for Identity {
+        type KernelCreateError = Infallible;
+        type ComputeError = Infallible;
+
+        const NAME: &'static CStr = c"Identity";
+
+        type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+        type OpOutputs = (ArrayD<f32>,);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(Identity)
+        }
+
+        fn kernel_compute(
+            &self,
+            (array,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            Ok((array.to_owned(),))
+        }
+    }
+
+    static OP_IDENTITY: OrtCustomOp = build::<Identity>();
+
+    /// API which only provides the functions needed to register
+    /// operators.
+    static API: LazyLock<OrtApi> = LazyLock::new(|| {
+        let mut api: OrtApi = unsafe { std::mem::zeroed() };
+        api.CreateCustomOpDomain = Some(create_custom_op_domain);
+        api.CustomOpDomain_Add = Some(custom_op_domain_add);
+        api.AddCustomOpDomain = Some(add_custom_op_domain);
+        api.ReleaseCustomOpDomain = Some(release_custom_op_domain);
+        api
+    });
+
+    unsafe extern "C" fn get_api(_version: u32) -> *const OrtApi {
+        &*API
+    }
+
+    unsafe extern "C" fn create_custom_op_domain(
+        _domain: *const c_char,
+        out: *mut *mut OrtCustomOpDomain,
+    ) -> OrtStatusPtr {
+        CREATED_DOMAINS.with(|created| created.set(created.get() + 1));
+        unsafe { *out = NonNull::dangling().as_ptr() };
+        std::ptr::null_mut()
+    }
+
+    unsafe extern "C" fn custom_op_domain_add(
+        _domain: *mut OrtCustomOpDomain,
+        op: *const OrtCustomOp,
+    ) -> OrtStatusPtr {
+        ADDED_OPS.with(|added| added.set(added.get() + 1));
+        ADDED_OP_VERSION.with(|version| version.set(unsafe { (*op).version }));
+        std::ptr::null_mut()
+    }
+
+    unsafe extern "C" fn add_custom_op_domain(
+        _options: *mut OrtSessionOptions,
+        _domain: *mut OrtCustomOpDomain,
+    ) -> OrtStatusPtr {
+        ADDED_DOMAINS.with(|added| added.set(added.get() + 1));
+        std::ptr::null_mut()
+    }
+
+    unsafe extern "C" fn release_custom_op_domain(_domain: *mut OrtCustomOpDomain) {}
+
+    /// Serializes the tests since they share the registry of domains.
+    static REGISTRY_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Exclusive access to an empty registry of domains. The counters
+    /// of the current thread are reset, too.
+    struct Fixture {
+        api_base: OrtApiBase,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let lock = REGISTRY_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+            release_domains();
+            CREATED_DOMAINS.with(|created| created.set(0));
+            ADDED_DOMAINS.with(|added| added.set(0));
+            ADDED_OPS.with(|added| added.set(0));
+            Self {
+                api_base: OrtApiBase {
+                    GetApi: Some(get_api),
+                    GetVersionString: None,
+                },
+                _lock: lock,
+            }
+        }
+
+        fn options(&self) -> &'static mut OrtSessionOptions {
+            unsafe { NonNull::<OrtSessionOptions>::dangling().as_mut() }
+        }
+
+        fn register<I>(&self, domains: I) -> Result<()>
+        where
+            I: IntoIterator<Item = CustomOpDomain>,
+        {
+            register_custom_op_domains(self.options(), &self.api_base, domains)
+        }
+    }
+
+    #[test]
+    fn domains_are_created_once() {
+        let fixture = Fixture::new();
+        let domains = || {
+            [
+                CustomOpDomain::new("test.a").with_op(&OP_IDENTITY),
+                CustomOpDomain::new("test.b").with_ops([&OP_IDENTITY, &OP_IDENTITY]),
+            ]
+        };
+        fixture.register(domains()).unwrap();
+        fixture.register(domains()).unwrap();
+        assert_eq!(CREATED_DOMAINS.with(Cell::get), 2);
+        assert_eq!(ADDED_DOMAINS.with(Cell::get), 4);
+    }
+
+    /// Operator which is generic over its element type.
+    struct GenericIdentity<T>(PhantomData<T>);
+
+    impl<T> CustomOp for GenericIdentity<T>
+    where
+        T: 'static + Clone,
+        for<'s> (ArrayViewD<'s, T>,): Inputs<'s>,
+        (ArrayD<T>,): Outputs,
+    {
+        type KernelCreateError = Infallible;
+        type ComputeError = Infallible;
+
+        const NAME: &'static CStr = c"GenericIdentity";
+
+        type OpInputs<'s> = (ArrayViewD<'s, T>,);
+        type OpOutputs = (ArrayD<T>,);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(GenericIdentity(PhantomData))
+        }
+
+        fn kernel_compute(
+            &self,
+            (array,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            Ok((array.to_owned(),))
+        }
+    }
+
+    crate::ort_custom_ops! {
+        fn RegisterTestOps;
+        domain "test.macro" => [
+            Identity,
+            GenericIdentity<[f32, i64]>,
+            GenericIdentity<bool>,
+        ],
+        domain "test.macro.empty" => [],
+    }
+
+    #[test]
+    fn generated_entry_point() {
+        let fixture = Fixture::new();
+        assert!(RegisterTestOps(fixture.options(), &fixture.api_base).is_null());
+        assert_eq!(CREATED_DOMAINS.with(Cell::get), 2);
+        assert_eq!(ADDED_DOMAINS.with(Cell::get), 2);
+        assert_eq!(ADDED_OPS.with(Cell::get), 4);
+    }
+
+    #[test]
+    fn ops_declare_negotiated_version() {
+        let fixture = Fixture::new();
+        fixture
+            .register([CustomOpDomain::new("test.version").with_op(&OP_IDENTITY)])
+            .unwrap();
+        assert_eq!(OP_IDENTITY.version, MIN_API_VERSION);
+        assert_eq!(ADDED_OP_VERSION.with(Cell::get), API_VERSION);
+    }
+
+    #[test]
+    fn negotiate_without_get_api() {
+        let api_base = OrtApiBase {
+            GetApi: None,
+            GetVersionString: None,
+        };
+        assert!(negotiate_api(&api_base).is_none());
+    }
+
+    /// Operator which shares its name with [`Identity`] but is only
+    /// used from opset version 2 on.
+    struct IdentityV2;
+
+    impl CustomOp for IdentityV2 {
+        type KernelCreateError = Infallible;
+        type ComputeError = Infallible;
+
+        const NAME: &'static CStr = c"Identity";
+        const SINCE_VERSION: i32 = 2;
+
+        type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+        type OpOutputs = (ArrayD<f32>,);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(IdentityV2)
+        }
+
+        fn kernel_compute(
+            &self,
+            (array,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            Ok((array.to_owned(),))
+        }
+    }
+
+    /// Operator implemented for the opset versions `SINCE..=END`.
+    struct Ranged<const SINCE: i32, const END: i32>;
+
+    impl<const SINCE: i32, const END: i32> CustomOp for Ranged<SINCE, END> {
+        type KernelCreateError = Infallible;
+        type ComputeError = Infallible;
+
+        const NAME: &'static CStr = c"Ranged";
+        const SINCE_VERSION: i32 = SINCE;
+        const END_VERSION: i32 = END;
+
+        type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+        type OpOutputs = (ArrayD<f32>,);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(Ranged)
+        }
+
+        fn kernel_compute(
+            &self,
+            (array,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            Ok((array.to_owned(),))
+        }
+    }
+
+    static OP_IDENTITY_V2: OrtCustomOp = build::<IdentityV2>();
+    static OP_RANGED_V1: OrtCustomOp = build::<Ranged<1, 1>>();
+    static OP_RANGED_V2: OrtCustomOp = build::<Ranged<2, { i32::MAX }>>();
+
+    #[test]
+    fn version_ranges_before_api_17() {
+        let ops = [&OP_IDENTITY, &OP_RANGED_V1, &OP_IDENTITY_V2, &OP_RANGED_V2];
+        let versioned = versioned_ops(&ops, 16).unwrap();
+        let names: Vec<_> = versioned.iter().map(|op| op_name(op).unwrap()).collect();
+        assert_eq!(names, ["Identity", "Ranged"]);
+        assert!(versioned.iter().all(|op| op.version == 16));
+        let create_kernel = |op: &OrtCustomOp| op.CreateKernelV2.map(|fun| fun as *const ());
+        assert_eq!(create_kernel(&versioned[0]), create_kernel(&OP_IDENTITY));
+        assert_eq!(
+            create_kernel(&versioned[1]),
+            Some(create_unsupported_kernel as *const ())
+        );
+        assert_eq!(
+            unsupported_message(&versioned[1]),
+            "Operator 'Ranged' declares a range of opset versions which requires onnxruntime 1.17 or later"
+        );
+
+        let versioned = versioned_ops(&ops, 17).unwrap();
+        assert_eq!(versioned.len(), 4);
+        assert!(versioned.iter().all(|op| op.version == 17));
+    }
+
+    thread_local! {
+        /// Versions of the API requested by `get_api_16`.
+        static REQUESTED_VERSIONS: std::cell::RefCell<Vec<u32>> = const {
+            std::cell::RefCell::new(Vec::new())
+        };
+    }
+
+    /// `GetApi` of onnxruntime 1.16.
+    unsafe extern "C" fn get_api_16(version: u32) -> *const OrtApi {
+        REQUESTED_VERSIONS.with(|requested| requested.borrow_mut().push(version));
+        if version <= 16 {
+            &*API
+        } else {
+            std::ptr::null()
+        }
+    }
+
+    unsafe extern "C" fn get_version_string_16() -> *const c_char {
+        c"1.16.3".as_ptr()
+    }
+
+    #[test]
+    fn negotiate_with_version_string() {
+        REQUESTED_VERSIONS.with(|requested| requested.borrow_mut().clear());
+        let api_base = OrtApiBase {
+            GetApi: Some(get_api_16),
+            GetVersionString: Some(get_version_string_16),
+        };
+        let (_, version) = negotiate_api(&api_base).unwrap();
+        assert_eq!(version, 16);
+        // Versions which the runtime does not provide are not requested
+        assert_eq!(REQUESTED_VERSIONS.with(|r| r.borrow().clone()), [16]);
+
+        REQUESTED_VERSIONS.with(|requested| requested.borrow_mut().clear());
+        let api_base = OrtApiBase {
+            GetApi: Some(get_api_16),
+            GetVersionString: None,
+        };
+        let (_, version) = negotiate_api(&api_base).unwrap();
+        assert_eq!(version, 16);
+        assert_eq!(REQUESTED_VERSIONS.with(|r| r.borrow().clone()), [17, 16]);
+    }
+
+    #[test]
+    fn invalid_domain_name() {
+        let err = Fixture::new()
+            .register([CustomOpDomain::new("test\0domain")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Domain names must not contain nul bytes.");
+    }
+}
@@ -1,7 +1,7 @@
 use std::ffi::CStr;
 use std::fmt;
 
-use crate::bindings::{OrtApi, OrtStatus};
+use crate::bindings::{OrtApi, OrtErrorCode_ORT_FAIL, OrtStatus};
 
 #[derive(Debug)]
 pub struct ErrorStatus {
@@ -27,6 +27,30 @@ impl ErrorStatus {
     }
 }
 
+/// Start of the message of the error onnxruntime reports when reading
+/// an attribute which is not defined on the node. The full message is
+/// `No attribute with name:'<name>'is defined.` as produced in
+/// `onnxruntime/core/framework/op_node_proto_helper.cc`.
+///
+/// The C API has no dedicated error code for this case; the status
+/// has the code `ORT_FAIL` like other errors such as a mismatching
+/// attribute type. Relying on the message couples this crate to an
+/// implementation detail of onnxruntime. Should the message change,
+/// missing optional attributes fail to be read and missing required
+/// attributes are reported as unreadable rather than missing. The
+/// onnxruntime tests `parse_datetime_invalid_fmt` in the `example`
+/// crate and `test_parse_datetime_missing_fmt` in `tests/python`
+/// check the message.
+pub(crate) const MISSING_ATTRIBUTE_PREFIX: &str = "No attribute with name:";
+
+impl ErrorStatus {
+    /// Is this the error onnxruntime reports when reading an
+    /// attribute which is not defined on the node?
+    pub(crate) fn is_missing_attribute(&self) -> bool {
+        self.code == OrtErrorCode_ORT_FAIL && self.msg.starts_with(MISSING_ATTRIBUTE_PREFIX)
+    }
+}
+
 impl fmt::Display for ErrorStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Error {}: {}", self.code, self.msg)
@@ -34,3 +58,26 @@ impl fmt::Display for ErrorStatus {
 }
 
 impl std::error::Error for ErrorStatus {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u32, msg: &str) -> ErrorStatus {
+        ErrorStatus {
+            msg: msg.to_string(),
+            code,
+        }
+    }
+
+    #[test]
+    fn missing_attribute() {
+        // Message and code as reported by onnxruntime
+        let missing = "No attribute with name:'alpha'is defined.";
+        assert!(status(OrtErrorCode_ORT_FAIL, missing).is_missing_attribute());
+        let mismatch = "Attribute name and type don't match for 'alpha'";
+        assert!(!status(OrtErrorCode_ORT_FAIL, mismatch).is_missing_attribute());
+        // Other errors with the same message
+        assert!(!status(OrtErrorCode_ORT_FAIL + 1, missing).is_missing_attribute());
+    }
+}
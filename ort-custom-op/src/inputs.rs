@@ -4,18 +4,29 @@ use crate::bindings::{
     OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED,
     OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_VARIADIC,
 };
-use crate::value::Value;
+use crate::value::{Sequence, Value};
 use anyhow::{Result, bail};
 use ndarray::ArrayViewD;
+use std::collections::HashMap;
+use std::hash::Hash;
 
 /// Trait defining which types can be used as inputs when implementing [crate::prelude::CustomOp].
 ///
 /// Currently, `Inputs` is implemented for tuples of up to ten
 /// elements of of [ArrayViewD] with element types `u8`,
 /// `u16`, `u32`, `u64`, `i8`, `i16`, `i32`, `i64`, `f32`, `f64`, `bool`, and
-/// `&str`. Furthermore, the last element of the tuple may be
+/// `&str`. With the `half` feature, `half::f16` and `half::bf16` are
+/// supported, too. Furthermore, the last element of the tuple may be
 /// variadic by being a `Vec` of [ArrayViewD] objects with one of the
-/// previously stated element types.
+/// previously stated element types. Inputs of type
+/// `seq(tensor(T))` are supported via [Sequence] and inputs of type
+/// `map(K, V)` via [HashMap]. A trailing `Vec<Value>` accepts any
+/// number of inputs of mixed types.
+///
+/// Sequence and map inputs are declared with an undefined element
+/// type since the custom operator API has no way to declare
+/// non-tensor types. Whether they are accepted thus depends on the
+/// type constraints onnxruntime derives for such inputs.
 pub trait Inputs<'a>: Sized {
     /// Is the variadic part of the inputs (if any) homogeneous?
     const VARIADIC_IS_HOMOGENEOUS: Option<bool>;
@@ -34,12 +45,21 @@ pub trait Inputs<'a>: Sized {
 }
 
 pub trait Input<'s>: Sized {
+    /// Must all variadic inputs of this type have the same type?
+    const HOMOGENEOUS: bool = true;
+
     fn try_from_value(value: Option<Value<'s>>) -> Result<Self>;
     fn characteristic() -> OrtCustomOpInputOutputCharacteristic;
 }
 
+/// Types which may be used as keys or values of map inputs.
+pub trait MapElement<'s>: Sized {
+    /// Collect the elements of the tensor holding the keys or values.
+    fn collect_from_value(value: Value<'s>) -> Result<Vec<Self>>;
+}
+
 /// Get ONNX tensor data type id if possible
-trait OnnxTensorDtype {
+pub trait OnnxTensorDtype {
     fn dtype_id() -> Option<ONNXTensorElementDataType>;
 }
 
@@ -60,6 +80,22 @@ impl<'s> Input<'s> for ArrayViewD<'s, &'s str> {
     }
 }
 
+/// Dynamically typed input. As the tail of variadic inputs, this
+/// accepts inputs of mixed types.
+impl<'s> Input<'s> for Value<'s> {
+    const HOMOGENEOUS: bool = false;
+
+    fn try_from_value(value: Option<Value<'s>>) -> Result<Self> {
+        match value {
+            Some(value) => Ok(value),
+            None => bail!("Expected a value, found a missing optional input"),
+        }
+    }
+    fn characteristic() -> OrtCustomOpInputOutputCharacteristic {
+        OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED
+    }
+}
+
 impl<'s, T> Input<'s> for Option<T>
 where
     T: Input<'s>,
@@ -76,6 +112,76 @@ where
     }
 }
 
+impl<'s, T> Input<'s> for Sequence<T>
+where
+    T: Input<'s>,
+{
+    fn try_from_value(value: Option<Value<'s>>) -> Result<Self> {
+        if let Some(Value::Sequence(elements)) = value {
+            elements
+                .into_iter()
+                .map(|el| T::try_from_value(Some(el)))
+                .collect::<Result<_>>()
+                .map(Sequence)
+        } else {
+            bail!("Expected sequence, found {:?}", value)
+        }
+    }
+    fn characteristic() -> OrtCustomOpInputOutputCharacteristic {
+        OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED
+    }
+}
+
+/// Map inputs such as `map(string, float)` as produced by the
+/// `ZipMap` or consumed by the `DictVectorizer` operators. Keys may
+/// be `&str`, `String` or `i64`. Maps are declared with an undefined
+/// element type. Map outputs are not supported since onnxruntime only
+/// provides tensors as outputs of custom operators.
+impl<'s, K, V> Input<'s> for HashMap<K, V>
+where
+    K: MapElement<'s> + Eq + Hash,
+    V: MapElement<'s>,
+{
+    fn try_from_value(value: Option<Value<'s>>) -> Result<Self> {
+        if let Some(Value::Map { keys, values }) = value {
+            let keys = K::collect_from_value(*keys)?;
+            let values = V::collect_from_value(*values)?;
+            if keys.len() != values.len() {
+                bail!("Map has {} keys but {} values", keys.len(), values.len())
+            }
+            Ok(keys.into_iter().zip(values).collect())
+        } else {
+            bail!("Expected map, found {:?}", value)
+        }
+    }
+    fn characteristic() -> OrtCustomOpInputOutputCharacteristic {
+        OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED
+    }
+}
+
+impl<'s> MapElement<'s> for String {
+    fn collect_from_value(value: Value<'s>) -> Result<Vec<Self>> {
+        let arr = <ArrayViewD<'s, &'s str>>::try_from_value(Some(value))?;
+        Ok(arr.iter().map(|s| s.to_string()).collect())
+    }
+}
+
+macro_rules! impl_map_element {
+    ($ty:ty) => {
+        impl<'s> MapElement<'s> for $ty {
+            fn collect_from_value(value: Value<'s>) -> Result<Vec<Self>> {
+                let arr = <ArrayViewD<'s, $ty>>::try_from_value(Some(value))?;
+                Ok(arr.iter().copied().collect())
+            }
+        }
+    };
+}
+
+impl_map_element!(&'s str);
+impl_map_element!(i64);
+impl_map_element!(f32);
+impl_map_element!(f64);
+
 macro_rules! impl_try_from {
     ($ty:ty, $variant:path) => {
         impl<'a> Input<'a> for ArrayViewD<'a, $ty> {
@@ -104,6 +210,10 @@ impl_try_from!(i32, Value::TensorI32);
 impl_try_from!(i16, Value::TensorI16);
 impl_try_from!(f64, Value::TensorF64);
 impl_try_from!(f32, Value::TensorF32);
+#[cfg(feature = "half")]
+impl_try_from!(half::f16, Value::TensorF16);
+#[cfg(feature = "half")]
+impl_try_from!(half::bf16, Value::TensorBF16);
 
 // This could be implemented using the below macro, but then we would
 // have to disable some lints.
@@ -111,7 +221,7 @@ impl<'s, A> Inputs<'s> for (Vec<A>,)
 where
     A: Input<'s> + OnnxTensorDtype,
 {
-    const VARIADIC_IS_HOMOGENEOUS: Option<bool> = Some(true);
+    const VARIADIC_IS_HOMOGENEOUS: Option<bool> = Some(A::HOMOGENEOUS);
     const NUM_POSITIONAL: usize = 0;
 
     fn try_from_values(values: Vec<Option<Value<'s>>>) -> Result<Self> {
@@ -139,7 +249,11 @@ macro_rules! impl_inputs {
             $($positional_ty: Input<'s> + OnnxTensorDtype,)*
             $($var_ty: Input<'s> + OnnxTensorDtype,)*
         {
-            const VARIADIC_IS_HOMOGENEOUS: Option<bool> = if $is_variadic {Some(true)} else { None };
+            const VARIADIC_IS_HOMOGENEOUS: Option<bool> = if $is_variadic {
+                Some($(<$var_ty as Input>::HOMOGENEOUS &&)* true)
+            } else {
+                None
+            };
             const NUM_POSITIONAL: usize = $n_min;
 
             fn try_from_values(values: Vec<Option<Value<'s>>>) -> Result<Self>
@@ -194,7 +308,7 @@ impl_inputs!(8, false, | A, B, C, D, E, F, G, H);
 impl_inputs!(9, false, | A, B, C, D, E, F, G, H, I);
 impl_inputs!(10, false, | A, B, C, D, E, F, G, H, I, J);
 
-// // Variadic implementations; variadic input must be homogeneous, but may be empty
+// // Variadic implementations; variadic input may be empty
 impl_inputs!(1, true, Z | A);
 impl_inputs!(2, true, Z | A, B);
 impl_inputs!(3, true, Z | A, B, C);
@@ -212,6 +326,13 @@ impl<'s> OnnxTensorDtype for ArrayViewD<'s, &'s str> {
     }
 }
 
+/// The type of dynamically typed inputs is undefined.
+impl OnnxTensorDtype for Value<'_> {
+    fn dtype_id() -> Option<ONNXTensorElementDataType> {
+        None
+    }
+}
+
 impl<T> OnnxTensorDtype for Option<T>
 where
     T: OnnxTensorDtype,
@@ -221,6 +342,22 @@ where
     }
 }
 
+/// Sequences are not tensors and are thus declared with an undefined
+/// element type.
+impl<T> OnnxTensorDtype for Sequence<T> {
+    fn dtype_id() -> Option<ONNXTensorElementDataType> {
+        None
+    }
+}
+
+/// Maps are not tensors and are thus declared with an undefined
+/// element type.
+impl<K, V> OnnxTensorDtype for HashMap<K, V> {
+    fn dtype_id() -> Option<ONNXTensorElementDataType> {
+        None
+    }
+}
+
 macro_rules! impl_onnx_tensor_dtype {
     ($ty:ty, $ident:ident) => {
         impl<'s> OnnxTensorDtype for ArrayViewD<'s, $ty> {
@@ -242,3 +379,7 @@ macro_rules! impl_onnx_tensor_dtype {
 #[rustfmt::skip] impl_onnx_tensor_dtype!(i16, ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_INT16);
 #[rustfmt::skip] impl_onnx_tensor_dtype!(i32, ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_INT32);
 #[rustfmt::skip] impl_onnx_tensor_dtype!(i64, ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_INT64);
+#[cfg(feature = "half")]
+#[rustfmt::skip] impl_onnx_tensor_dtype!(half::f16, ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT16);
+#[cfg(feature = "half")]
+#[rustfmt::skip] impl_onnx_tensor_dtype!(half::bf16, ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_BFLOAT16);
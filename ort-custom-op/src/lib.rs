@@ -1,16 +1,55 @@
 mod api;
+mod attributes;
 mod bindings;
+mod compute_context;
 mod custom_op;
+mod domain;
 mod error;
 mod inputs;
+mod logger;
 mod outputs;
+mod shape_inference;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod value;
 
+#[cfg(feature = "half")]
+pub use half;
+
 pub mod prelude {
-    pub use crate::api::{KernelInfo, create_custom_op_domain};
+    pub use crate::api::{
+        API_VERSION, ElementType, KernelInfo, MIN_API_VERSION, TypeInfo, api_version,
+    };
+    pub use crate::attributes::{Attribute, OpAttributes};
     pub use crate::bindings::{OrtApiBase, OrtCustomOp, OrtSessionOptions, OrtStatus};
+    pub use crate::build_for_types;
+    pub use crate::compute_context::{AllocatedBuffer, Allocator, ComputeContext};
     pub use crate::custom_op::{CustomOp, build};
+    pub use crate::domain::{
+        CustomOpDomain, create_custom_op_domain, register_custom_op_domains, result_to_status,
+    };
     pub use crate::inputs::Inputs;
-    pub use crate::outputs::Outputs;
-    pub use crate::value::Value;
+    pub use crate::logger::{Logger, Severity};
+    pub use crate::ort_custom_ops;
+    pub use crate::outputs::{OutputBuffers, Outputs, TensorElement};
+    pub use crate::shape_inference::{Dim, ShapeInferContext};
+    pub use crate::value::{Sequence, Value};
+    pub use crate::{ort_error, ort_fatal, ort_info, ort_log, ort_verbose, ort_warn};
+
+    #[cfg(feature = "derive")]
+    pub use ort_custom_op_derive::{Inputs, OpAttributes, Outputs, custom_op};
+}
+
+/// Items used by the code generated by the derive macros and by the
+/// integration tests. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::api::ElementType;
+    pub use crate::bindings::{
+        ONNXTensorElementDataType, OrtApi, OrtCustomOpDomain, OrtCustomOpInputOutputCharacteristic,
+        OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_VARIADIC, OrtKernelContext,
+    };
+    pub use crate::inputs::{Input, MapElement, OnnxTensorDtype};
+    pub use crate::outputs::{LastOutput, Output};
+    pub use anyhow;
 }
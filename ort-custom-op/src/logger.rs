@@ -0,0 +1,277 @@
+use std::cell::Cell;
+use std::ffi::CString;
+use std::ptr::NonNull;
+
+use anyhow::{Result, bail};
+
+use crate::bindings::{
+    OrtApi, OrtLogger, OrtLoggingLevel, OrtLoggingLevel_ORT_LOGGING_LEVEL_ERROR,
+    OrtLoggingLevel_ORT_LOGGING_LEVEL_FATAL, OrtLoggingLevel_ORT_LOGGING_LEVEL_INFO,
+    OrtLoggingLevel_ORT_LOGGING_LEVEL_VERBOSE, OrtLoggingLevel_ORT_LOGGING_LEVEL_WARNING,
+};
+
+thread_local! {
+    /// Logger of the kernel which is currently created or computed on
+    /// this thread.
+    static CURRENT: Cell<Option<Logger>> = const { Cell::new(None) };
+}
+
+/// Severity of a log message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+    Fatal,
+}
+
+impl Severity {
+    fn to_ort(self) -> OrtLoggingLevel {
+        match self {
+            Severity::Verbose => OrtLoggingLevel_ORT_LOGGING_LEVEL_VERBOSE,
+            Severity::Info => OrtLoggingLevel_ORT_LOGGING_LEVEL_INFO,
+            Severity::Warning => OrtLoggingLevel_ORT_LOGGING_LEVEL_WARNING,
+            Severity::Error => OrtLoggingLevel_ORT_LOGGING_LEVEL_ERROR,
+            Severity::Fatal => OrtLoggingLevel_ORT_LOGGING_LEVEL_FATAL,
+        }
+    }
+
+    #[allow(non_upper_case_globals)]
+    pub(crate) fn from_ort(level: OrtLoggingLevel) -> Result<Self> {
+        Ok(match level {
+            OrtLoggingLevel_ORT_LOGGING_LEVEL_VERBOSE => Severity::Verbose,
+            OrtLoggingLevel_ORT_LOGGING_LEVEL_INFO => Severity::Info,
+            OrtLoggingLevel_ORT_LOGGING_LEVEL_WARNING => Severity::Warning,
+            OrtLoggingLevel_ORT_LOGGING_LEVEL_ERROR => Severity::Error,
+            OrtLoggingLevel_ORT_LOGGING_LEVEL_FATAL => Severity::Fatal,
+            _ => bail!("Unknown logging level '{}'", level),
+        })
+    }
+}
+
+/// Handle to a logger of onnxruntime.
+///
+/// Messages end up in the log sink of the session and are subject to
+/// its severity level. The logger obtained from
+/// [`crate::prelude::KernelInfo::logger`] belongs to the session and
+/// may be stored in the kernel. The one obtained from
+/// [`crate::prelude::ComputeContext::logger`] belongs to the current
+/// run. Use the [`crate::ort_log`] family of
+/// macros to log formatted messages including their source location.
+#[derive(Debug, Clone, Copy)]
+pub struct Logger {
+    api: &'static OrtApi,
+    logger: NonNull<OrtLogger>,
+}
+
+// Loggers of onnxruntime are thread safe and outlive the kernels.
+unsafe impl Send for Logger {}
+unsafe impl Sync for Logger {}
+
+impl Logger {
+    /// Callers must ensure that `ptr` points to a logger which
+    /// outlives the returned object.
+    pub(crate) unsafe fn from_raw(api: &'static OrtApi, ptr: *const OrtLogger) -> Result<Self> {
+        let Some(logger) = NonNull::new(ptr as *mut _) else {
+            bail!("onnxruntime returned a null logger")
+        };
+        Ok(Self { api, logger })
+    }
+
+    /// Logger of the kernel which is currently being created or
+    /// computed on this thread, if any.
+    pub fn current() -> Option<Logger> {
+        CURRENT.with(Cell::get)
+    }
+
+    /// Minimum severity of messages which are logged.
+    pub fn severity_level(&self) -> Result<Severity> {
+        let fun = self.api.Logger_GetLoggingSeverityLevel.unwrap();
+        let mut level = OrtLoggingLevel_ORT_LOGGING_LEVEL_VERBOSE;
+        let status = unsafe { fun(self.logger.as_ptr(), &mut level) };
+        self.api.status_to_result(status)?;
+        Severity::from_ort(level)
+    }
+
+    /// Is a message of the given severity logged?
+    pub fn is_enabled(&self, severity: Severity) -> bool {
+        self.severity_level()
+            .map_or(true, |level| severity >= level)
+    }
+
+    /// Log `message` as if emitted at the given location. Prefer the
+    /// [`crate::ort_log`] family of macros which fill in the location.
+    pub fn log_message(
+        &self,
+        severity: Severity,
+        message: &str,
+        file: &str,
+        line: u32,
+        function: &str,
+    ) -> Result<()> {
+        let message = to_c_string(message);
+        let file = to_c_string(file);
+        let function = to_c_string(function);
+        let fun = self.api.Logger_LogMessage.unwrap();
+        let status = unsafe {
+            fun(
+                self.logger.as_ptr(),
+                severity.to_ort(),
+                message.as_ptr(),
+                file.as_ptr(),
+                line as _,
+                function.as_ptr(),
+            )
+        };
+        Ok(self.api.status_to_result(status)?)
+    }
+}
+
+fn to_c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "\\0")).unwrap_or_default()
+}
+
+/// Make `logger` the current logger of this thread while running `f`.
+pub(crate) fn with_current<R>(logger: Option<Logger>, f: impl FnOnce() -> R) -> R {
+    // Restores the previous logger even if `f` panics
+    struct Reset(Option<Logger>);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            CURRENT.with(|current| current.set(self.0));
+        }
+    }
+    let _reset = Reset(CURRENT.with(|current| current.replace(logger)));
+    f()
+}
+
+/// Log a formatted message with the given [`Severity`] to a
+/// [`Logger`]. The message is only formatted if the severity is
+/// enabled. Failures to log are ignored.
+///
+/// ```ignore
+/// ort_log!(logger, Severity::Warning, "Ignoring {} values", n);
+/// ```
+#[macro_export]
+macro_rules! ort_log {
+    ($logger:expr, $severity:expr, $($arg:tt)+) => {{
+        let logger: &$crate::prelude::Logger = &$logger;
+        let severity: $crate::prelude::Severity = $severity;
+        if logger.is_enabled(severity) {
+            let _ = logger.log_message(
+                severity,
+                &::std::format!($($arg)+),
+                ::std::file!(),
+                ::std::line!(),
+                $crate::__function_name!(),
+            );
+        }
+    }};
+}
+
+/// Log a formatted message with [`Severity::Verbose`]. See [`crate::ort_log`].
+#[macro_export]
+macro_rules! ort_verbose {
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::ort_log!($logger, $crate::prelude::Severity::Verbose, $($arg)+)
+    };
+}
+
+/// Log a formatted message with [`Severity::Info`]. See [`crate::ort_log`].
+#[macro_export]
+macro_rules! ort_info {
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::ort_log!($logger, $crate::prelude::Severity::Info, $($arg)+)
+    };
+}
+
+/// Log a formatted message with [`Severity::Warning`]. See [`crate::ort_log`].
+#[macro_export]
+macro_rules! ort_warn {
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::ort_log!($logger, $crate::prelude::Severity::Warning, $($arg)+)
+    };
+}
+
+/// Log a formatted message with [`Severity::Error`]. See [`crate::ort_log`].
+#[macro_export]
+macro_rules! ort_error {
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::ort_log!($logger, $crate::prelude::Severity::Error, $($arg)+)
+    };
+}
+
+/// Log a formatted message with [`Severity::Fatal`]. See [`crate::ort_log`].
+#[macro_export]
+macro_rules! ort_fatal {
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::ort_log!($logger, $crate::prelude::Severity::Fatal, $($arg)+)
+    };
+}
+
+/// Name of the surrounding function.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __function_name {
+    () => {{
+        fn f() {}
+        fn type_name_of<T>(_: T) -> &'static str {
+            ::std::any::type_name::<T>()
+        }
+        let name = type_name_of(f);
+        name.strip_suffix("::f").unwrap_or(name)
+    }};
+}
+
+/// Backend for the `log` crate which forwards records to the logger
+/// of the kernel currently running on this thread (see
+/// [`Logger::current`]). Records emitted outside of kernel creation
+/// and compute are dropped.
+#[cfg(feature = "log")]
+struct LogBackend;
+
+#[cfg(feature = "log")]
+impl log::Log for LogBackend {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        Logger::current()
+            .is_some_and(|logger| logger.is_enabled(level_to_severity(metadata.level())))
+    }
+
+    fn log(&self, record: &log::Record) {
+        let Some(logger) = Logger::current() else {
+            return;
+        };
+        let severity = level_to_severity(record.level());
+        if logger.is_enabled(severity) {
+            let _ = logger.log_message(
+                severity,
+                &record.args().to_string(),
+                record.file().unwrap_or_default(),
+                record.line().unwrap_or_default(),
+                record.module_path().unwrap_or_default(),
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "log")]
+fn level_to_severity(level: log::Level) -> Severity {
+    match level {
+        log::Level::Error => Severity::Error,
+        log::Level::Warn => Severity::Warning,
+        log::Level::Info => Severity::Info,
+        log::Level::Debug | log::Level::Trace => Severity::Verbose,
+    }
+}
+
+/// Install the `log` backend unless another logger was already set.
+#[cfg(feature = "log")]
+pub(crate) fn install_log_backend() {
+    static BACKEND: LogBackend = LogBackend;
+    if log::set_logger(&BACKEND).is_ok() {
+        // Filtering is done according to the session's severity level
+        log::set_max_level(log::LevelFilter::Trace);
+    }
+}
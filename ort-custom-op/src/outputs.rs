@@ -1,11 +1,15 @@
+use std::marker::PhantomData;
+
 use crate::api::ElementType;
 use crate::bindings::{
     OrtApi, OrtCustomOpInputOutputCharacteristic,
+    OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_OPTIONAL,
     OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED,
     OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_VARIADIC, OrtKernelContext,
 };
 
-use ndarray::ArrayD;
+use anyhow::{Result, bail};
+use ndarray::{ArrayD, ArrayViewMutD};
 
 /// Trait which qualifies types to be used as outputs by the
 /// `kernel_compute` function of the custom operator.
@@ -18,29 +22,160 @@ pub trait Outputs {
     const VARIADIC_IS_HOMOGENEOUS: bool;
 
     const OUTPUT_TYPES: &'static [ElementType];
-    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext);
+    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext) -> Result<()>;
 }
 
-trait Output {
+pub trait Output {
     const OUTPUT_TYPE: ElementType;
     const CHARACTERISTIC: OrtCustomOpInputOutputCharacteristic;
 
-    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize);
+    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize) -> Result<()>;
+}
+
+/// Element types of output tensors which can be written in place
+/// through [`OutputBuffers::allocate`].
+pub trait TensorElement: Clone + Default {
+    const ELEMENT_TYPE: ElementType;
+}
+
+/// Output tensors owned by onnxruntime.
+///
+/// Operators overriding
+/// [`crate::prelude::CustomOp::kernel_compute_in_place`] use this
+/// object to write their results directly into the memory of the
+/// output tensors rather than returning owned arrays which are then
+/// copied.
+pub struct OutputBuffers<'ctx, O> {
+    api: &'ctx OrtApi,
+    // Raw pointer since the input arrays borrow from the same context.
+    ctx: *mut OrtKernelContext,
+    allocated: Vec<usize>,
+    owned: Option<O>,
+    _ctx: PhantomData<&'ctx mut OrtKernelContext>,
+}
+
+impl<'ctx, O> OutputBuffers<'ctx, O>
+where
+    O: Outputs,
+{
+    /// Callers must ensure that `ctx` is valid for `'ctx` and that no
+    /// other object writes to its outputs during that time.
+    pub(crate) unsafe fn new(api: &'ctx OrtApi, ctx: *mut OrtKernelContext) -> Self {
+        Self {
+            api,
+            ctx,
+            allocated: vec![],
+            owned: None,
+            _ctx: PhantomData,
+        }
+    }
+
+    /// Allocate the output tensor with index `idx` and the given
+    /// `shape` and return a mutable view of its data. The data is
+    /// initialized with the element type's default value.
+    ///
+    /// Each output may only be allocated once and its element type
+    /// must match the one declared by the operator's outputs. Optional
+    /// outputs which are not consumed by the graph cannot be allocated.
+    pub fn allocate<T>(&mut self, idx: usize, shape: &[usize]) -> Result<ArrayViewMutD<'ctx, T>>
+    where
+        T: TensorElement,
+    {
+        let declared = O::OUTPUT_TYPES.get(idx).or_else(|| {
+            if O::CHARACTERISTICS.last()
+                == Some(&OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_VARIADIC)
+            {
+                O::OUTPUT_TYPES.last()
+            } else {
+                None
+            }
+        });
+        match declared {
+            None => bail!("Output index '{}' is out of range", idx),
+            Some(ty) if *ty != T::ELEMENT_TYPE => bail!(
+                "Output '{}' is declared as {:?} but was allocated as {:?}",
+                idx,
+                ty,
+                T::ELEMENT_TYPE
+            ),
+            _ => {}
+        }
+        if self.allocated.contains(&idx) {
+            bail!("Output '{}' was already allocated", idx)
+        }
+        if self.owned.is_some() {
+            bail!("Outputs were already set from owned arrays")
+        }
+        let shape_i64: Vec<_> = shape.iter().map(|v| *v as i64).collect();
+        // Each output index is handed out only once, hence the
+        // returned views never alias.
+        let ctx = unsafe { &mut *self.ctx };
+        let Some(val) = unsafe { ctx.get_output(self.api, idx, &shape_i64) }? else {
+            bail!("Output '{}' is not consumed by the graph", idx)
+        };
+        let mut arr = unsafe { val.as_array_mut(self.api) }?;
+        // Memory provided by onnxruntime is uninitialized
+        arr.fill(T::default());
+        self.allocated.push(idx);
+        Ok(arr)
+    }
+
+    /// Set all outputs from owned arrays. The arrays are copied into
+    /// the output tensors once the compute function returns.
+    pub fn set_owned(&mut self, outputs: O) {
+        self.owned = Some(outputs);
+    }
+
+    /// Copy owned outputs (if any) into the output tensors. Otherwise,
+    /// check that every required output was allocated.
+    pub(crate) fn finish(self) -> Result<()> {
+        match self.owned {
+            Some(outputs) => {
+                if !self.allocated.is_empty() {
+                    bail!("Outputs were both allocated in place and set from owned arrays")
+                }
+                outputs.write_to_ort(self.api, unsafe { &mut *self.ctx })
+            }
+            None => {
+                let missing = O::CHARACTERISTICS.iter().enumerate().find(|(idx, ch)| {
+                    **ch == OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED
+                        && !self.allocated.contains(idx)
+                });
+                match missing {
+                    Some((idx, _)) => bail!("Required output '{}' was not allocated", idx),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
 }
 
 macro_rules! impl_output_non_string {
     ($ty:ty, $variant:tt) => {
+        impl TensorElement for $ty {
+            const ELEMENT_TYPE: ElementType = ElementType::$variant;
+        }
+
         impl<'s> Output for ArrayD<$ty> {
             const OUTPUT_TYPE: ElementType = ElementType::$variant;
             const CHARACTERISTIC: OrtCustomOpInputOutputCharacteristic =
                 OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED;
 
-            fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize) {
+            fn write_to_ort(
+                self,
+                api: &OrtApi,
+                ctx: &mut OrtKernelContext,
+                idx: usize,
+            ) -> Result<()> {
                 let shape = self.shape();
                 let shape_i64: Vec<_> = shape.iter().map(|v| *v as i64).collect();
-                let val = unsafe { ctx.get_output(api, idx, &shape_i64) }.unwrap();
-                let mut arr = unsafe { val.as_array_mut(api).unwrap() };
+                let Some(val) = unsafe { ctx.get_output(api, idx, &shape_i64) }? else {
+                    // Output is not consumed by the graph
+                    return Ok(());
+                };
+                let mut arr = unsafe { val.as_array_mut(api) }?;
                 arr.assign(&self);
+                Ok(())
             }
         }
     };
@@ -51,8 +186,25 @@ impl Output for ArrayD<String> {
     const CHARACTERISTIC: OrtCustomOpInputOutputCharacteristic =
         OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_REQUIRED;
 
-    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize) {
-        ctx.fill_string_tensor(api, idx, self).unwrap();
+    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize) -> Result<()> {
+        ctx.fill_string_tensor(api, idx, self)
+    }
+}
+
+/// Optional output which is skipped if `None`.
+impl<T> Output for Option<T>
+where
+    T: Output,
+{
+    const OUTPUT_TYPE: ElementType = T::OUTPUT_TYPE;
+    const CHARACTERISTIC: OrtCustomOpInputOutputCharacteristic =
+        OrtCustomOpInputOutputCharacteristic_INPUT_OUTPUT_OPTIONAL;
+
+    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize) -> Result<()> {
+        match self {
+            Some(value) => value.write_to_ort(api, ctx, idx),
+            None => Ok(()),
+        }
     }
 }
 
@@ -67,6 +219,10 @@ impl_output_non_string!(u64, U64);
 impl_output_non_string!(u8, U8);
 impl_output_non_string!(i8, I8);
 impl_output_non_string!(i16, I16);
+#[cfg(feature = "half")]
+impl_output_non_string!(half::f16, F16);
+#[cfg(feature = "half")]
+impl_output_non_string!(half::bf16, BF16);
 
 macro_rules! impl_outputs {
     (($($idx:tt: $param:tt),*) | $last_idx:tt: $last_param:tt ) => {
@@ -86,9 +242,9 @@ macro_rules! impl_outputs {
                 $(<$param as Output>::OUTPUT_TYPE,)* $last_param::OUTPUT_TYPE
             ];
 
-            fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext,) {
-                $(self.$idx.write_to_ort(api, ctx, $idx);)*
-                self.$last_idx.write_to_ort(api, ctx, $last_idx);
+            fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext,) -> Result<()> {
+                $(self.$idx.write_to_ort(api, ctx, $idx)?;)*
+                self.$last_idx.write_to_ort(api, ctx, $last_idx)
             }
         }
     };
@@ -106,7 +262,7 @@ impl_outputs! {(0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H )| 8: Z}
 impl_outputs! {(0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H, 8: I )| 9: Z}
 impl_outputs! {(0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H, 8: I, 9: J )| 10: Z}
 
-trait LastOutput {
+pub trait LastOutput {
     const CHARACTERISTIC: OrtCustomOpInputOutputCharacteristic;
 
     // TODO: min arity should not be defined here but in the user-impl of CustomOp
@@ -114,7 +270,7 @@ trait LastOutput {
     const VARIADIC_IS_HOMOGENEOUS: bool;
     const OUTPUT_TYPE: ElementType;
 
-    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize);
+    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize) -> Result<()>;
 }
 
 impl<T> LastOutput for T
@@ -126,8 +282,8 @@ where
     const VARIADIC_IS_HOMOGENEOUS: bool = false;
     const OUTPUT_TYPE: ElementType = T::OUTPUT_TYPE;
 
-    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize) {
-        self.write_to_ort(api, ctx, idx);
+    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, idx: usize) -> Result<()> {
+        self.write_to_ort(api, ctx, idx)
     }
 }
 
@@ -140,9 +296,74 @@ where
     const VARIADIC_IS_HOMOGENEOUS: bool = true;
     const OUTPUT_TYPE: ElementType = T::OUTPUT_TYPE;
 
-    fn write_to_ort(self, api: &OrtApi, ctx: &mut OrtKernelContext, first_idx: usize) {
+    fn write_to_ort(
+        self,
+        api: &OrtApi,
+        ctx: &mut OrtKernelContext,
+        first_idx: usize,
+    ) -> Result<()> {
         for (idx, arr) in self.into_iter().enumerate() {
-            arr.write_to_ort(api, ctx, idx + first_idx);
+            arr.write_to_ort(api, ctx, idx + first_idx)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::convert::Infallible;
+    use std::ffi::CStr;
+
+    use ndarray::{ArrayD, ArrayViewD, arr1};
+
+    use crate::prelude::*;
+    use crate::testing::TestKernel;
+
+    /// Operator which forgets to allocate its second output.
+    struct ForgetfulOp;
+
+    impl CustomOp for ForgetfulOp {
+        type KernelCreateError = Infallible;
+        type ComputeError = anyhow::Error;
+
+        const NAME: &'static CStr = c"ForgetfulOp";
+
+        type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+        type OpOutputs = (ArrayD<f32>, ArrayD<f32>);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(ForgetfulOp)
         }
+
+        fn kernel_compute(
+            &self,
+            (a,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            Ok((a.to_owned(), a.to_owned()))
+        }
+
+        fn kernel_compute_in_place(
+            &self,
+            _ctx: &ComputeContext<'_>,
+            (a,): Self::OpInputs<'_>,
+            outputs: &mut OutputBuffers<'_, Self::OpOutputs>,
+        ) -> Result<(), Self::ComputeError> {
+            outputs.allocate::<f32>(0, a.shape())?.assign(&a);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn unallocated_output() {
+        let kernel = TestKernel::<ForgetfulOp>::builder().build().unwrap();
+        let err = kernel
+            .compute(vec![Some(arr1(&[1f32]).into_dyn().into())])
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Required output '1' was not allocated"),
+            "{}",
+            err
+        );
     }
 }
@@ -0,0 +1,274 @@
+use std::ffi::CString;
+use std::mem::ManuallyDrop;
+
+use anyhow::{Result, bail};
+
+use crate::api::{ElementType, TensorTypeAndShapeInfo};
+use crate::bindings::{
+    OrtApi, OrtOpAttr, OrtOpAttrType, OrtOpAttrType_ORT_OP_ATTR_FLOAT,
+    OrtOpAttrType_ORT_OP_ATTR_FLOATS, OrtOpAttrType_ORT_OP_ATTR_INT,
+    OrtOpAttrType_ORT_OP_ATTR_INTS, OrtOpAttrType_ORT_OP_ATTR_STRING, OrtShapeInferContext,
+};
+
+/// Dimension of a tensor shape during shape inference.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dim {
+    /// Dimension of known size
+    Fixed(usize),
+    /// Dimension of unknown size which is referred to by name such as
+    /// `"batch"`. Dimensions with the same name have the same size.
+    Symbolic(String),
+    /// Dimension of unknown size
+    Unknown,
+}
+
+/// Context passed to [`crate::prelude::CustomOp::infer_output_shapes`].
+///
+/// Provides the element types and shapes of the inputs as well as the
+/// node's attributes and receives the types and shapes of the
+/// outputs.
+#[derive(Debug)]
+pub struct ShapeInferContext<'s> {
+    api: &'s OrtApi,
+    ctx: &'s OrtShapeInferContext,
+}
+
+impl<'s> ShapeInferContext<'s> {
+    pub(crate) fn from_ort(api: &'s OrtApi, ctx: &'s OrtShapeInferContext) -> Self {
+        Self { api, ctx }
+    }
+
+    /// Number of inputs of the node.
+    pub fn input_count(&self) -> Result<usize> {
+        let fun = self.api.ShapeInferContext_GetInputCount.unwrap();
+        let mut out = 0;
+        self.api
+            .status_to_result(unsafe { fun(self.ctx, &mut out) })?;
+        Ok(out)
+    }
+
+    /// Element type of the input `idx`.
+    pub fn input_element_type(&self, idx: usize) -> Result<ElementType> {
+        self.input_info(idx)?.get_element_type()
+    }
+
+    /// Shape of the input `idx`.
+    pub fn input_shape(&self, idx: usize) -> Result<Vec<Dim>> {
+        self.input_info(idx)?.get_shape()
+    }
+
+    /// Set the element type and shape of the output `idx`.
+    pub fn set_output(&self, idx: usize, element_type: ElementType, shape: &[Dim]) -> Result<()> {
+        let mut info = TensorTypeAndShapeInfo::new(self.api)?;
+        info.set_element_type(element_type)?;
+        let dims: Vec<_> = shape
+            .iter()
+            .map(|dim| match dim {
+                Dim::Fixed(size) => *size as i64,
+                Dim::Symbolic(_) | Dim::Unknown => -1,
+            })
+            .collect();
+        info.set_dimensions(&dims)?;
+        let names = shape
+            .iter()
+            .map(|dim| match dim {
+                Dim::Symbolic(name) => CString::new(name.as_str()),
+                Dim::Fixed(_) | Dim::Unknown => Ok(CString::default()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        info.set_symbolic_dimensions(&names)?;
+
+        let fun = self.api.ShapeInferContext_SetOutputTypeShape.unwrap();
+        self.api
+            .status_to_result(unsafe { fun(self.ctx, idx, info.as_ptr()) })?;
+        Ok(())
+    }
+
+    /// Read a `f32` attribute.
+    pub fn get_attribute_f32(&self, name: &str) -> Result<f32> {
+        self.read_attribute_single(name, OrtOpAttrType_ORT_OP_ATTR_FLOAT)
+    }
+
+    /// Read a `i64` attribute.
+    pub fn get_attribute_i64(&self, name: &str) -> Result<i64> {
+        self.read_attribute_single(name, OrtOpAttrType_ORT_OP_ATTR_INT)
+    }
+
+    /// Read a `String` attribute.
+    pub fn get_attribute_string(&self, name: &str) -> Result<String> {
+        let buf = self.read_attribute::<u8>(name, OrtOpAttrType_ORT_OP_ATTR_STRING)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    /// Read a `Vec<f32>` attribute.
+    pub fn get_attribute_f32s(&self, name: &str) -> Result<Vec<f32>> {
+        self.read_attribute(name, OrtOpAttrType_ORT_OP_ATTR_FLOATS)
+    }
+
+    /// Read a `Vec<i64>` attribute.
+    pub fn get_attribute_i64s(&self, name: &str) -> Result<Vec<i64>> {
+        self.read_attribute(name, OrtOpAttrType_ORT_OP_ATTR_INTS)
+    }
+
+    /// The returned object is owned by the context and must not be
+    /// released.
+    fn input_info(&self, idx: usize) -> Result<ManuallyDrop<TensorTypeAndShapeInfo<'s>>> {
+        let fun = self.api.ShapeInferContext_GetInputTypeShape.unwrap();
+        let mut info = std::ptr::null_mut();
+        self.api
+            .status_to_result(unsafe { fun(self.ctx, idx, &mut info) })?;
+        Ok(ManuallyDrop::new(unsafe {
+            TensorTypeAndShapeInfo::from_raw(self.api, info)?
+        }))
+    }
+
+    fn get_attribute_ptr(&self, name: &str) -> Result<&'s OrtOpAttr> {
+        let name = CString::new(name)?;
+        let fun = self.api.ShapeInferContext_GetAttribute.unwrap();
+        let mut attr = std::ptr::null();
+        self.api
+            .status_to_result(unsafe { fun(self.ctx, name.as_ptr(), &mut attr) })?;
+        match unsafe { attr.as_ref() } {
+            Some(attr) => Ok(attr),
+            None => bail!("No attribute with name '{}'", name.to_string_lossy()),
+        }
+    }
+
+    fn read_attribute_single<T>(&self, name: &str, ty: OrtOpAttrType) -> Result<T>
+    where
+        T: Copy + Default,
+    {
+        match self.read_attribute(name, ty)?.as_slice() {
+            [value] => Ok(*value),
+            _ => bail!("Unexpected size of attribute '{}'", name),
+        }
+    }
+
+    /// Read the attribute `name` as an array of `T`.
+    fn read_attribute<T>(&self, name: &str, ty: OrtOpAttrType) -> Result<Vec<T>>
+    where
+        T: Copy + Default,
+    {
+        let attr = self.get_attribute_ptr(name)?;
+        let fun = self.api.ReadOpAttr.unwrap();
+
+        // The first call reports the required size in bytes. It fails
+        // if the provided buffer is too small, which is expected here.
+        let mut n_bytes = 0;
+        let _ = self
+            .api
+            .status_to_result(unsafe { fun(attr, ty, std::ptr::null_mut(), 0, &mut n_bytes) });
+        let mut buf = vec![T::default(); n_bytes.div_ceil(size_of::<T>())];
+        self.api.status_to_result(unsafe {
+            fun(
+                attr,
+                ty,
+                buf.as_mut_ptr() as *mut _,
+                buf.len() * size_of::<T>(),
+                &mut n_bytes,
+            )
+        })?;
+        Ok(buf)
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::convert::Infallible;
+    use std::ffi::CStr;
+
+    use anyhow::Error;
+    use ndarray::{ArrayD, ArrayViewD};
+
+    use crate::prelude::*;
+    use crate::testing::TestKernel;
+
+    /// Prepend the dimensions given by the attributes to the shape of
+    /// the input.
+    struct Expand;
+
+    impl CustomOp for Expand {
+        type KernelCreateError = Infallible;
+        type ComputeError = Infallible;
+
+        const NAME: &'static CStr = c"Expand";
+        const INFER_OUTPUT_SHAPES: bool = true;
+
+        type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+        type OpOutputs = (ArrayD<f32>,);
+
+        fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+            Ok(Expand)
+        }
+
+        fn kernel_compute(
+            &self,
+            (a,): Self::OpInputs<'_>,
+        ) -> Result<Self::OpOutputs, Self::ComputeError> {
+            Ok((a.to_owned(),))
+        }
+
+        fn infer_output_shapes(ctx: &ShapeInferContext) -> Result<(), Error> {
+            let mut shape: Vec<_> = ctx
+                .get_attribute_i64s("sizes")?
+                .into_iter()
+                .map(|size| Dim::Fixed(size as usize))
+                .collect();
+            shape.push(Dim::Symbolic(ctx.get_attribute_string("name")?));
+            shape.extend(ctx.input_shape(0)?);
+            ctx.set_output(0, ctx.input_element_type(0)?, &shape)
+        }
+    }
+
+    fn input() -> TypeInfo {
+        TypeInfo::Tensor {
+            element_type: ElementType::F32,
+            shape: vec![Dim::Symbolic("batch".into()), Dim::Unknown, Dim::Fixed(3)],
+        }
+    }
+
+    #[test]
+    fn infer_output_shapes_round_trip() {
+        let outputs = TestKernel::<Expand>::builder()
+            .attribute("sizes", vec![2i64, 4])
+            .attribute("name", "extra")
+            .input("X", input())
+            .infer_output_shapes()
+            .unwrap();
+        let expected = TypeInfo::Tensor {
+            element_type: ElementType::F32,
+            shape: vec![
+                Dim::Fixed(2),
+                Dim::Fixed(4),
+                Dim::Symbolic("extra".into()),
+                Dim::Symbolic("batch".into()),
+                Dim::Unknown,
+                Dim::Fixed(3),
+            ],
+        };
+        assert_eq!(outputs, [Some(expected)]);
+    }
+
+    #[test]
+    fn infer_output_shapes_missing_attribute() {
+        let err = TestKernel::<Expand>::builder()
+            .attribute("sizes", vec![2i64])
+            .input("X", input())
+            .infer_output_shapes()
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Expand:"), "{}", err);
+        assert!(err.to_string().contains("'name'"), "{}", err);
+    }
+
+    #[test]
+    fn infer_output_shapes_requires_api_17() {
+        let outputs = TestKernel::<Expand>::builder()
+            .api_version(16)
+            .attribute("sizes", vec![2i64])
+            .attribute("name", "extra")
+            .input("X", input())
+            .infer_output_shapes()
+            .unwrap();
+        assert_eq!(outputs, [None]);
+    }
+}
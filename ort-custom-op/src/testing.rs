@@ -0,0 +1,1639 @@
+//! Run custom operators in-process without onnxruntime.
+//!
+//! A [`TestKernel`] drives an operator through the same trampolines
+//! which onnxruntime calls (see [`crate::prelude::build`]), backed by a
+//! fake implementation of the C API:
+//!
+//! ```ignore
+//! use ndarray::{ArrayD, arr1};
+//! use ort_custom_op::testing::{Tensor, TestKernel};
+//!
+//! let kernel = TestKernel::<CustomAdd<f32>>::builder().build()?;
+//! let outputs = kernel.compute(vec![
+//!     Some(arr1(&[1.0f32, 2.0]).into_dyn().into()),
+//!     Some(arr1(&[3.0f32, 4.0]).into_dyn().into()),
+//! ])?;
+//! let sum: ArrayD<f32> = outputs[0].as_ref().unwrap().to_array()?;
+//! ```
+//!
+//! The fake API supports tensor, sequence and map inputs (see
+//! [`TestKernel::compute_inputs`]), tensor outputs, attributes,
+//! constant inputs, logging and a thread pool for
+//! [`crate::prelude::ComputeContext::parallel_for`]. The names and
+//! types of the node's inputs and outputs are those declared with
+//! [`TestKernelBuilder::input`] and [`TestKernelBuilder::output`].
+//! Shape inference runs on the declared inputs via
+//! [`TestKernelBuilder::infer_output_shapes`].
+//!
+//! A [`Model`] describes a graph of operators. With the
+//! `testing-runtime` feature, models can be run in onnxruntime loaded
+//! from a shared library (see [`Runtime`]).
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use anyhow::{Result, anyhow, bail};
+use ndarray::{ArrayD, IxDyn};
+
+use crate::api::{API_VERSION, ElementType, TypeInfo, with_testing_api};
+use crate::bindings::*;
+use crate::custom_op::{CustomOp, build};
+use crate::error::MISSING_ATTRIBUTE_PREFIX;
+use crate::logger::Severity;
+use crate::outputs::TensorElement;
+use crate::shape_inference::Dim;
+
+mod model;
+#[cfg(feature = "testing-runtime")]
+mod runtime;
+
+pub use model::{Model, Node};
+#[cfg(feature = "testing-runtime")]
+pub use runtime::{ORT_DYLIB_PATH, RegisterCustomOpsFn, Runtime, Session};
+
+/// Tensor passed to or returned by a [`TestKernel`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    element_type: ElementType,
+    shape: Vec<usize>,
+    data: TensorData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TensorData {
+    /// Elements in standard layout. Stored as `u64` to be aligned for
+    /// every element type.
+    Plain(Vec<u64>),
+    Strings(Vec<String>),
+}
+
+impl Tensor {
+    /// Tensor of the given type and shape filled with zeros or empty
+    /// strings.
+    fn zeros(element_type: ElementType, shape: Vec<usize>) -> Self {
+        let len = shape.iter().product::<usize>();
+        let data = match element_type {
+            ElementType::String => TensorData::Strings(vec![String::new(); len]),
+            _ => TensorData::Plain(vec![0; (len * element_size(element_type)).div_ceil(8)]),
+        };
+        Self {
+            element_type,
+            shape,
+            data,
+        }
+    }
+
+    pub fn element_type(&self) -> ElementType {
+        self.element_type
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    /// Elements of a numeric tensor in native byte order.
+    fn as_bytes(&self) -> &[u8] {
+        let TensorData::Plain(buf) = &self.data else {
+            unreachable!("String tensors are not stored as plain data")
+        };
+        let len = self.len() * element_size(self.element_type);
+        unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, len) }
+    }
+
+    /// Mutable elements of a numeric tensor in native byte order.
+    #[cfg(feature = "testing-runtime")]
+    fn as_bytes_mut(&mut self) -> &mut [u8] {
+        let len = self.len() * element_size(self.element_type);
+        let TensorData::Plain(buf) = &mut self.data else {
+            unreachable!("String tensors are not stored as plain data")
+        };
+        unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, len) }
+    }
+
+    /// Copy the elements into an array. Fails if `T` is not the
+    /// element type of the tensor.
+    pub fn to_array<T>(&self) -> Result<ArrayD<T>>
+    where
+        T: TensorElement + Copy,
+    {
+        let TensorData::Plain(buf) = &self.data else {
+            bail!(
+                "Tensor of type {:?} cannot be read as {:?}",
+                self.element_type,
+                T::ELEMENT_TYPE
+            )
+        };
+        if self.element_type != T::ELEMENT_TYPE {
+            bail!(
+                "Tensor of type {:?} cannot be read as {:?}",
+                self.element_type,
+                T::ELEMENT_TYPE
+            )
+        }
+        let elements = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const T, self.len()) };
+        Ok(ArrayD::from_shape_vec(
+            IxDyn(&self.shape),
+            elements.to_vec(),
+        )?)
+    }
+
+    /// Copy the elements of a string tensor into an array.
+    pub fn to_strings(&self) -> Result<ArrayD<String>> {
+        let TensorData::Strings(strings) = &self.data else {
+            bail!(
+                "Tensor of type {:?} cannot be read as strings",
+                self.element_type
+            )
+        };
+        Ok(ArrayD::from_shape_vec(IxDyn(&self.shape), strings.clone())?)
+    }
+}
+
+impl<T> From<ArrayD<T>> for Tensor
+where
+    T: TensorElement + Copy,
+{
+    fn from(array: ArrayD<T>) -> Self {
+        let mut tensor = Tensor::zeros(T::ELEMENT_TYPE, array.shape().to_vec());
+        let TensorData::Plain(buf) = &mut tensor.data else {
+            unreachable!("String tensors are not `TensorElement`s")
+        };
+        let elements =
+            unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, array.len()) };
+        for (element, value) in elements.iter_mut().zip(array.iter()) {
+            *element = *value;
+        }
+        tensor
+    }
+}
+
+impl From<ArrayD<String>> for Tensor {
+    fn from(array: ArrayD<String>) -> Self {
+        Self {
+            element_type: ElementType::String,
+            shape: array.shape().to_vec(),
+            data: TensorData::Strings(array.iter().cloned().collect()),
+        }
+    }
+}
+
+impl From<ArrayD<&str>> for Tensor {
+    fn from(array: ArrayD<&str>) -> Self {
+        array.mapv(str::to_string).into()
+    }
+}
+
+/// Input of a [`TestKernel`] or a [`Session`] of any supported type.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Input {
+    Tensor(Tensor),
+    /// `seq(tensor)` of the given elements.
+    Sequence(Vec<Tensor>),
+    /// `map(key, value)` of the keys and values at the same positions
+    /// of two 1D tensors.
+    Map {
+        keys: Tensor,
+        values: Tensor,
+    },
+}
+
+impl From<Tensor> for Input {
+    fn from(tensor: Tensor) -> Self {
+        Self::Tensor(tensor)
+    }
+}
+
+fn element_size(element_type: ElementType) -> usize {
+    match element_type {
+        ElementType::Bool | ElementType::I8 | ElementType::U8 => 1,
+        ElementType::I16 | ElementType::U16 => 2,
+        ElementType::F32 | ElementType::I32 | ElementType::U32 => 4,
+        ElementType::F64 | ElementType::I64 | ElementType::U64 => 8,
+        ElementType::String => unreachable!("String tensors are not stored as plain data"),
+        #[cfg(feature = "half")]
+        ElementType::F16 | ElementType::BF16 => 2,
+    }
+}
+
+/// Value of a node attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Float(f32),
+    Int(i64),
+    String(String),
+    Floats(Vec<f32>),
+    Ints(Vec<i64>),
+    Tensor(Tensor),
+}
+
+impl From<f32> for AttributeValue {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<Vec<f32>> for AttributeValue {
+    fn from(value: Vec<f32>) -> Self {
+        Self::Floats(value)
+    }
+}
+
+impl From<Vec<i64>> for AttributeValue {
+    fn from(value: Vec<i64>) -> Self {
+        Self::Ints(value)
+    }
+}
+
+impl From<Tensor> for AttributeValue {
+    fn from(value: Tensor) -> Self {
+        Self::Tensor(value)
+    }
+}
+
+/// Builder of a [`TestKernel`] describing the node of the operator.
+pub struct TestKernelBuilder<T> {
+    node_name: String,
+    api_version: u32,
+    attributes: HashMap<String, AttributeValue>,
+    constant_inputs: HashMap<usize, Input>,
+    inputs: Vec<(String, TypeInfo)>,
+    outputs: Vec<(String, TypeInfo)>,
+    _op: PhantomData<T>,
+}
+
+impl<T> TestKernelBuilder<T>
+where
+    T: CustomOp,
+    T::KernelCreateError: std::fmt::Display,
+    T::ComputeError: std::fmt::Display,
+{
+    /// Name of the node; defaults to the name of the operator.
+    pub fn node_name(mut self, name: &str) -> Self {
+        self.node_name = name.to_string();
+        self
+    }
+
+    /// Version of the C API which the fake runtime claims to provide;
+    /// defaults to [`API_VERSION`]. Functions introduced in later
+    /// versions are not used by the operator.
+    pub fn api_version(mut self, version: u32) -> Self {
+        self.api_version = version;
+        self
+    }
+
+    /// Set the attribute `name` of the node.
+    pub fn attribute(mut self, name: &str, value: impl Into<AttributeValue>) -> Self {
+        self.attributes.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Make the input `index` a constant such as an initializer. It is
+    /// available through [`crate::prelude::KernelInfo::get_constant_input`]
+    /// but must still be passed to [`TestKernel::compute`].
+    pub fn constant_input(mut self, index: usize, value: impl Into<Tensor>) -> Self {
+        self.constant_inputs
+            .insert(index, Input::Tensor(value.into()));
+        self
+    }
+
+    /// Add an input to the node with its name in the graph and its
+    /// type as known at kernel creation. These are reported by
+    /// [`crate::prelude::KernelInfo::input_count`] and related methods;
+    /// the node has no inputs unless declared here.
+    pub fn input(mut self, name: &str, type_info: TypeInfo) -> Self {
+        self.inputs.push((name.to_string(), type_info));
+        self
+    }
+
+    /// Add an output to the node; see [`Self::input`].
+    pub fn output(mut self, name: &str, type_info: TypeInfo) -> Self {
+        self.outputs.push((name.to_string(), type_info));
+        self
+    }
+
+    /// Run the operator's [`CustomOp::infer_output_shapes`] on the
+    /// inputs declared with [`Self::input`] and the attributes of the
+    /// node. Returns the inferred type of each declared output; `None`
+    /// if the output was not set.
+    pub fn infer_output_shapes(&self) -> Result<Vec<Option<TypeInfo>>> {
+        let op = build::<T>();
+        let Some(infer) = op.InferOutputShapeFn else {
+            bail!("Operator does not infer the shapes of its outputs")
+        };
+        let n_outputs = unsafe { op.GetOutputTypeCount.unwrap()(&op) };
+        let ctx = FakeShapeInferContext {
+            inputs: self
+                .inputs
+                .iter()
+                .map(|(_, type_info)| type_info.into())
+                .collect(),
+            attributes: &self.attributes,
+            outputs: (0..n_outputs).map(|_| RefCell::new(None)).collect(),
+        };
+        let status = with_testing_api(&API, self.api_version, || unsafe {
+            infer(&op, &ctx as *const _ as *mut OrtShapeInferContext)
+        });
+        status_to_result(status)?;
+        ctx.outputs
+            .into_iter()
+            .map(|output| {
+                output
+                    .into_inner()
+                    .map(|info| info.to_type_info())
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Create the kernel. Errors contain the message of the status
+    /// returned by the operator.
+    pub fn build(self) -> Result<TestKernel<T>> {
+        let op = build::<T>();
+        let logger = Box::new(FakeLogger::default());
+        let node_args = |args: Vec<(String, TypeInfo)>| {
+            args.into_iter()
+                .map(|(name, type_info)| Ok((CString::new(name)?, type_info)))
+                .collect::<Result<Vec<_>>>()
+        };
+        let info = FakeKernelInfo {
+            node_name: CString::new(self.node_name)?,
+            attributes: self.attributes,
+            constant_inputs: self.constant_inputs,
+            inputs: node_args(self.inputs)?,
+            outputs: node_args(self.outputs)?,
+            logger: &logger,
+        };
+        let mut kernel = std::ptr::null_mut();
+        let status = with_testing_api(&API, self.api_version, || unsafe {
+            op.CreateKernelV2.unwrap()(
+                &op,
+                &*API,
+                &info as *const _ as *const OrtKernelInfo,
+                &mut kernel,
+            )
+        });
+        status_to_result(status)?;
+        let Some(kernel) = NonNull::new(kernel) else {
+            bail!("Kernel creation returned a null kernel")
+        };
+        Ok(TestKernel {
+            op,
+            kernel,
+            api_version: self.api_version,
+            logger,
+            _op: PhantomData,
+        })
+    }
+}
+
+/// Kernel of the custom operator `T` created without onnxruntime.
+pub struct TestKernel<T> {
+    op: OrtCustomOp,
+    kernel: NonNull<c_void>,
+    api_version: u32,
+    logger: Box<FakeLogger>,
+    _op: PhantomData<T>,
+}
+
+// Manual implementations since operators need not implement `Debug`
+impl<T> std::fmt::Debug for TestKernelBuilder<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TestKernelBuilder")
+            .field("node_name", &self.node_name)
+            .field("api_version", &self.api_version)
+            .field("attributes", &self.attributes)
+            .field("constant_inputs", &self.constant_inputs)
+            .field("inputs", &self.inputs)
+            .field("outputs", &self.outputs)
+            .finish()
+    }
+}
+
+impl<T> std::fmt::Debug for TestKernel<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TestKernel")
+            .field("op", &self.op)
+            .field("kernel", &self.kernel)
+            .field("api_version", &self.api_version)
+            .field("logger", &self.logger)
+            .finish()
+    }
+}
+
+impl<T> TestKernel<T>
+where
+    T: CustomOp,
+    T::KernelCreateError: std::fmt::Display,
+    T::ComputeError: std::fmt::Display,
+{
+    pub fn builder() -> TestKernelBuilder<T> {
+        TestKernelBuilder {
+            node_name: T::NAME.to_string_lossy().into_owned(),
+            api_version: API_VERSION,
+            attributes: HashMap::new(),
+            constant_inputs: HashMap::new(),
+            inputs: vec![],
+            outputs: vec![],
+            _op: PhantomData,
+        }
+    }
+
+    /// Run the kernel on `inputs`, where `None` denotes a missing
+    /// optional input, and return its outputs.
+    ///
+    /// All declared outputs are requested; an output is `None` if it
+    /// was not written. Errors contain the message of the status
+    /// returned by the operator.
+    pub fn compute(&self, inputs: Vec<Option<Tensor>>) -> Result<Vec<Option<Tensor>>> {
+        let n_outputs = unsafe { self.op.GetOutputTypeCount.unwrap()(&self.op) };
+        self.compute_with_output_count(inputs, n_outputs)
+    }
+
+    /// Like [`Self::compute`] but request `n_outputs` outputs, for
+    /// instance to run variadic operators or to omit optional outputs.
+    pub fn compute_with_output_count(
+        &self,
+        inputs: Vec<Option<Tensor>>,
+        n_outputs: usize,
+    ) -> Result<Vec<Option<Tensor>>> {
+        let inputs = inputs.into_iter().map(|input| input.map(Input::Tensor));
+        self.compute_inputs_with_output_count(inputs.collect(), n_outputs)
+    }
+
+    /// Like [`Self::compute`] but for inputs which need not be tensors,
+    /// such as sequences and maps.
+    pub fn compute_inputs(&self, inputs: Vec<Option<Input>>) -> Result<Vec<Option<Tensor>>> {
+        let n_outputs = unsafe { self.op.GetOutputTypeCount.unwrap()(&self.op) };
+        self.compute_inputs_with_output_count(inputs, n_outputs)
+    }
+
+    fn compute_inputs_with_output_count(
+        &self,
+        inputs: Vec<Option<Input>>,
+        n_outputs: usize,
+    ) -> Result<Vec<Option<Tensor>>> {
+        let n_declared = unsafe { self.op.GetOutputTypeCount.unwrap()(&self.op) };
+        // The last declared type applies to all variadic outputs
+        let output_types = (0..n_outputs)
+            .map(|idx| {
+                let idx = idx.min(n_declared.saturating_sub(1));
+                let ty = unsafe { self.op.GetOutputType.unwrap()(&self.op, idx) };
+                ElementType::try_from_ort_encoding(ty)
+            })
+            .collect::<Result<_>>()?;
+        let mut ctx = FakeKernelContext {
+            inputs,
+            output_types,
+            outputs: vec![None; n_outputs],
+            logger: &self.logger,
+        };
+        let status = with_testing_api(&API, self.api_version, || unsafe {
+            self.op.KernelComputeV2.unwrap()(
+                self.kernel.as_ptr(),
+                &mut ctx as *mut _ as *mut OrtKernelContext,
+            )
+        });
+        status_to_result(status)?;
+        ctx.outputs
+            .into_iter()
+            .map(|output| match output {
+                None => Ok(None),
+                Some(Input::Tensor(tensor)) => Ok(Some(tensor)),
+                Some(other) => bail!("Output {:?} is not a tensor", other),
+            })
+            .collect()
+    }
+
+    /// Messages logged by the kernel so far.
+    pub fn logs(&self) -> Vec<(Severity, String)> {
+        self.logger
+            .messages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Destroy the kernel and return all messages it logged, including
+    /// those logged while it was dropped.
+    pub fn destroy(self) -> Vec<(Severity, String)> {
+        let this = ManuallyDrop::new(self);
+        unsafe { this.op.KernelDestroy.unwrap()(this.kernel.as_ptr()) }
+        let logs = this.logs();
+        // The logger is the only field which owns resources
+        drop(unsafe { std::ptr::read(&this.logger) });
+        logs
+    }
+}
+
+thread_local! {
+    /// Values handed out by the fake API on this thread which were not
+    /// released yet.
+    static OWNED_VALUES: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Number of values which the fake API handed out on the current
+/// thread to be released by the operator, such as tensor attributes,
+/// and which were not released yet.
+pub fn owned_values() -> isize {
+    OWNED_VALUES.with(Cell::get)
+}
+
+impl<T> Drop for TestKernel<T> {
+    fn drop(&mut self) {
+        unsafe { self.op.KernelDestroy.unwrap()(self.kernel.as_ptr()) }
+    }
+}
+
+/// `OrtStatus` of the fake API.
+struct FakeStatus {
+    code: OrtErrorCode,
+    message: CString,
+}
+
+/// `OrtTensorTypeAndShapeInfo` of the fake API. The element type is
+/// `None` until set.
+#[derive(Clone)]
+struct FakeTypeAndShape {
+    element_type: Option<ElementType>,
+    dims: Vec<i64>,
+    /// Names of the symbolic dimensions; missing names are empty.
+    symbolic: Vec<CString>,
+}
+
+impl FakeTypeAndShape {
+    fn to_type_info(&self) -> Result<TypeInfo> {
+        let Some(element_type) = self.element_type else {
+            bail!("Element type is not set")
+        };
+        let shape = self
+            .dims
+            .iter()
+            .enumerate()
+            .map(|(idx, dim)| match (*dim, self.symbolic.get(idx)) {
+                (0.., _) => Dim::Fixed(*dim as usize),
+                (_, Some(name)) if !name.is_empty() => {
+                    Dim::Symbolic(name.to_string_lossy().into_owned())
+                }
+                _ => Dim::Unknown,
+            })
+            .collect();
+        Ok(TypeInfo::Tensor {
+            element_type,
+            shape,
+        })
+    }
+}
+
+/// `OrtTypeInfo` of the fake API. Types other than tensors have no
+/// tensor info.
+struct FakeTypeInfo {
+    tensor: Option<FakeTypeAndShape>,
+}
+
+impl From<&TypeInfo> for FakeTypeInfo {
+    fn from(type_info: &TypeInfo) -> Self {
+        let TypeInfo::Tensor {
+            element_type,
+            shape,
+        } = type_info
+        else {
+            return FakeTypeInfo { tensor: None };
+        };
+        let (dims, symbolic) = shape
+            .iter()
+            .map(|dim| match dim {
+                Dim::Fixed(size) => (*size as i64, CString::default()),
+                Dim::Symbolic(name) => (-1, CString::new(name.as_str()).unwrap_or_default()),
+                Dim::Unknown => (-1, CString::default()),
+            })
+            .unzip();
+        FakeTypeInfo {
+            tensor: Some(FakeTypeAndShape {
+                element_type: Some(*element_type),
+                dims,
+                symbolic,
+            }),
+        }
+    }
+}
+
+/// `OrtLogger` of the fake API recording all messages.
+#[derive(Debug, Default)]
+struct FakeLogger {
+    messages: Mutex<Vec<(Severity, String)>>,
+}
+
+/// `OrtKernelInfo` of the fake API.
+struct FakeKernelInfo<'a> {
+    node_name: CString,
+    attributes: HashMap<String, AttributeValue>,
+    constant_inputs: HashMap<usize, Input>,
+    inputs: Vec<(CString, TypeInfo)>,
+    outputs: Vec<(CString, TypeInfo)>,
+    logger: &'a FakeLogger,
+}
+
+/// `OrtShapeInferContext` of the fake API. Outputs are set through a
+/// shared reference as with onnxruntime.
+struct FakeShapeInferContext<'a> {
+    inputs: Vec<FakeTypeInfo>,
+    attributes: &'a HashMap<String, AttributeValue>,
+    outputs: Vec<RefCell<Option<FakeTypeAndShape>>>,
+}
+
+/// `OrtKernelContext` of the fake API.
+struct FakeKernelContext<'a> {
+    inputs: Vec<Option<Input>>,
+    output_types: Vec<ElementType>,
+    outputs: Vec<Option<Input>>,
+    logger: &'a FakeLogger,
+}
+
+/// API backed by the fake objects above. Functions which are not
+/// supported are left unset or fail.
+static API: LazyLock<OrtApi> = LazyLock::new(|| {
+    let mut api: OrtApi = unsafe { std::mem::zeroed() };
+    api.CreateStatus = Some(create_status);
+    api.GetErrorCode = Some(get_error_code);
+    api.GetErrorMessage = Some(get_error_message);
+    api.ReleaseStatus = Some(release_status);
+
+    api.GetValueType = Some(get_value_type);
+    api.GetValueCount = Some(get_value_count);
+    api.GetValue = Some(get_value);
+    api.ReleaseValue = Some(release_value);
+    api.GetTensorTypeAndShape = Some(get_tensor_type_and_shape);
+    api.ReleaseTensorTypeAndShapeInfo = Some(release_tensor_type_and_shape_info);
+    api.GetTensorElementType = Some(get_tensor_element_type);
+    api.GetDimensionsCount = Some(get_dimensions_count);
+    api.GetDimensions = Some(get_dimensions);
+    api.GetSymbolicDimensions = Some(get_symbolic_dimensions);
+    api.GetTensorShapeElementCount = Some(get_tensor_shape_element_count);
+    api.CreateTensorTypeAndShapeInfo = Some(create_tensor_type_and_shape_info);
+    api.SetTensorElementType = Some(set_tensor_element_type);
+    api.SetDimensions = Some(set_dimensions);
+    api.SetSymbolicDimensions = Some(set_symbolic_dimensions);
+    api.GetOnnxTypeFromTypeInfo = Some(get_onnx_type_from_type_info);
+    api.CastTypeInfoToTensorInfo = Some(cast_type_info_to_tensor_info);
+    api.ReleaseTypeInfo = Some(release_type_info);
+    api.GetTensorMutableData = Some(get_tensor_mutable_data);
+    api.GetStringTensorDataLength = Some(get_string_tensor_data_length);
+    api.GetStringTensorContent = Some(get_string_tensor_content);
+    api.FillStringTensor = Some(fill_string_tensor);
+    api.GetAllocatorWithDefaultOptions = Some(get_allocator_with_default_options);
+
+    api.KernelInfoGetAttribute_float = Some(kernel_info_get_attribute_float);
+    api.KernelInfoGetAttribute_int64 = Some(kernel_info_get_attribute_int64);
+    api.KernelInfoGetAttribute_string = Some(kernel_info_get_attribute_string);
+    api.KernelInfoGetAttributeArray_float = Some(kernel_info_get_attribute_array_float);
+    api.KernelInfoGetAttributeArray_int64 = Some(kernel_info_get_attribute_array_int64);
+    api.KernelInfoGetAttribute_tensor = Some(kernel_info_get_attribute_tensor);
+    api.KernelInfoGetConstantInput_tensor = Some(kernel_info_get_constant_input_tensor);
+    api.KernelInfo_GetNodeName = Some(kernel_info_get_node_name);
+    api.KernelInfo_GetLogger = Some(kernel_info_get_logger);
+    api.KernelInfo_GetInputCount = Some(kernel_info_get_input_count);
+    api.KernelInfo_GetOutputCount = Some(kernel_info_get_output_count);
+    api.KernelInfo_GetInputName = Some(kernel_info_get_input_name);
+    api.KernelInfo_GetOutputName = Some(kernel_info_get_output_name);
+    api.KernelInfo_GetInputTypeInfo = Some(kernel_info_get_input_type_info);
+    api.KernelInfo_GetOutputTypeInfo = Some(kernel_info_get_output_type_info);
+
+    api.ShapeInferContext_GetInputCount = Some(shape_infer_context_get_input_count);
+    api.ShapeInferContext_GetInputTypeShape = Some(shape_infer_context_get_input_type_shape);
+    api.ShapeInferContext_GetAttribute = Some(shape_infer_context_get_attribute);
+    api.ShapeInferContext_SetOutputTypeShape = Some(shape_infer_context_set_output_type_shape);
+    api.ReadOpAttr = Some(read_op_attr);
+
+    api.KernelContext_GetInputCount = Some(kernel_context_get_input_count);
+    api.KernelContext_GetOutputCount = Some(kernel_context_get_output_count);
+    api.KernelContext_GetInput = Some(kernel_context_get_input);
+    api.KernelContext_GetOutput = Some(kernel_context_get_output);
+    api.KernelContext_GetLogger = Some(kernel_context_get_logger);
+    api.KernelContext_GetAllocator = Some(kernel_context_get_allocator);
+    api.KernelContext_GetResource = Some(kernel_context_get_resource);
+    api.KernelContext_ParallelFor = Some(kernel_context_parallel_for);
+    api.CreateCpuMemoryInfo = Some(create_cpu_memory_info);
+    api.ReleaseMemoryInfo = Some(release_memory_info);
+
+    api.Logger_LogMessage = Some(logger_log_message);
+    api.Logger_GetLoggingSeverityLevel = Some(logger_get_logging_severity_level);
+    api
+});
+
+/// Failed status with the given message.
+fn fail(message: &str) -> OrtStatusPtr {
+    let message = CString::new(message.replace('\0', "\\0")).unwrap_or_default();
+    Box::into_raw(Box::new(FakeStatus {
+        code: OrtErrorCode_ORT_FAIL,
+        message,
+    })) as OrtStatusPtr
+}
+
+/// Consume a status returned by an operator.
+fn status_to_result(status: OrtStatusPtr) -> Result<()> {
+    if status.is_null() {
+        return Ok(());
+    }
+    let status = unsafe { Box::from_raw(status as *mut FakeStatus) };
+    Err(anyhow!("{}", status.message.to_string_lossy()))
+}
+
+/// Turn an error into a failed status.
+fn to_status(result: Result<()>) -> OrtStatusPtr {
+    match result {
+        Ok(()) => std::ptr::null_mut(),
+        Err(err) => fail(&err.to_string()),
+    }
+}
+
+/// Write the result of `f` to `out` or turn its error into a status.
+unsafe fn write_out<T>(out: *mut T, f: impl FnOnce() -> Result<T>) -> OrtStatusPtr {
+    to_status(f().map(|value| unsafe { out.write(value) }))
+}
+
+unsafe extern "C" fn create_status(code: OrtErrorCode, msg: *const c_char) -> *mut OrtStatus {
+    let message = unsafe { CStr::from_ptr(msg) }.to_owned();
+    Box::into_raw(Box::new(FakeStatus { code, message })) as *mut OrtStatus
+}
+
+unsafe extern "C" fn get_error_code(status: *const OrtStatus) -> OrtErrorCode {
+    unsafe { &*(status as *const FakeStatus) }.code
+}
+
+unsafe extern "C" fn get_error_message(status: *const OrtStatus) -> *const c_char {
+    unsafe { &*(status as *const FakeStatus) }.message.as_ptr()
+}
+
+unsafe extern "C" fn release_status(status: *mut OrtStatus) {
+    drop(unsafe { Box::from_raw(status as *mut FakeStatus) });
+}
+
+/// `OrtValue`s of the fake API are [`Input`]s.
+unsafe fn value<'a>(value: *const OrtValue) -> &'a Input {
+    unsafe { &*(value as *const Input) }
+}
+
+unsafe fn tensor<'a>(value: *const OrtValue) -> Result<&'a Tensor> {
+    match unsafe { self::value(value) } {
+        Input::Tensor(tensor) => Ok(tensor),
+        other => bail!("Value {:?} is not a tensor", other),
+    }
+}
+
+unsafe fn tensor_mut<'a>(value: *mut OrtValue) -> Result<&'a mut Tensor> {
+    match unsafe { &mut *(value as *mut Input) } {
+        Input::Tensor(tensor) => Ok(tensor),
+        other => bail!("Value {:?} is not a tensor", other),
+    }
+}
+
+/// Turn `input` into a value owned by the caller which releases it
+/// through `ReleaseValue`.
+fn into_owned_value(input: Input) -> *mut OrtValue {
+    OWNED_VALUES.with(|count| count.set(count.get() + 1));
+    Box::into_raw(Box::new(input)) as *mut OrtValue
+}
+
+unsafe extern "C" fn get_value_type(value: *const OrtValue, out: *mut ONNXType) -> OrtStatusPtr {
+    let onnx_type = match unsafe { self::value(value) } {
+        Input::Tensor(_) => ONNXType_ONNX_TYPE_TENSOR,
+        Input::Sequence(_) => ONNXType_ONNX_TYPE_SEQUENCE,
+        Input::Map { .. } => ONNXType_ONNX_TYPE_MAP,
+    };
+    unsafe { write_out(out, || Ok(onnx_type)) }
+}
+
+unsafe extern "C" fn get_value_count(value: *const OrtValue, out: *mut usize) -> OrtStatusPtr {
+    unsafe {
+        write_out(out, || match self::value(value) {
+            Input::Sequence(elements) => Ok(elements.len()),
+            Input::Map { .. } => Ok(2),
+            Input::Tensor(_) => bail!("Tensors have no value count"),
+        })
+    }
+}
+
+unsafe extern "C" fn get_value(
+    value: *const OrtValue,
+    index: c_int,
+    _allocator: *mut OrtAllocator,
+    out: *mut *mut OrtValue,
+) -> OrtStatusPtr {
+    unsafe {
+        write_out(out, || {
+            // onnxruntime copies the elements into new values, too
+            let element = match (self::value(value), index) {
+                (Input::Sequence(elements), _) => elements.get(index as usize),
+                (Input::Map { keys, .. }, 0) => Some(keys),
+                (Input::Map { values, .. }, 1) => Some(values),
+                (Input::Map { .. }, _) => None,
+                (Input::Tensor(_), _) => bail!("Tensors have no elements"),
+            };
+            let Some(element) = element else {
+                bail!("Index '{}' is out of range", index)
+            };
+            Ok(into_owned_value(Input::Tensor(element.clone())))
+        })
+    }
+}
+
+unsafe extern "C" fn release_value(value: *mut OrtValue) {
+    OWNED_VALUES.with(|count| count.set(count.get() - 1));
+    drop(unsafe { Box::from_raw(value as *mut Input) });
+}
+
+unsafe extern "C" fn get_tensor_type_and_shape(
+    value: *const OrtValue,
+    out: *mut *mut OrtTensorTypeAndShapeInfo,
+) -> OrtStatusPtr {
+    unsafe {
+        write_out(out, || {
+            let tensor = tensor(value)?;
+            let info = FakeTypeAndShape {
+                element_type: Some(tensor.element_type),
+                dims: tensor.shape.iter().map(|dim| *dim as i64).collect(),
+                symbolic: vec![],
+            };
+            Ok(Box::into_raw(Box::new(info)) as *mut _)
+        })
+    }
+}
+
+unsafe extern "C" fn release_tensor_type_and_shape_info(info: *mut OrtTensorTypeAndShapeInfo) {
+    drop(unsafe { Box::from_raw(info as *mut FakeTypeAndShape) });
+}
+
+unsafe fn type_and_shape<'a>(info: *const OrtTensorTypeAndShapeInfo) -> &'a FakeTypeAndShape {
+    unsafe { &*(info as *const FakeTypeAndShape) }
+}
+
+unsafe extern "C" fn get_tensor_element_type(
+    info: *const OrtTensorTypeAndShapeInfo,
+    out: *mut ONNXTensorElementDataType,
+) -> OrtStatusPtr {
+    let info = unsafe { type_and_shape(info) };
+    let encoding = info.element_type.map_or(
+        ONNXTensorElementDataType_ONNX_TENSOR_ELEMENT_DATA_TYPE_UNDEFINED,
+        ElementType::to_ort_encoding,
+    );
+    unsafe { write_out(out, || Ok(encoding)) }
+}
+
+unsafe extern "C" fn get_dimensions_count(
+    info: *const OrtTensorTypeAndShapeInfo,
+    out: *mut usize,
+) -> OrtStatusPtr {
+    let info = unsafe { type_and_shape(info) };
+    unsafe { write_out(out, || Ok(info.dims.len())) }
+}
+
+unsafe extern "C" fn get_dimensions(
+    info: *const OrtTensorTypeAndShapeInfo,
+    dim_values: *mut i64,
+    dim_values_length: usize,
+) -> OrtStatusPtr {
+    let dims = &unsafe { type_and_shape(info) }.dims;
+    let n = dims.len().min(dim_values_length);
+    unsafe { std::ptr::copy_nonoverlapping(dims.as_ptr(), dim_values, n) };
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn get_symbolic_dimensions(
+    info: *const OrtTensorTypeAndShapeInfo,
+    dim_params: *mut *const c_char,
+    dim_params_length: usize,
+) -> OrtStatusPtr {
+    let symbolic = &unsafe { type_and_shape(info) }.symbolic;
+    for idx in 0..dim_params_length {
+        let name = symbolic.get(idx).map_or(c"", CString::as_c_str);
+        unsafe { dim_params.add(idx).write(name.as_ptr()) };
+    }
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn create_tensor_type_and_shape_info(
+    out: *mut *mut OrtTensorTypeAndShapeInfo,
+) -> OrtStatusPtr {
+    let info = FakeTypeAndShape {
+        element_type: None,
+        dims: vec![],
+        symbolic: vec![],
+    };
+    unsafe { write_out(out, || Ok(Box::into_raw(Box::new(info)) as *mut _)) }
+}
+
+unsafe fn type_and_shape_mut<'a>(info: *mut OrtTensorTypeAndShapeInfo) -> &'a mut FakeTypeAndShape {
+    unsafe { &mut *(info as *mut FakeTypeAndShape) }
+}
+
+unsafe extern "C" fn set_tensor_element_type(
+    info: *mut OrtTensorTypeAndShapeInfo,
+    ty: ONNXTensorElementDataType,
+) -> OrtStatusPtr {
+    let info = unsafe { type_and_shape_mut(info) };
+    to_status(ElementType::try_from_ort_encoding(ty).map(|ty| info.element_type = Some(ty)))
+}
+
+unsafe extern "C" fn set_dimensions(
+    info: *mut OrtTensorTypeAndShapeInfo,
+    dim_values: *const i64,
+    dim_count: usize,
+) -> OrtStatusPtr {
+    let info = unsafe { type_and_shape_mut(info) };
+    info.dims = unsafe { std::slice::from_raw_parts(dim_values, dim_count) }.to_vec();
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn set_symbolic_dimensions(
+    info: *mut OrtTensorTypeAndShapeInfo,
+    dim_params: *mut *const c_char,
+    dim_params_length: usize,
+) -> OrtStatusPtr {
+    let info = unsafe { type_and_shape_mut(info) };
+    info.symbolic = unsafe { std::slice::from_raw_parts(dim_params, dim_params_length) }
+        .iter()
+        .map(|name| unsafe { CStr::from_ptr(*name) }.to_owned())
+        .collect();
+    std::ptr::null_mut()
+}
+
+unsafe fn type_info<'a>(info: *const OrtTypeInfo) -> &'a FakeTypeInfo {
+    unsafe { &*(info as *const FakeTypeInfo) }
+}
+
+unsafe extern "C" fn get_onnx_type_from_type_info(
+    info: *const OrtTypeInfo,
+    out: *mut ONNXType,
+) -> OrtStatusPtr {
+    // `TypeInfo` does not tell sequences and maps apart
+    let onnx_type = match unsafe { type_info(info) }.tensor {
+        Some(_) => ONNXType_ONNX_TYPE_TENSOR,
+        None => ONNXType_ONNX_TYPE_UNKNOWN,
+    };
+    unsafe { write_out(out, || Ok(onnx_type)) }
+}
+
+unsafe extern "C" fn cast_type_info_to_tensor_info(
+    info: *const OrtTypeInfo,
+    out: *mut *const OrtTensorTypeAndShapeInfo,
+) -> OrtStatusPtr {
+    // Null for other types, as with onnxruntime
+    let tensor = unsafe { type_info(info) }.tensor.as_ref();
+    let tensor = tensor.map_or(std::ptr::null(), |tensor| {
+        tensor as *const FakeTypeAndShape as *const OrtTensorTypeAndShapeInfo
+    });
+    unsafe { write_out(out, || Ok(tensor)) }
+}
+
+unsafe extern "C" fn release_type_info(info: *mut OrtTypeInfo) {
+    drop(unsafe { Box::from_raw(info as *mut FakeTypeInfo) });
+}
+
+unsafe extern "C" fn get_tensor_shape_element_count(
+    info: *const OrtTensorTypeAndShapeInfo,
+    out: *mut usize,
+) -> OrtStatusPtr {
+    let info = unsafe { type_and_shape(info) };
+    unsafe { write_out(out, || Ok(info.dims.iter().product::<i64>() as usize)) }
+}
+
+unsafe extern "C" fn get_tensor_mutable_data(
+    value: *mut OrtValue,
+    out: *mut *mut c_void,
+) -> OrtStatusPtr {
+    unsafe {
+        write_out(out, || match &mut tensor_mut(value)?.data {
+            TensorData::Plain(buf) => Ok(buf.as_mut_ptr() as *mut c_void),
+            TensorData::Strings(_) => bail!("String tensors have no mutable data"),
+        })
+    }
+}
+
+fn strings(tensor: &Tensor) -> Result<&[String]> {
+    match &tensor.data {
+        TensorData::Strings(strings) => Ok(strings),
+        TensorData::Plain(_) => bail!(
+            "Tensor of type {:?} is not a string tensor",
+            tensor.element_type
+        ),
+    }
+}
+
+unsafe extern "C" fn get_string_tensor_data_length(
+    value: *const OrtValue,
+    len: *mut usize,
+) -> OrtStatusPtr {
+    unsafe {
+        write_out(len, || {
+            Ok(strings(tensor(value)?)?.iter().map(String::len).sum())
+        })
+    }
+}
+
+unsafe extern "C" fn get_string_tensor_content(
+    value: *const OrtValue,
+    s: *mut c_void,
+    s_len: usize,
+    offsets: *mut usize,
+    offsets_len: usize,
+) -> OrtStatusPtr {
+    let Ok(strings) = unsafe { tensor(value) }.and_then(strings) else {
+        return fail("Not a string tensor");
+    };
+    if offsets_len != strings.len() || s_len < strings.iter().map(String::len).sum() {
+        return fail("Buffers are too small for the string tensor");
+    }
+    let mut offset = 0;
+    for (idx, string) in strings.iter().enumerate() {
+        unsafe {
+            offsets.add(idx).write(offset);
+            std::ptr::copy_nonoverlapping(
+                string.as_ptr(),
+                (s as *mut u8).add(offset),
+                string.len(),
+            );
+        }
+        offset += string.len();
+    }
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn fill_string_tensor(
+    value: *mut OrtValue,
+    s: *const *const c_char,
+    s_len: usize,
+) -> OrtStatusPtr {
+    let Ok(tensor) = (unsafe { tensor_mut(value) }) else {
+        return fail("Not a string tensor");
+    };
+    let len = tensor.len();
+    let TensorData::Strings(strings) = &mut tensor.data else {
+        return fail("Not a string tensor");
+    };
+    if s_len != len {
+        return fail("Number of strings does not match the shape of the tensor");
+    }
+    for (idx, string) in strings.iter_mut().enumerate() {
+        *string = unsafe { CStr::from_ptr(*s.add(idx)) }
+            .to_string_lossy()
+            .into_owned();
+    }
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn get_allocator_with_default_options(
+    out: *mut *mut OrtAllocator,
+) -> OrtStatusPtr {
+    // Values of the fake API are allocated by Rust; the allocator is
+    // never used.
+    unsafe { write_out(out, || Ok(NonNull::dangling().as_ptr())) }
+}
+
+unsafe fn kernel_info<'a>(info: *const OrtKernelInfo) -> &'a FakeKernelInfo<'a> {
+    unsafe { &*(info as *const FakeKernelInfo) }
+}
+
+/// Look up the attribute `name` and convert it with `f` which returns
+/// `None` if the attribute is of another type.
+unsafe fn read_attribute<'a, T>(
+    info: *const OrtKernelInfo,
+    name: *const c_char,
+    f: impl FnOnce(&'a AttributeValue) -> Option<T>,
+) -> Result<T> {
+    let info: &'a FakeKernelInfo = unsafe { kernel_info(info) };
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    // Same message as onnxruntime which identifies missing attributes
+    let Some(value) = info.attributes.get(name.as_ref()) else {
+        bail!("{}'{}'is defined.", MISSING_ATTRIBUTE_PREFIX, name)
+    };
+    f(value).ok_or_else(|| anyhow!("Attribute name and type don't match for '{}'", name))
+}
+
+/// Two-phase read of an array into `out` as done by onnxruntime:
+/// only the size is written if `out` is null.
+unsafe fn write_array<T: Copy>(values: &[T], out: *mut T, size: *mut usize) -> Result<()> {
+    if !out.is_null() {
+        if unsafe { *size } < values.len() {
+            bail!("Buffer of size {} is too small", unsafe { *size })
+        }
+        unsafe { std::ptr::copy_nonoverlapping(values.as_ptr(), out, values.len()) };
+    }
+    unsafe { size.write(values.len()) };
+    Ok(())
+}
+
+unsafe extern "C" fn kernel_info_get_attribute_float(
+    info: *const OrtKernelInfo,
+    name: *const c_char,
+    out: *mut f32,
+) -> OrtStatusPtr {
+    unsafe {
+        write_out(out, || {
+            read_attribute(info, name, |value| match value {
+                AttributeValue::Float(value) => Some(*value),
+                _ => None,
+            })
+        })
+    }
+}
+
+unsafe extern "C" fn kernel_info_get_attribute_int64(
+    info: *const OrtKernelInfo,
+    name: *const c_char,
+    out: *mut i64,
+) -> OrtStatusPtr {
+    unsafe {
+        write_out(out, || {
+            read_attribute(info, name, |value| match value {
+                AttributeValue::Int(value) => Some(*value),
+                _ => None,
+            })
+        })
+    }
+}
+
+unsafe extern "C" fn kernel_info_get_attribute_string(
+    info: *const OrtKernelInfo,
+    name: *const c_char,
+    out: *mut c_char,
+    size: *mut usize,
+) -> OrtStatusPtr {
+    let result = unsafe {
+        read_attribute(info, name, |value| match value {
+            AttributeValue::String(value) => CString::new(value.as_str()).ok(),
+            _ => None,
+        })
+    }
+    .and_then(|value| unsafe { write_array(value.as_bytes_with_nul(), out as *mut u8, size) });
+    to_status(result)
+}
+
+unsafe extern "C" fn kernel_info_get_attribute_array_float(
+    info: *const OrtKernelInfo,
+    name: *const c_char,
+    out: *mut f32,
+    size: *mut usize,
+) -> OrtStatusPtr {
+    let result = unsafe {
+        read_attribute(info, name, |value| match value {
+            AttributeValue::Floats(values) => Some(values.as_slice()),
+            _ => None,
+        })
+    }
+    .and_then(|values| unsafe { write_array(values, out, size) });
+    to_status(result)
+}
+
+unsafe extern "C" fn kernel_info_get_attribute_array_int64(
+    info: *const OrtKernelInfo,
+    name: *const c_char,
+    out: *mut i64,
+    size: *mut usize,
+) -> OrtStatusPtr {
+    let result = unsafe {
+        read_attribute(info, name, |value| match value {
+            AttributeValue::Ints(values) => Some(values.as_slice()),
+            _ => None,
+        })
+    }
+    .and_then(|values| unsafe { write_array(values, out, size) });
+    to_status(result)
+}
+
+unsafe extern "C" fn kernel_info_get_attribute_tensor(
+    info: *const OrtKernelInfo,
+    name: *const c_char,
+    _allocator: *mut OrtAllocator,
+    out: *mut *mut OrtValue,
+) -> OrtStatusPtr {
+    unsafe {
+        write_out(out, || {
+            let tensor = read_attribute(info, name, |value| match value {
+                AttributeValue::Tensor(tensor) => Some(tensor.clone()),
+                _ => None,
+            })?;
+            Ok(into_owned_value(Input::Tensor(tensor)))
+        })
+    }
+}
+
+unsafe extern "C" fn kernel_info_get_constant_input_tensor(
+    info: *const OrtKernelInfo,
+    index: usize,
+    is_constant: *mut c_int,
+    out: *mut *const OrtValue,
+) -> OrtStatusPtr {
+    let info = unsafe { kernel_info(info) };
+    let value = info.constant_inputs.get(&index);
+    unsafe {
+        is_constant.write(value.is_some() as c_int);
+        out.write(value.map_or(std::ptr::null(), |input| {
+            input as *const Input as *const OrtValue
+        }));
+    }
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn kernel_info_get_node_name(
+    info: *const OrtKernelInfo,
+    out: *mut c_char,
+    size: *mut usize,
+) -> OrtStatusPtr {
+    let name = &unsafe { kernel_info(info) }.node_name;
+    let result = unsafe { write_array(name.as_bytes_with_nul(), out as *mut u8, size) };
+    to_status(result)
+}
+
+unsafe extern "C" fn kernel_info_get_logger(
+    info: *const OrtKernelInfo,
+    logger: *mut *const OrtLogger,
+) -> OrtStatusPtr {
+    let info = unsafe { kernel_info(info) };
+    unsafe { write_out(logger, || Ok(info.logger as *const _ as *const OrtLogger)) }
+}
+
+/// Define functions of the fake API which always fail.
+macro_rules! unsupported {
+    ($($fun:ident($($arg:ty),*) => $name:literal;)*) => {
+        $(
+            unsafe extern "C" fn $fun($(_: $arg),*) -> OrtStatusPtr {
+                fail(concat!($name, " is not supported by the testing API"))
+            }
+        )*
+    };
+}
+
+unsafe extern "C" fn kernel_info_get_input_count(
+    info: *const OrtKernelInfo,
+    out: *mut usize,
+) -> OrtStatusPtr {
+    let info = unsafe { kernel_info(info) };
+    unsafe { write_out(out, || Ok(info.inputs.len())) }
+}
+
+unsafe extern "C" fn kernel_info_get_output_count(
+    info: *const OrtKernelInfo,
+    out: *mut usize,
+) -> OrtStatusPtr {
+    let info = unsafe { kernel_info(info) };
+    unsafe { write_out(out, || Ok(info.outputs.len())) }
+}
+
+/// Input or output `index` of the node.
+fn node_arg(args: &[(CString, TypeInfo)], index: usize) -> Result<&(CString, TypeInfo)> {
+    args.get(index)
+        .ok_or_else(|| anyhow!("Index '{}' is out of range", index))
+}
+
+unsafe extern "C" fn kernel_info_get_input_name(
+    info: *const OrtKernelInfo,
+    index: usize,
+    out: *mut c_char,
+    size: *mut usize,
+) -> OrtStatusPtr {
+    let info = unsafe { kernel_info(info) };
+    let result = node_arg(&info.inputs, index).and_then(|(name, _)| unsafe {
+        write_array(name.as_bytes_with_nul(), out as *mut u8, size)
+    });
+    to_status(result)
+}
+
+unsafe extern "C" fn kernel_info_get_output_name(
+    info: *const OrtKernelInfo,
+    index: usize,
+    out: *mut c_char,
+    size: *mut usize,
+) -> OrtStatusPtr {
+    let info = unsafe { kernel_info(info) };
+    let result = node_arg(&info.outputs, index).and_then(|(name, _)| unsafe {
+        write_array(name.as_bytes_with_nul(), out as *mut u8, size)
+    });
+    to_status(result)
+}
+
+unsafe extern "C" fn kernel_info_get_input_type_info(
+    info: *const OrtKernelInfo,
+    index: usize,
+    out: *mut *mut OrtTypeInfo,
+) -> OrtStatusPtr {
+    let info = unsafe { kernel_info(info) };
+    unsafe {
+        write_out(out, || {
+            let (_, type_info) = node_arg(&info.inputs, index)?;
+            Ok(Box::into_raw(Box::new(FakeTypeInfo::from(type_info))) as *mut _)
+        })
+    }
+}
+
+unsafe extern "C" fn kernel_info_get_output_type_info(
+    info: *const OrtKernelInfo,
+    index: usize,
+    out: *mut *mut OrtTypeInfo,
+) -> OrtStatusPtr {
+    let info = unsafe { kernel_info(info) };
+    unsafe {
+        write_out(out, || {
+            let (_, type_info) = node_arg(&info.outputs, index)?;
+            Ok(Box::into_raw(Box::new(FakeTypeInfo::from(type_info))) as *mut _)
+        })
+    }
+}
+
+unsupported! {
+    kernel_context_get_allocator(*const OrtKernelContext, *const OrtMemoryInfo, *mut *mut OrtAllocator)
+        => "KernelContext_GetAllocator";
+}
+
+unsafe fn shape_infer_context<'a>(
+    context: *const OrtShapeInferContext,
+) -> &'a FakeShapeInferContext<'a> {
+    unsafe { &*(context as *const FakeShapeInferContext) }
+}
+
+unsafe extern "C" fn shape_infer_context_get_input_count(
+    context: *const OrtShapeInferContext,
+    out: *mut usize,
+) -> OrtStatusPtr {
+    let context = unsafe { shape_infer_context(context) };
+    unsafe { write_out(out, || Ok(context.inputs.len())) }
+}
+
+unsafe extern "C" fn shape_infer_context_get_input_type_shape(
+    context: *const OrtShapeInferContext,
+    index: usize,
+    info: *mut *mut OrtTensorTypeAndShapeInfo,
+) -> OrtStatusPtr {
+    let context = unsafe { shape_infer_context(context) };
+    unsafe {
+        write_out(info, || {
+            let Some(input) = context.inputs.get(index) else {
+                bail!("Input index '{}' is out of range", index)
+            };
+            let Some(tensor) = &input.tensor else {
+                bail!("Input '{}' is not a tensor", index)
+            };
+            // Owned by the context
+            Ok(tensor as *const FakeTypeAndShape as *mut OrtTensorTypeAndShapeInfo)
+        })
+    }
+}
+
+unsafe extern "C" fn shape_infer_context_get_attribute(
+    context: *const OrtShapeInferContext,
+    attr_name: *const c_char,
+    attr: *mut *const OrtOpAttr,
+) -> OrtStatusPtr {
+    let context = unsafe { shape_infer_context(context) };
+    let name = unsafe { CStr::from_ptr(attr_name) }.to_string_lossy();
+    unsafe {
+        write_out(attr, || match context.attributes.get(name.as_ref()) {
+            Some(value) => Ok(value as *const AttributeValue as *const OrtOpAttr),
+            None => bail!("Attribute '{}' does not exist", name),
+        })
+    }
+}
+
+unsafe extern "C" fn shape_infer_context_set_output_type_shape(
+    context: *const OrtShapeInferContext,
+    index: usize,
+    info: *const OrtTensorTypeAndShapeInfo,
+) -> OrtStatusPtr {
+    let context = unsafe { shape_infer_context(context) };
+    let Some(output) = context.outputs.get(index) else {
+        return fail(&format!("Output index '{index}' is out of range"));
+    };
+    // onnxruntime copies the type and shape, too
+    *output.borrow_mut() = Some(unsafe { type_and_shape(info) }.clone());
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn read_op_attr(
+    op_attr: *const OrtOpAttr,
+    ty: OrtOpAttrType,
+    data: *mut c_void,
+    len: usize,
+    out: *mut usize,
+) -> OrtStatusPtr {
+    let value = unsafe { &*(op_attr as *const AttributeValue) };
+    #[allow(non_upper_case_globals)]
+    let bytes: Vec<u8> = match (ty, value) {
+        (OrtOpAttrType_ORT_OP_ATTR_FLOAT, AttributeValue::Float(value)) => {
+            value.to_ne_bytes().to_vec()
+        }
+        (OrtOpAttrType_ORT_OP_ATTR_INT, AttributeValue::Int(value)) => value.to_ne_bytes().to_vec(),
+        (OrtOpAttrType_ORT_OP_ATTR_STRING, AttributeValue::String(value)) => value.clone().into(),
+        (OrtOpAttrType_ORT_OP_ATTR_FLOATS, AttributeValue::Floats(values)) => values
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect(),
+        (OrtOpAttrType_ORT_OP_ATTR_INTS, AttributeValue::Ints(values)) => values
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect(),
+        _ => return fail("Attribute type does not match"),
+    };
+    // The required size is reported even if the buffer is too small
+    unsafe { out.write(bytes.len()) };
+    if len < bytes.len() {
+        return fail("Size of data not large enough to hold the attribute");
+    }
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), data as *mut u8, bytes.len()) };
+    std::ptr::null_mut()
+}
+
+unsafe fn kernel_context<'a>(context: *const OrtKernelContext) -> &'a mut FakeKernelContext<'a> {
+    unsafe { &mut *(context as *mut FakeKernelContext) }
+}
+
+unsafe extern "C" fn kernel_context_get_input_count(
+    context: *const OrtKernelContext,
+    out: *mut usize,
+) -> OrtStatusPtr {
+    let context = unsafe { kernel_context(context) };
+    unsafe { write_out(out, || Ok(context.inputs.len())) }
+}
+
+unsafe extern "C" fn kernel_context_get_output_count(
+    context: *const OrtKernelContext,
+    out: *mut usize,
+) -> OrtStatusPtr {
+    let context = unsafe { kernel_context(context) };
+    unsafe { write_out(out, || Ok(context.outputs.len())) }
+}
+
+unsafe extern "C" fn kernel_context_get_input(
+    context: *const OrtKernelContext,
+    index: usize,
+    out: *mut *const OrtValue,
+) -> OrtStatusPtr {
+    let context = unsafe { kernel_context(context) };
+    unsafe {
+        write_out(out, || {
+            let Some(input) = context.inputs.get_mut(index) else {
+                bail!("Input index '{}' is out of range", index)
+            };
+            // Missing optional inputs are null
+            Ok(input.as_ref().map_or(std::ptr::null(), |input| {
+                input as *const Input as *const OrtValue
+            }))
+        })
+    }
+}
+
+unsafe extern "C" fn kernel_context_get_output(
+    context: *mut OrtKernelContext,
+    index: usize,
+    dim_values: *const i64,
+    dim_count: usize,
+    out: *mut *mut OrtValue,
+) -> OrtStatusPtr {
+    let context = unsafe { kernel_context(context) };
+    unsafe {
+        write_out(out, || {
+            let Some(element_type) = context.output_types.get(index) else {
+                bail!("Output index '{}' is out of range", index)
+            };
+            let dims = std::slice::from_raw_parts(dim_values, dim_count);
+            if dims.iter().any(|dim| *dim < 0) {
+                bail!("Output shape {:?} has negative dimensions", dims)
+            }
+            let shape: Vec<_> = dims.iter().map(|dim| *dim as usize).collect();
+            let output = &mut context.outputs[index];
+            match output {
+                Some(Input::Tensor(tensor)) if tensor.shape == shape => {}
+                Some(Input::Tensor(tensor)) => bail!(
+                    "Output '{}' was already allocated with shape {:?}",
+                    index,
+                    tensor.shape
+                ),
+                Some(other) => bail!("Output {:?} is not a tensor", other),
+                None => *output = Some(Input::Tensor(Tensor::zeros(*element_type, shape))),
+            }
+            Ok(output.as_mut().unwrap() as *mut Input as *mut OrtValue)
+        })
+    }
+}
+
+unsafe extern "C" fn kernel_context_get_logger(
+    context: *const OrtKernelContext,
+    logger: *mut *const OrtLogger,
+) -> OrtStatusPtr {
+    let context = unsafe { kernel_context(context) };
+    unsafe {
+        write_out(
+            logger,
+            || Ok(context.logger as *const _ as *const OrtLogger),
+        )
+    }
+}
+
+unsafe extern "C" fn kernel_context_get_resource(
+    _context: *const OrtKernelContext,
+    _resource_version: c_int,
+    _resource_id: c_int,
+    resource: *mut *mut c_void,
+) -> OrtStatusPtr {
+    // There is no execution provider providing resources
+    unsafe { write_out(resource, || Ok(std::ptr::null_mut())) }
+}
+
+/// Number of threads of the fake intra-op thread pool.
+const THREAD_POOL_SIZE: usize = 4;
+
+unsafe extern "C" fn kernel_context_parallel_for(
+    _context: *const OrtKernelContext,
+    fun: Option<unsafe extern "C" fn(*mut c_void, usize)>,
+    total: usize,
+    _num_batch: usize,
+    usr_data: *mut c_void,
+) -> OrtStatusPtr {
+    let Some(fun) = fun else {
+        return fail("No function given to parallel_for");
+    };
+    // Like onnxruntime's thread pool, workers take the next index
+    // until all have been run
+    let next = AtomicUsize::new(0);
+    let usr_data = usr_data as usize;
+    std::thread::scope(|scope| {
+        for _ in 0..THREAD_POOL_SIZE.min(total) {
+            scope.spawn(|| {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    if idx >= total {
+                        break;
+                    }
+                    unsafe { fun(usr_data as *mut c_void, idx) };
+                }
+            });
+        }
+    });
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn create_cpu_memory_info(
+    _type: OrtAllocatorType,
+    _mem_type: OrtMemType,
+    out: *mut *mut OrtMemoryInfo,
+) -> OrtStatusPtr {
+    unsafe { write_out(out, || Ok(NonNull::dangling().as_ptr())) }
+}
+
+unsafe extern "C" fn release_memory_info(_info: *mut OrtMemoryInfo) {}
+
+unsafe extern "C" fn logger_log_message(
+    logger: *const OrtLogger,
+    log_severity_level: OrtLoggingLevel,
+    message: *const c_char,
+    _file_path: *const c_char,
+    _line_number: c_int,
+    _func_name: *const c_char,
+) -> OrtStatusPtr {
+    let logger = unsafe { &*(logger as *const FakeLogger) };
+    let message = unsafe { CStr::from_ptr(message) }
+        .to_string_lossy()
+        .into_owned();
+    to_status(Severity::from_ort(log_severity_level).map(|severity| {
+        logger
+            .messages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((severity, message))
+    }))
+}
+
+unsafe extern "C" fn logger_get_logging_severity_level(
+    _logger: *const OrtLogger,
+    out: *mut OrtLoggingLevel,
+) -> OrtStatusPtr {
+    // All messages are recorded
+    unsafe { write_out(out, || Ok(OrtLoggingLevel_ORT_LOGGING_LEVEL_VERBOSE)) }
+}
@@ -0,0 +1,455 @@
+//! Minimal builder of serialized ONNX models.
+//!
+//! Only the subset of `onnx.proto` which is needed to describe a graph
+//! of custom operators is supported. The protobuf wire format is
+//! written by hand to avoid depending on `protoc` or the `onnx`
+//! Python package.
+
+use crate::api::ElementType;
+use crate::shape_inference::Dim;
+
+use super::{AttributeValue, Tensor, TensorData};
+
+/// IR version written into the models (`IR_VERSION_2023_5_5`).
+const IR_VERSION: u64 = 9;
+
+/// ONNX model consisting of a single graph.
+///
+/// ```ignore
+/// use ort_custom_op::prelude::*;
+/// use ort_custom_op::testing::{Model, Node};
+///
+/// let model = Model::new()
+///     .opset("my.domain", 1)
+///     .node(Node::new("my.domain", "CustomAdd").inputs(&["A", "B"]).outputs(&["C"]))
+///     .input("A", ElementType::F32, None)
+///     .input("B", ElementType::F32, None)
+///     .output("C", ElementType::F32, None);
+/// let bytes = model.to_bytes();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+    opsets: Vec<(String, i64)>,
+    nodes: Vec<Node>,
+    inputs: Vec<ValueInfo>,
+    outputs: Vec<ValueInfo>,
+    initializers: Vec<(String, Tensor)>,
+}
+
+/// Node of a [`Model`].
+#[derive(Debug, Clone)]
+pub struct Node {
+    domain: String,
+    op_type: String,
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    attributes: Vec<(String, AttributeValue)>,
+}
+
+/// Input or output of the graph.
+#[derive(Debug, Clone)]
+struct ValueInfo {
+    name: String,
+    ty: ValueType,
+}
+
+#[derive(Debug, Clone)]
+enum ValueType {
+    Tensor {
+        element_type: ElementType,
+        shape: Option<Vec<Dim>>,
+    },
+    /// `map(key, tensor(value))` with scalar values.
+    Map {
+        key: ElementType,
+        value: ElementType,
+    },
+}
+
+impl Model {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Import `version` of the operator set of `domain`.
+    pub fn opset(mut self, domain: &str, version: i64) -> Self {
+        self.opsets.push((domain.to_string(), version));
+        self
+    }
+
+    /// Append a node to the graph. Nodes must be topologically sorted.
+    pub fn node(mut self, node: Node) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    /// Declare a tensor input of the graph. A shape of `None` leaves
+    /// the rank unspecified.
+    pub fn input(mut self, name: &str, element_type: ElementType, shape: Option<&[Dim]>) -> Self {
+        self.inputs
+            .push(ValueInfo::new(name, element_type, shape.map(<[_]>::to_vec)));
+        self
+    }
+
+    /// Declare an input of type `map(key, value)` of the graph such as
+    /// the class probabilities consumed by operators of `ai.onnx.ml`.
+    pub fn map_input(mut self, name: &str, key: ElementType, value: ElementType) -> Self {
+        self.inputs.push(ValueInfo {
+            name: name.to_string(),
+            ty: ValueType::Map { key, value },
+        });
+        self
+    }
+
+    /// Declare a tensor output of the graph. A shape of `None` leaves
+    /// the rank unspecified.
+    pub fn output(mut self, name: &str, element_type: ElementType, shape: Option<&[Dim]>) -> Self {
+        self.outputs
+            .push(ValueInfo::new(name, element_type, shape.map(<[_]>::to_vec)));
+        self
+    }
+
+    /// Add a constant tensor to the graph.
+    pub fn initializer(mut self, name: &str, value: impl Into<Tensor>) -> Self {
+        self.initializers.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Serialize the model into the protobuf representation of a
+    /// `ModelProto`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut model = Encoder::default();
+        model.varint(1, IR_VERSION);
+        model.message(7, |graph| {
+            for node in &self.nodes {
+                graph.message(1, |enc| node.encode(enc));
+            }
+            graph.string(2, "graph");
+            for (name, tensor) in &self.initializers {
+                graph.message(5, |enc| encode_tensor(enc, name, tensor));
+            }
+            for input in &self.inputs {
+                graph.message(11, |enc| input.encode(enc));
+            }
+            for output in &self.outputs {
+                graph.message(12, |enc| output.encode(enc));
+            }
+        });
+        for (domain, version) in &self.opsets {
+            model.message(8, |opset| {
+                opset.string(1, domain);
+                opset.varint(2, *version as u64);
+            });
+        }
+        model.buf
+    }
+}
+
+impl Node {
+    pub fn new(domain: &str, op_type: &str) -> Self {
+        Self {
+            domain: domain.to_string(),
+            op_type: op_type.to_string(),
+            name: String::new(),
+            inputs: vec![],
+            outputs: vec![],
+            attributes: vec![],
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Names of the inputs. An empty name denotes a missing optional
+    /// input.
+    pub fn inputs(mut self, names: &[&str]) -> Self {
+        self.inputs = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    pub fn outputs(mut self, names: &[&str]) -> Self {
+        self.outputs = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    pub fn attribute(mut self, name: &str, value: impl Into<AttributeValue>) -> Self {
+        self.attributes.push((name.to_string(), value.into()));
+        self
+    }
+
+    fn encode(&self, enc: &mut Encoder) {
+        for input in &self.inputs {
+            enc.string(1, input);
+        }
+        for output in &self.outputs {
+            enc.string(2, output);
+        }
+        if !self.name.is_empty() {
+            enc.string(3, &self.name);
+        }
+        enc.string(4, &self.op_type);
+        for (name, value) in &self.attributes {
+            enc.message(5, |enc| encode_attribute(enc, name, value));
+        }
+        enc.string(7, &self.domain);
+    }
+}
+
+impl ValueInfo {
+    fn new(name: &str, element_type: ElementType, shape: Option<Vec<Dim>>) -> Self {
+        Self {
+            name: name.to_string(),
+            ty: ValueType::Tensor {
+                element_type,
+                shape,
+            },
+        }
+    }
+
+    fn encode(&self, enc: &mut Encoder) {
+        enc.string(1, &self.name);
+        enc.message(2, |ty| self.ty.encode(ty));
+    }
+}
+
+impl ValueType {
+    /// Encode a `TypeProto`.
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            ValueType::Tensor {
+                element_type,
+                shape,
+            } => enc.message(1, |tensor| {
+                encode_tensor_type(tensor, *element_type, shape.as_deref())
+            }),
+            // TypeProto.Map
+            ValueType::Map { key, value } => enc.message(5, |map| {
+                map.varint(1, key.to_ort_encoding() as u64);
+                map.message(2, |ty| {
+                    ty.message(1, |tensor| encode_tensor_type(tensor, *value, Some(&[])))
+                });
+            }),
+        }
+    }
+}
+
+/// Encode a `TypeProto.Tensor`. A shape of `None` leaves the rank
+/// unspecified.
+fn encode_tensor_type(enc: &mut Encoder, element_type: ElementType, shape: Option<&[Dim]>) {
+    enc.varint(1, element_type.to_ort_encoding() as u64);
+    if let Some(shape) = shape {
+        enc.message(2, |shape_proto| {
+            for dim in shape {
+                shape_proto.message(1, |dim_proto| match dim {
+                    Dim::Fixed(size) => dim_proto.varint(1, *size as u64),
+                    Dim::Symbolic(name) => dim_proto.string(2, name),
+                    Dim::Unknown => {}
+                });
+            }
+        });
+    }
+}
+
+/// Encode an `AttributeProto`.
+fn encode_attribute(enc: &mut Encoder, name: &str, value: &AttributeValue) {
+    // Values of `AttributeProto.AttributeType`
+    const FLOAT: u64 = 1;
+    const INT: u64 = 2;
+    const STRING: u64 = 3;
+    const TENSOR: u64 = 4;
+    const FLOATS: u64 = 6;
+    const INTS: u64 = 7;
+
+    enc.string(1, name);
+    let ty = match value {
+        AttributeValue::Float(value) => {
+            enc.fixed32(2, value.to_bits());
+            FLOAT
+        }
+        AttributeValue::Int(value) => {
+            enc.varint(3, *value as u64);
+            INT
+        }
+        AttributeValue::String(value) => {
+            enc.string(4, value);
+            STRING
+        }
+        AttributeValue::Tensor(tensor) => {
+            enc.message(5, |enc| encode_tensor(enc, "", tensor));
+            TENSOR
+        }
+        AttributeValue::Floats(values) => {
+            for value in values {
+                enc.fixed32(7, value.to_bits());
+            }
+            FLOATS
+        }
+        AttributeValue::Ints(values) => {
+            for value in values {
+                enc.varint(8, *value as u64);
+            }
+            INTS
+        }
+    };
+    enc.varint(20, ty);
+}
+
+/// Encode a `TensorProto`. Numeric data is stored little-endian in
+/// `raw_data`.
+fn encode_tensor(enc: &mut Encoder, name: &str, tensor: &Tensor) {
+    for dim in tensor.shape() {
+        enc.varint(1, *dim as u64);
+    }
+    enc.varint(2, tensor.element_type().to_ort_encoding() as u64);
+    if !name.is_empty() {
+        enc.string(8, name);
+    }
+    match &tensor.data {
+        TensorData::Plain(_) => enc.bytes(9, tensor.as_bytes()),
+        TensorData::Strings(strings) => {
+            for string in strings {
+                enc.string(6, string);
+            }
+        }
+    }
+}
+
+/// Writer of the protobuf wire format.
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    const VARINT: u32 = 0;
+    const LEN: u32 = 2;
+    const FIXED32: u32 = 5;
+
+    fn tag(&mut self, field: u32, wire_type: u32) {
+        self.raw_varint(((field << 3) | wire_type) as u64);
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    /// Write a varint field. Negative `int64` values are passed as
+    /// their two's complement.
+    fn varint(&mut self, field: u32, value: u64) {
+        self.tag(field, Self::VARINT);
+        self.raw_varint(value);
+    }
+
+    fn fixed32(&mut self, field: u32, value: u32) {
+        self.tag(field, Self::FIXED32);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.tag(field, Self::LEN);
+        self.raw_varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    /// Write an embedded message whose fields are written by `f`.
+    fn message(&mut self, field: u32, f: impl FnOnce(&mut Encoder)) {
+        let mut inner = Encoder::default();
+        f(&mut inner);
+        self.bytes(field, &inner.buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr1;
+
+    use super::*;
+
+    #[test]
+    fn encode_varints() {
+        let mut enc = Encoder::default();
+        enc.varint(1, 150);
+        enc.varint(2, -1i64 as u64);
+        assert_eq!(
+            enc.buf,
+            [
+                0x08, 0x96, 0x01, 0x10, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_model() {
+        let model = Model::new()
+            .opset("d", 1)
+            .node(
+                Node::new("d", "Op")
+                    .inputs(&["A"])
+                    .outputs(&["B"])
+                    .attribute("x", 1i64),
+            )
+            .input("A", ElementType::F32, Some(&[Dim::Fixed(2)]))
+            .output("B", ElementType::F32, None)
+            .initializer("C", arr1(&[1u8, 2]).into_dyn());
+
+        let node = [
+            &[0x0a, 1, b'A', 0x12, 1, b'B', 0x22, 2, b'O', b'p'][..],
+            // attribute `x` of type INT
+            &[0x2a, 8, 0x0a, 1, b'x', 0x18, 1, 0xa0, 0x01, 2],
+            &[0x3a, 1, b'd'],
+        ]
+        .concat();
+        let initializer = [0x08, 2, 0x10, 2, 0x42, 1, b'C', 0x4a, 2, 1, 2];
+        let input = [
+            0x0a, 1, b'A', 0x12, 10, 0x0a, 8, 0x08, 1, 0x12, 4, 0x0a, 2, 0x08, 2,
+        ];
+        let output = [0x0a, 1, b'B', 0x12, 4, 0x0a, 2, 0x08, 1];
+        let graph = [
+            &[0x0a, node.len() as u8][..],
+            &node,
+            &[0x12, 5],
+            b"graph",
+            &[0x2a, initializer.len() as u8],
+            &initializer,
+            &[0x5a, input.len() as u8],
+            &input,
+            &[0x62, output.len() as u8],
+            &output,
+        ]
+        .concat();
+        let expected = [
+            &[0x08, 9, 0x3a, graph.len() as u8][..],
+            &graph,
+            &[0x42, 5, 0x0a, 1, b'd', 0x10, 1],
+        ]
+        .concat();
+        assert_eq!(model.to_bytes(), expected);
+    }
+
+    #[test]
+    fn encode_map_input() {
+        let model = Model::new().map_input("A", ElementType::String, ElementType::F32);
+        let mut enc = Encoder::default();
+        model.inputs[0].encode(&mut enc);
+        let value_type = [0x0a, 4, 0x08, 1, 0x12, 0];
+        let map_type = [&[0x08, 8, 0x12, value_type.len() as u8][..], &value_type].concat();
+        let type_proto = [&[0x2a, map_type.len() as u8][..], &map_type].concat();
+        let expected = [
+            &[0x0a, 1, b'A', 0x12, type_proto.len() as u8][..],
+            &type_proto,
+        ]
+        .concat();
+        assert_eq!(enc.buf, expected);
+    }
+}
@@ -0,0 +1,489 @@
+//! Run custom operators in onnxruntime loaded from a shared library.
+//!
+//! Unlike [`super::TestKernel`], operators run inside a real session,
+//! so that the registration, the kernel creation from the model and
+//! the compute are exercised as they are by any other onnxruntime
+//! client:
+//!
+//! ```ignore
+//! use ort_custom_op::testing::{Runtime, Tensor};
+//!
+//! let Some(runtime) = Runtime::get()? else {
+//!     return Ok(()); // onnxruntime is not installed
+//! };
+//! let register = Runtime::load_custom_ops("target/debug/libexample.so")?;
+//! let session = runtime.session(&model.to_bytes(), register)?;
+//! let outputs = session.run(&[("A", a), ("B", b)])?;
+//! ```
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, OsStr, c_char, c_void};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex, OnceLock, PoisonError};
+
+use anyhow::{Context, Result, anyhow, bail};
+use libloading::Library;
+
+use crate::api::{ElementType, OwnedValue, TypeInfo, negotiate_api, read_type_info};
+use crate::bindings::*;
+use crate::logger::Severity;
+
+use super::{Input, Tensor, TensorData};
+
+/// Environment variable holding the path of the onnxruntime shared
+/// library. The default name of the library is used if it is not set.
+pub const ORT_DYLIB_PATH: &str = "ORT_DYLIB_PATH";
+
+/// Signature of the entry point generated by
+/// [`crate::ort_custom_ops`] which onnxruntime calls to register the
+/// operators of a library.
+pub type RegisterCustomOpsFn =
+    unsafe extern "C" fn(&mut OrtSessionOptions, &OrtApiBase) -> *mut OrtStatus;
+
+/// onnxruntime library with the environment shared by all sessions.
+///
+/// onnxruntime allows a single environment per process, hence the
+/// runtime is a process-wide singleton which is never unloaded.
+#[derive(Debug)]
+pub struct Runtime {
+    api_base: &'static OrtApiBase,
+    api: &'static OrtApi,
+    env: NonNull<OrtEnv>,
+    _library: Library,
+}
+
+// The environment is thread-safe.
+unsafe impl Send for Runtime {}
+unsafe impl Sync for Runtime {}
+
+static RUNTIME: OnceLock<Result<Option<Runtime>, String>> = OnceLock::new();
+
+/// Messages logged for a session.
+type Messages = Vec<(Severity, String)>;
+
+/// Messages logged by each open session, keyed by the log id of the
+/// session.
+static SESSION_LOGS: LazyLock<Mutex<HashMap<String, Messages>>> = LazyLock::new(Default::default);
+
+impl Runtime {
+    /// Runtime of this process, loaded from [`ORT_DYLIB_PATH`] on
+    /// first use.
+    ///
+    /// Returns `None` if the variable is not set and the library
+    /// cannot be found under its default name, so that tests may be
+    /// skipped where onnxruntime is not installed.
+    pub fn get() -> Result<Option<&'static Runtime>> {
+        RUNTIME
+            .get_or_init(|| {
+                let runtime = match std::env::var_os(ORT_DYLIB_PATH) {
+                    Some(path) => Runtime::load(&path).map(Some),
+                    None => Ok(Runtime::load(libloading::library_filename("onnxruntime")).ok()),
+                };
+                runtime.map_err(|err| format!("{err:#}"))
+            })
+            .as_ref()
+            .map(Option::as_ref)
+            .map_err(|err| anyhow!("{err}"))
+    }
+
+    fn load(path: impl AsRef<OsStr>) -> Result<Self> {
+        let path = path.as_ref();
+        let library = unsafe { Library::new(path) }
+            .with_context(|| format!("Failed to load onnxruntime from {path:?}"))?;
+        let api_base = unsafe {
+            let get_api_base = library
+                .get::<unsafe extern "C" fn() -> *const OrtApiBase>(b"OrtGetApiBase\0")
+                .context("Failed to look up `OrtGetApiBase`")?;
+            get_api_base()
+                .as_ref()
+                .context("onnxruntime returned a null API base")?
+        };
+        let Some((api, _)) = negotiate_api(api_base) else {
+            bail!("onnxruntime at {path:?} does not provide a supported version of the C API");
+        };
+
+        let mut env = std::ptr::null_mut();
+        api.status_to_result(unsafe {
+            api.CreateEnvWithCustomLogger.unwrap()(
+                Some(log_message),
+                std::ptr::null_mut(),
+                OrtLoggingLevel_ORT_LOGGING_LEVEL_WARNING,
+                c"ort-custom-op".as_ptr(),
+                &mut env,
+            )
+        })?;
+        Ok(Self {
+            api_base,
+            api,
+            env: NonNull::new(env).context("onnxruntime returned a null environment")?,
+            _library: library,
+        })
+    }
+
+    /// Look up the entry point `RegisterCustomOps` in the custom
+    /// operator library at `path`.
+    ///
+    /// The library stays loaded for the lifetime of the process since
+    /// sessions and the domains cached by the library may outlive any
+    /// handle to it.
+    pub fn load_custom_ops(path: impl AsRef<OsStr>) -> Result<RegisterCustomOpsFn> {
+        let path = path.as_ref();
+        let library = unsafe { Library::new(path) }
+            .with_context(|| format!("Failed to load custom operators from {path:?}"))?;
+        let register = unsafe {
+            *library
+                .get::<RegisterCustomOpsFn>(b"RegisterCustomOps\0")
+                .with_context(|| format!("Failed to look up `RegisterCustomOps` in {path:?}"))?
+        };
+        std::mem::forget(library);
+        Ok(register)
+    }
+
+    /// Create a session of the serialized `model` (see
+    /// [`super::Model::to_bytes`]) after registering custom operators
+    /// with `register_custom_ops`.
+    pub fn session(
+        &self,
+        model: &[u8],
+        register_custom_ops: RegisterCustomOpsFn,
+    ) -> Result<Session<'_>> {
+        static SESSION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let api = self.api;
+        let options = SessionOptions::new(api)?;
+        let log_id = format!("session-{}", SESSION_COUNT.fetch_add(1, Ordering::Relaxed));
+        let c_log_id = CString::new(log_id.as_str())?;
+        unsafe {
+            api.status_to_result(api.SetSessionLogId.unwrap()(
+                options.ptr.as_ptr(),
+                c_log_id.as_ptr(),
+            ))?;
+            api.status_to_result(api.SetSessionLogSeverityLevel.unwrap()(
+                options.ptr.as_ptr(),
+                OrtLoggingLevel_ORT_LOGGING_LEVEL_VERBOSE as _,
+            ))?;
+            api.status_to_result(register_custom_ops(
+                &mut *options.ptr.as_ptr(),
+                self.api_base,
+            ))?;
+        }
+
+        // Collect the messages logged while creating the session, too
+        SESSION_LOGS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(log_id.clone(), vec![]);
+        let mut session = Session {
+            runtime: self,
+            session: None,
+            log_id,
+            output_names: vec![],
+        };
+        let mut ptr = std::ptr::null_mut();
+        api.status_to_result(unsafe {
+            api.CreateSessionFromArray.unwrap()(
+                self.env.as_ptr(),
+                model.as_ptr() as *const c_void,
+                model.len(),
+                options.ptr.as_ptr(),
+                &mut ptr,
+            )
+        })?;
+        session.session = NonNull::new(ptr);
+        session.output_names = session.read_output_names()?;
+        Ok(session)
+    }
+}
+
+/// Session of a model in a [`Runtime`].
+#[derive(Debug)]
+pub struct Session<'r> {
+    runtime: &'r Runtime,
+    session: Option<NonNull<OrtSession>>,
+    log_id: String,
+    output_names: Vec<CString>,
+}
+
+impl Session<'_> {
+    /// Run the model on the named tensor `inputs` and return all its
+    /// outputs. Errors contain the message of the status returned by
+    /// onnxruntime.
+    pub fn run(&self, inputs: &[(&str, Tensor)]) -> Result<Vec<Tensor>> {
+        let inputs: Vec<_> = inputs
+            .iter()
+            .map(|(name, tensor)| (*name, Input::Tensor(tensor.clone())))
+            .collect();
+        self.run_inputs(&inputs)
+    }
+
+    /// Run the model on named `inputs` of any supported type and
+    /// return all its outputs, which must be tensors.
+    pub fn run_inputs(&self, inputs: &[(&str, Input)]) -> Result<Vec<Tensor>> {
+        let api = self.runtime.api;
+        let names = inputs
+            .iter()
+            .map(|(name, _)| CString::new(*name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut values = inputs
+            .iter()
+            .map(|(_, input)| match input {
+                Input::Tensor(tensor) => self.create_value(tensor),
+                Input::Sequence(elements) => {
+                    self.create_non_tensor(elements, ONNXType_ONNX_TYPE_SEQUENCE)
+                }
+                Input::Map { keys, values } => {
+                    self.create_non_tensor([keys, values], ONNXType_ONNX_TYPE_MAP)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let name_ptrs: Vec<_> = names.iter().map(|name| name.as_ptr()).collect();
+        let value_ptrs: Vec<_> = values
+            .iter_mut()
+            .map(|value| value.as_mut() as *const OrtValue)
+            .collect();
+        let output_name_ptrs: Vec<_> = self.output_names.iter().map(|n| n.as_ptr()).collect();
+        let mut outputs = vec![std::ptr::null_mut(); output_name_ptrs.len()];
+        api.status_to_result(unsafe {
+            api.Run.unwrap()(
+                self.ptr(),
+                std::ptr::null(),
+                name_ptrs.as_ptr(),
+                value_ptrs.as_ptr(),
+                value_ptrs.len(),
+                output_name_ptrs.as_ptr(),
+                output_name_ptrs.len(),
+                outputs.as_mut_ptr(),
+            )
+        })?;
+        // Take ownership of all outputs before reading any of them
+        let outputs = outputs
+            .into_iter()
+            .map(|value| unsafe { OwnedValue::from_raw(api, value) })
+            .collect::<Result<Vec<_>>>()?;
+        outputs
+            .into_iter()
+            .map(|mut value| read_tensor(api, value.as_mut()))
+            .collect()
+    }
+
+    /// Type and shape of the output `idx` of the model as inferred by
+    /// onnxruntime.
+    pub fn output_type_info(&self, idx: usize) -> Result<TypeInfo> {
+        let api = self.runtime.api;
+        let mut type_info = std::ptr::null_mut();
+        api.status_to_result(unsafe {
+            api.SessionGetOutputTypeInfo.unwrap()(self.ptr(), idx, &mut type_info)
+        })?;
+        unsafe { read_type_info(api, type_info) }
+    }
+
+    /// Messages logged by onnxruntime and the operators for this
+    /// session so far.
+    pub fn logs(&self) -> Messages {
+        SESSION_LOGS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&self.log_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn ptr(&self) -> *mut OrtSession {
+        self.session
+            .expect("Session is initialized once created")
+            .as_ptr()
+    }
+
+    fn read_output_names(&self) -> Result<Vec<CString>> {
+        let api = self.runtime.api;
+        let allocator = default_allocator(api)?;
+        let mut count = 0;
+        api.status_to_result(unsafe {
+            api.SessionGetOutputCount.unwrap()(self.ptr(), &mut count)
+        })?;
+        (0..count)
+            .map(|idx| unsafe {
+                let mut name = std::ptr::null_mut();
+                api.status_to_result(api.SessionGetOutputName.unwrap()(
+                    self.ptr(),
+                    idx,
+                    allocator,
+                    &mut name,
+                ))?;
+                let owned = CStr::from_ptr(name).to_owned();
+                api.status_to_result(api.AllocatorFree.unwrap()(allocator, name as *mut c_void))?;
+                Ok(owned)
+            })
+            .collect()
+    }
+
+    /// Copy `tensor` into a value allocated by onnxruntime.
+    fn create_value(&self, tensor: &Tensor) -> Result<OwnedValue<'static>> {
+        let api = self.runtime.api;
+        let dims: Vec<_> = tensor.shape().iter().map(|&dim| dim as i64).collect();
+        let mut value = unsafe {
+            let mut ptr = std::ptr::null_mut();
+            api.status_to_result(api.CreateTensorAsOrtValue.unwrap()(
+                default_allocator(api)?,
+                dims.as_ptr(),
+                dims.len(),
+                tensor.element_type().to_ort_encoding(),
+                &mut ptr,
+            ))?;
+            OwnedValue::from_raw(api, ptr)?
+        };
+        match &tensor.data {
+            TensorData::Strings(strings) => {
+                let strings = strings
+                    .iter()
+                    .map(|s| CString::new(s.as_str()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let ptrs: Vec<_> = strings.iter().map(|s| s.as_ptr()).collect();
+                api.status_to_result(unsafe {
+                    api.FillStringTensor.unwrap()(value.as_mut(), ptrs.as_ptr(), ptrs.len())
+                })?;
+            }
+            TensorData::Plain(_) => {
+                let bytes = tensor.as_bytes();
+                if !bytes.is_empty() {
+                    let data = tensor_data(api, value.as_mut())?;
+                    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len()) };
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    /// Create a sequence of tensors or a map from the tensors of its
+    /// keys and values.
+    fn create_non_tensor<'a>(
+        &self,
+        parts: impl IntoIterator<Item = &'a Tensor>,
+        onnx_type: ONNXType,
+    ) -> Result<OwnedValue<'static>> {
+        let api = self.runtime.api;
+        // onnxruntime copies the parts into the new value
+        let parts = parts
+            .into_iter()
+            .map(|tensor| self.create_value(tensor))
+            .collect::<Result<Vec<_>>>()?;
+        let ptrs: Vec<_> = parts
+            .iter()
+            .map(|part| part.as_ref() as *const OrtValue)
+            .collect();
+        unsafe {
+            let mut ptr = std::ptr::null_mut();
+            api.status_to_result(api.CreateValue.unwrap()(
+                ptrs.as_ptr(),
+                ptrs.len(),
+                onnx_type,
+                &mut ptr,
+            ))?;
+            OwnedValue::from_raw(api, ptr)
+        }
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        if let Some(session) = self.session {
+            unsafe { self.runtime.api.ReleaseSession.unwrap()(session.as_ptr()) }
+        }
+        SESSION_LOGS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.log_id);
+    }
+}
+
+/// Owned `OrtSessionOptions` which are released when dropped.
+struct SessionOptions {
+    api: &'static OrtApi,
+    ptr: NonNull<OrtSessionOptions>,
+}
+
+impl SessionOptions {
+    fn new(api: &'static OrtApi) -> Result<Self> {
+        let mut ptr = std::ptr::null_mut();
+        api.status_to_result(unsafe { api.CreateSessionOptions.unwrap()(&mut ptr) })?;
+        Ok(Self {
+            api,
+            ptr: NonNull::new(ptr).context("onnxruntime returned null session options")?,
+        })
+    }
+}
+
+impl Drop for SessionOptions {
+    fn drop(&mut self) {
+        unsafe { self.api.ReleaseSessionOptions.unwrap()(self.ptr.as_ptr()) }
+    }
+}
+
+fn default_allocator(api: &OrtApi) -> Result<*mut OrtAllocator> {
+    let mut allocator = std::ptr::null_mut();
+    api.status_to_result(unsafe { api.GetAllocatorWithDefaultOptions.unwrap()(&mut allocator) })?;
+    Ok(allocator)
+}
+
+/// Pointer to the elements of a numeric tensor.
+fn tensor_data(api: &OrtApi, value: &mut OrtValue) -> Result<*mut u8> {
+    let mut data = std::ptr::null_mut();
+    api.status_to_result(unsafe { api.GetTensorMutableData.unwrap()(value, &mut data) })?;
+    Ok(data as *mut u8)
+}
+
+/// Copy a tensor returned by onnxruntime.
+fn read_tensor(api: &OrtApi, value: &mut OrtValue) -> Result<Tensor> {
+    let (element_type, shape) = {
+        let info = value.get_tensor_type_and_shape(api)?;
+        (info.get_element_type()?, info.shape()?)
+    };
+    if element_type == ElementType::String {
+        let (buf, offsets) = value.get_string_tensor_single_buf(api)?;
+        let ends = offsets.iter().skip(1).copied().chain([buf.len()]);
+        let strings = offsets
+            .iter()
+            .zip(ends)
+            .map(|(&start, end)| Ok(std::str::from_utf8(&buf[start..end])?.to_string()))
+            .collect::<Result<_>>()?;
+        return Ok(Tensor {
+            element_type,
+            shape,
+            data: TensorData::Strings(strings),
+        });
+    }
+    let mut tensor = Tensor::zeros(element_type, shape);
+    let bytes = tensor.as_bytes_mut();
+    if !bytes.is_empty() {
+        let data = tensor_data(api, value)?;
+        unsafe { std::ptr::copy_nonoverlapping(data, bytes.as_mut_ptr(), bytes.len()) };
+    }
+    Ok(tensor)
+}
+
+/// Logging function of the environment which records the messages of
+/// open sessions.
+unsafe extern "C" fn log_message(
+    _param: *mut c_void,
+    severity: OrtLoggingLevel,
+    _category: *const c_char,
+    log_id: *const c_char,
+    _code_location: *const c_char,
+    message: *const c_char,
+) {
+    let (Ok(severity), Some(log_id), Some(message)) = (
+        Severity::from_ort(severity),
+        unsafe { log_id.as_ref() }.map(|ptr| unsafe { CStr::from_ptr(ptr) }),
+        unsafe { message.as_ref() }.map(|ptr| unsafe { CStr::from_ptr(ptr) }),
+    ) else {
+        return;
+    };
+    // The loggers of individual runs are named `<session log id>:<run tag>`
+    let log_id = log_id.to_string_lossy();
+    let session_id = log_id.split(':').next().unwrap_or_default();
+    let mut logs = SESSION_LOGS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(messages) = logs.get_mut(session_id) {
+        messages.push((severity, message.to_string_lossy().into_owned()));
+    }
+}
@@ -1,12 +1,16 @@
 use crate::{
-    api::ElementType,
+    api::{ElementType, OwnedValue},
     bindings::{OrtApi, OrtValue},
 };
 use anyhow::Result;
 use ndarray::{ArrayView, ArrayViewD};
 
 /// Enum over all currently supported input value types.
+///
+/// Further variants are available with the `half` feature and may be
+/// added in the future.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Value<'a> {
     TensorBool(ArrayViewD<'a, bool>),
     TensorF32(ArrayViewD<'a, f32>),
@@ -20,10 +24,47 @@ pub enum Value<'a> {
     TensorU64(ArrayViewD<'a, u64>),
     TensorU8(ArrayViewD<'a, u8>),
     TensorStr(ArrayViewD<'a, &'a str>),
+    #[cfg(feature = "half")]
+    TensorF16(ArrayViewD<'a, half::f16>),
+    #[cfg(feature = "half")]
+    TensorBF16(ArrayViewD<'a, half::bf16>),
+    Sequence(Vec<Value<'a>>),
+    /// Map given by its keys and values which are both 1D tensors
+    Map {
+        keys: Box<Value<'a>>,
+        values: Box<Value<'a>>,
+    },
 }
 
+/// Sequence value such as `seq(tensor(int64))`.
+///
+/// This type is distinct from a trailing `Vec` in [crate::prelude::Inputs]
+/// which denotes variadic inputs. Sequences are only supported as
+/// inputs since onnxruntime only provides tensors as outputs of
+/// custom operators. See [crate::prelude::Inputs] for how sequence
+/// inputs are declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence<T>(pub Vec<T>);
+
 pub(crate) enum ValueBuffer<Buf, Shape> {
-    Tensor { buf: Buf, shape: Shape },
+    Tensor {
+        buf: Buf,
+        shape: Shape,
+    },
+    Sequence(Vec<ValueBuffer<Buf, Shape>>),
+    Map {
+        keys: Box<ValueBuffer<Buf, Shape>>,
+        values: Box<ValueBuffer<Buf, Shape>>,
+    },
+}
+
+/// Buffer of an input value as loaded from onnxruntime along with
+/// the values it borrows from.
+pub(crate) struct InputBuffer<'s> {
+    // Declared first so that it is dropped before the values it
+    // borrows from.
+    buf: ValueBuffer<BufferMaybeOwned<'s>, Vec<usize>>,
+    _owned: Vec<OwnedValue<'s>>,
 }
 
 pub(crate) enum Buffer<'s> {
@@ -39,6 +80,10 @@ pub(crate) enum Buffer<'s> {
     U64(&'s [u64]),
     U8(&'s [u8]),
     Str(Vec<&'s str>),
+    #[cfg(feature = "half")]
+    F16(&'s [half::f16]),
+    #[cfg(feature = "half")]
+    BF16(&'s [half::bf16]),
 }
 
 pub(crate) enum BufferMaybeOwned<'s> {
@@ -54,6 +99,10 @@ pub(crate) enum BufferMaybeOwned<'s> {
     U64(&'s [u64]),
     U8(&'s [u8]),
     String(StringBuffer),
+    #[cfg(feature = "half")]
+    F16(&'s [half::f16]),
+    #[cfg(feature = "half")]
+    BF16(&'s [half::bf16]),
 }
 
 /// Object owning the contiguous String buffer and the associated offsets.
@@ -67,27 +116,31 @@ impl<'s> BufferMaybeOwned<'s> {
     /// ensure that the `dtype` is correct.
     pub unsafe fn load_from_ort(
         api: &OrtApi,
-        ort_value: &'s mut OrtValue,
+        ort_value: &'s OrtValue,
         dtype: &ElementType,
     ) -> Result<Self> {
         // tensor data
         Ok(unsafe {
             match dtype {
-                ElementType::U8 => Self::U8(ort_value.get_data_mut(api)?),
-                ElementType::U16 => Self::U16(ort_value.get_data_mut(api)?),
-                ElementType::U32 => Self::U32(ort_value.get_data_mut(api)?),
-                ElementType::U64 => Self::U64(ort_value.get_data_mut(api)?),
-                ElementType::I8 => Self::I8(ort_value.get_data_mut(api)?),
-                ElementType::I16 => Self::I16(ort_value.get_data_mut(api)?),
-                ElementType::I32 => Self::I32(ort_value.get_data_mut(api)?),
-                ElementType::I64 => Self::I64(ort_value.get_data_mut(api)?),
-                ElementType::F32 => Self::F32(ort_value.get_data_mut(api)?),
-                ElementType::F64 => Self::F64(ort_value.get_data_mut(api)?),
-                ElementType::Bool => Self::Bool(ort_value.get_data_mut(api)?),
+                ElementType::U8 => Self::U8(ort_value.get_data(api)?),
+                ElementType::U16 => Self::U16(ort_value.get_data(api)?),
+                ElementType::U32 => Self::U32(ort_value.get_data(api)?),
+                ElementType::U64 => Self::U64(ort_value.get_data(api)?),
+                ElementType::I8 => Self::I8(ort_value.get_data(api)?),
+                ElementType::I16 => Self::I16(ort_value.get_data(api)?),
+                ElementType::I32 => Self::I32(ort_value.get_data(api)?),
+                ElementType::I64 => Self::I64(ort_value.get_data(api)?),
+                ElementType::F32 => Self::F32(ort_value.get_data(api)?),
+                ElementType::F64 => Self::F64(ort_value.get_data(api)?),
+                ElementType::Bool => Self::Bool(ort_value.get_data(api)?),
                 ElementType::String => {
                     let (buf, offsets) = ort_value.get_string_tensor_single_buf(api)?;
                     Self::String(StringBuffer { buf, offsets })
                 }
+                #[cfg(feature = "half")]
+                ElementType::F16 => Self::F16(ort_value.get_data(api)?),
+                #[cfg(feature = "half")]
+                ElementType::BF16 => Self::BF16(ort_value.get_data(api)?),
             }
         })
     }
@@ -106,6 +159,10 @@ impl<'s> BufferMaybeOwned<'s> {
             Self::U64(buf) => Buffer::U64(buf),
             Self::U8(buf) => Buffer::U8(buf),
             Self::String(string_buf) => Buffer::Str(string_buf.vec_of_strs()),
+            #[cfg(feature = "half")]
+            Self::F16(buf) => Buffer::F16(buf),
+            #[cfg(feature = "half")]
+            Self::BF16(buf) => Buffer::BF16(buf),
         }
     }
 }
@@ -126,13 +183,34 @@ impl StringBuffer {
     }
 }
 
+impl<'s> InputBuffer<'s> {
+    /// Create a buffer which borrows from the values in `owned`.
+    pub(crate) fn new(
+        buf: ValueBuffer<BufferMaybeOwned<'s>, Vec<usize>>,
+        owned: Vec<OwnedValue<'s>>,
+    ) -> Self {
+        Self { buf, _owned: owned }
+    }
+
+    pub fn normalize_buffers(&self) -> ValueBuffer<Buffer<'_>, &[usize]> {
+        self.buf.normalize_buffers()
+    }
+}
+
 impl<'s> ValueBuffer<BufferMaybeOwned<'s>, Vec<usize>> {
-    pub fn normalize_buffers(&'s self) -> ValueBuffer<Buffer<'s>, &'s [usize]> {
+    pub fn normalize_buffers(&self) -> ValueBuffer<Buffer<'_>, &[usize]> {
         match self {
             Self::Tensor { buf, shape } => ValueBuffer::Tensor {
                 shape: shape.as_slice(),
                 buf: buf.view(),
             },
+            Self::Sequence(elements) => {
+                ValueBuffer::Sequence(elements.iter().map(Self::normalize_buffers).collect())
+            }
+            Self::Map { keys, values } => ValueBuffer::Map {
+                keys: Box::new(keys.normalize_buffers()),
+                values: Box::new(values.normalize_buffers()),
+            },
         }
     }
 }
@@ -155,8 +233,19 @@ impl<'s> ValueBuffer<Buffer<'s>, &'s [usize]> {
                     Buffer::U64(buf) => Value::TensorU64(ArrayView::from(buf).into_shape(shape)?),
                     Buffer::U8(buf) => Value::TensorU8(ArrayView::from(buf).into_shape(shape)?),
                     Buffer::Str(buf) => Value::TensorStr(ArrayView::from(buf).into_shape(shape)?),
+                    #[cfg(feature = "half")]
+                    Buffer::F16(buf) => Value::TensorF16(ArrayView::from(buf).into_shape(shape)?),
+                    #[cfg(feature = "half")]
+                    Buffer::BF16(buf) => Value::TensorBF16(ArrayView::from(buf).into_shape(shape)?),
                 }
             }
+            Self::Sequence(elements) => {
+                Value::Sequence(elements.iter().map(Self::as_value).collect::<Result<_>>()?)
+            }
+            Self::Map { keys, values } => Value::Map {
+                keys: Box::new(keys.as_value()?),
+                values: Box::new(values.as_value()?),
+            },
         })
     }
 }
@@ -0,0 +1,119 @@
+//! Check that registering operators does not leak memory.
+//!
+//! The allocations are counted by a global allocator, which is why
+//! this test lives in its own binary rather than in the unit tests.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::ffi::{CStr, c_char};
+use std::ptr::NonNull;
+use std::sync::LazyLock;
+
+use ndarray::{ArrayD, ArrayViewD};
+use ort_custom_op::__private::{OrtApi, OrtCustomOpDomain};
+use ort_custom_op::prelude::*;
+
+/// Allocator keeping track of the bytes allocated by each thread
+/// which were not yet freed.
+struct CountingAllocator;
+
+thread_local! {
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.with(|live| live.set(live.get() + layout.size() as isize));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.with(|live| live.set(live.get() - layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+struct Identity;
+
+impl CustomOp for Identity {
+    type KernelCreateError = Infallible;
+    type ComputeError = Infallible;
+
+    const NAME: &'static CStr = c"Identity";
+
+    type OpInputs<'s> = (ArrayViewD<'s, f32>,);
+    type OpOutputs = (ArrayD<f32>,);
+
+    fn kernel_create(_info: &KernelInfo) -> Result<Self, Self::KernelCreateError> {
+        Ok(Identity)
+    }
+
+    fn kernel_compute(
+        &self,
+        (array,): Self::OpInputs<'_>,
+    ) -> Result<Self::OpOutputs, Self::ComputeError> {
+        Ok((array.to_owned(),))
+    }
+}
+
+static OP_IDENTITY: OrtCustomOp = build::<Identity>();
+
+/// API which only provides the functions needed to register
+/// operators.
+static API: LazyLock<OrtApi> = LazyLock::new(|| {
+    let mut api: OrtApi = unsafe { std::mem::zeroed() };
+    api.CreateCustomOpDomain = Some(create_custom_op_domain);
+    api.CustomOpDomain_Add = Some(custom_op_domain_add);
+    api.AddCustomOpDomain = Some(add_custom_op_domain);
+    api.ReleaseCustomOpDomain = Some(release_custom_op_domain);
+    api
+});
+
+unsafe extern "C" fn get_api(_version: u32) -> *const OrtApi {
+    &*API
+}
+
+unsafe extern "C" fn create_custom_op_domain(
+    _domain: *const c_char,
+    out: *mut *mut OrtCustomOpDomain,
+) -> *mut OrtStatus {
+    unsafe { *out = NonNull::dangling().as_ptr() };
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn custom_op_domain_add(
+    _domain: *mut OrtCustomOpDomain,
+    _op: *const OrtCustomOp,
+) -> *mut OrtStatus {
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn add_custom_op_domain(
+    _options: *mut OrtSessionOptions,
+    _domain: *mut OrtCustomOpDomain,
+) -> *mut OrtStatus {
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn release_custom_op_domain(_domain: *mut OrtCustomOpDomain) {}
+
+#[test]
+fn registering_operators_does_not_leak() {
+    let api_base = OrtApiBase {
+        GetApi: Some(get_api),
+        GetVersionString: None,
+    };
+    let options = unsafe { NonNull::<OrtSessionOptions>::dangling().as_mut() };
+    let domain = || CustomOpDomain::new("test.domain").with_op(&OP_IDENTITY);
+    // The first registration creates the domain
+    register_custom_op_domains(options, &api_base, [domain()]).unwrap();
+    let before = LIVE_BYTES.with(Cell::get);
+    for _ in 0..1_000 {
+        register_custom_op_domains(options, &api_base, [domain()]).unwrap();
+    }
+    assert_eq!(LIVE_BYTES.with(Cell::get), before);
+}
@@ -1,3 +1,4 @@
+import os
 from pathlib import Path
 from platform import platform
 import re
@@ -11,7 +12,15 @@ import onnxruntime as onnxrt
 ROOT = Path(__file__).parent.parent.parent
 
 
-@pytest.fixture(params=[TensorProto.FLOAT, TensorProto.DOUBLE])
+@pytest.fixture(
+    params=[
+        TensorProto.FLOAT,
+        TensorProto.DOUBLE,
+        TensorProto.FLOAT16,
+        TensorProto.INT32,
+        TensorProto.INT64,
+    ]
+)
 def onnx_tensor_type(request):
     return request.param
 
@@ -86,6 +95,10 @@ def custom_add_model(onnx_tensor_type):
 
 @pytest.fixture
 def parse_datetime_model():
+    return make_parse_datetime_model(fmt="%d.%m.%Y %H:%M %P %z")
+
+
+def make_parse_datetime_model(**attrs):
     # Using custom operators with the DSL (i.e. `onnx.parse`) for
     # defining ONNX models seems to be unsupported...
     node = helper.make_node(
@@ -93,7 +106,7 @@ def parse_datetime_model():
         ["A"],
         ["B"],
         domain="my.domain",
-        **{"fmt": "%d.%m.%Y %H:%M %P %z"},
+        **attrs,
     )
     value_infos_input = [
         helper.make_value_info(
@@ -284,6 +297,289 @@ def variadic_identity_model():
     )
 
 
+@pytest.fixture
+def concat_sequence_model():
+    # The sequence is built inside the graph from two tensor inputs
+    nodes = [
+        helper.make_node("SequenceConstruct", ["A", "B"], ["S"]),
+        helper.make_node("ConcatSequence", ["S"], ["C"], domain="my.domain"),
+    ]
+    value_infos_input = [
+        helper.make_value_info(
+            "A", helper.make_tensor_type_proto(TensorProto.INT64, [None, None])
+        ),
+        helper.make_value_info(
+            "B", helper.make_tensor_type_proto(TensorProto.INT64, [None])
+        ),
+    ]
+    value_infos_output = [
+        helper.make_value_info(
+            "C", helper.make_tensor_type_proto(TensorProto.INT64, [None])
+        ),
+    ]
+    graph = helper.make_graph(
+        nodes,
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[
+            helper.make_opsetid("", 18),
+            helper.make_opsetid("my.domain", 1),
+        ],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
+@pytest.fixture
+def map_arg_max_model():
+    node = helper.make_node("MapArgMax", ["A"], ["B"], domain="my.domain")
+    value_infos_input = [
+        helper.make_value_info(
+            "A",
+            helper.make_map_type_proto(
+                TensorProto.STRING,
+                helper.make_tensor_type_proto(TensorProto.FLOAT, []),
+            ),
+        ),
+    ]
+    value_infos_output = [
+        helper.make_value_info(
+            "B", helper.make_tensor_type_proto(TensorProto.STRING, [])
+        ),
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", 1)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
+def fill_nan_model(with_mask: bool):
+    outputs = ["B", "MASK"] if with_mask else ["B"]
+    node = helper.make_node("FillNan", ["A"], outputs, domain="my.domain")
+    value_infos_input = [
+        helper.make_value_info(
+            "A", helper.make_tensor_type_proto(TensorProto.FLOAT, [None])
+        ),
+    ]
+    value_infos_output = [
+        helper.make_value_info(
+            name,
+            helper.make_tensor_type_proto(
+                TensorProto.BOOL if name == "MASK" else TensorProto.FLOAT, [None]
+            ),
+        )
+        for name in outputs
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", 1)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
+@pytest.fixture
+def concat_to_string_model():
+    node = helper.make_node(
+        "ConcatToString", ["A", "B", "C"], ["D"], domain="my.domain"
+    )
+    value_infos_input = [
+        helper.make_value_info(
+            "A", helper.make_tensor_type_proto(TensorProto.INT64, [None])
+        ),
+        helper.make_value_info(
+            "B", helper.make_tensor_type_proto(TensorProto.BOOL, [None])
+        ),
+        helper.make_value_info(
+            "C", helper.make_tensor_type_proto(TensorProto.STRING, [None])
+        ),
+    ]
+    value_infos_output = [
+        helper.make_value_info(
+            "D", helper.make_tensor_type_proto(TensorProto.STRING, [None])
+        ),
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", 1)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
+def describe_node_model():
+    node = helper.make_node(
+        "DescribeNode", ["A"], ["B", "C"], domain="my.domain", name="describe"
+    )
+    value_infos_input = [
+        helper.make_value_info(
+            "A", helper.make_tensor_type_proto(TensorProto.FLOAT, ["N", 3, None])
+        ),
+    ]
+    value_infos_output = [
+        helper.make_value_info(
+            "B", helper.make_tensor_type_proto(TensorProto.STRING, [3])
+        ),
+        helper.make_value_info(
+            "C", helper.make_tensor_type_proto(TensorProto.INT64, [3])
+        ),
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", 1)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
+def lookup_index_model(constant_vocabulary: bool):
+    node = helper.make_node(
+        "LookupIndex", ["queries", "vocabulary"], ["out"], domain="my.domain"
+    )
+    value_infos_input = [
+        helper.make_value_info(
+            "queries", helper.make_tensor_type_proto(TensorProto.STRING, [None])
+        ),
+    ]
+    initializers = []
+    if constant_vocabulary:
+        initializers.append(
+            helper.make_tensor(
+                "vocabulary", TensorProto.STRING, [3], [b"a", b"b", b"c"]
+            )
+        )
+    else:
+        value_infos_input.append(
+            helper.make_value_info(
+                "vocabulary", helper.make_tensor_type_proto(TensorProto.STRING, [None])
+            )
+        )
+    value_infos_output = [
+        helper.make_value_info(
+            "out", helper.make_tensor_type_proto(TensorProto.INT64, [None])
+        ),
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+        initializer=initializers,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", 1)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
+def decode_labels_model():
+    classes = numpy_helper.from_array(np.array(["cat", "dog", "bird"], dtype=object))
+    node = helper.make_node(
+        "DecodeLabels", ["A"], ["B"], domain="my.domain", classes=classes
+    )
+    value_infos_input = [
+        helper.make_value_info(
+            "A", helper.make_tensor_type_proto(TensorProto.INT64, [None])
+        ),
+    ]
+    value_infos_output = [
+        helper.make_value_info(
+            "B", helper.make_tensor_type_proto(TensorProto.STRING, [None])
+        ),
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", 1)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
+def scale_model(domain_version: int):
+    node = helper.make_node("Scale", ["A"], ["B"], domain="my.domain")
+    value_infos_input = [
+        helper.make_value_info(
+            "A", helper.make_tensor_type_proto(TensorProto.FLOAT, [None])
+        ),
+    ]
+    value_infos_output = [
+        helper.make_value_info(
+            "B", helper.make_tensor_type_proto(TensorProto.FLOAT, [None])
+        ),
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", domain_version)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
+@pytest.fixture
+def add_in_place_model():
+    node = helper.make_node("AddInPlace", ["A", "B"], ["C"], domain="my.domain")
+    value_infos_input = [
+        helper.make_value_info(
+            "A", helper.make_tensor_type_proto(TensorProto.FLOAT, [None, None])
+        ),
+        helper.make_value_info(
+            "B", helper.make_tensor_type_proto(TensorProto.FLOAT, [None, None])
+        ),
+    ]
+    value_infos_output = [
+        helper.make_value_info(
+            "C", helper.make_tensor_type_proto(TensorProto.FLOAT, [None, None])
+        ),
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    return helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", 1)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+
+
 def fallible_model(with_attr: bool):
     # Using custom operators with the DSL (i.e. `onnx.parse`) for
     # defining ONNX models seems to be unsupported...
@@ -375,6 +671,25 @@ def test_custom_add(shared_lib, custom_add_model, onnx_tensor_type):
     np.testing.assert_allclose(output_expected, res[0], rtol=1e-05, atol=1e-08)
 
 
+def test_custom_add_panic(shared_lib, custom_add_model, onnx_tensor_type):
+    # Incompatible shapes make ndarray panic inside the kernel
+    dtype = helper.tensor_dtype_to_np_dtype(onnx_tensor_type)
+    sess = setup_session(shared_lib, custom_add_model)
+    input_feed = {
+        "A": np.ones((3, 5)).astype(dtype),
+        "B": np.ones((2, 4)).astype(dtype),
+    }
+    with pytest.raises(
+        onnxrt.capi.onnxruntime_pybind11_state.RuntimeException,
+        match=re.escape("CustomAdd: panicked:"),
+    ):
+        sess.run(None, input_feed)
+
+    # The session remains usable after a panic
+    input_feed["B"] = np.ones((3, 5)).astype(dtype)
+    sess.run(None, input_feed)
+
+
 def test_parse_datetime(shared_lib, parse_datetime_model):
     sess = setup_session(shared_lib, parse_datetime_model)
     # Run with input data
@@ -389,6 +704,46 @@ def test_parse_datetime(shared_lib, parse_datetime_model):
     np.testing.assert_equal(output_expected, res[0])
 
 
+def test_parse_datetime_parallel(shared_lib, parse_datetime_model):
+    # Large enough to be split across the intra-op thread pool
+    sess = setup_session(shared_lib, parse_datetime_model)
+    inputs = np.array(
+        ["5.8.1994 8:00 am +0000", "invalid", "5.8.2022 8:00 am +0000"] * 3_000
+    ).reshape(3, -1)
+    (res,) = sess.run(None, {sess.get_inputs()[0].name: inputs})
+    output_expected = np.array([776073600, np.nan, 1659686400] * 3_000).reshape(3, -1)
+    np.testing.assert_equal(output_expected, res)
+
+
+def test_parse_datetime_missing_fmt(shared_lib):
+    # Missing attributes are recognized by the message of onnxruntime's
+    # error, which this checks, too
+    model = make_parse_datetime_model()
+    with pytest.raises(
+        onnxrt.capi.onnxruntime_pybind11_state.RuntimeException,
+        match=re.escape("ParseDateTime: Missing required attribute 'fmt'"),
+    ):
+        setup_session(shared_lib, model)
+
+
+def test_parse_datetime_empty_fmt(shared_lib):
+    model = make_parse_datetime_model(fmt="")
+    with pytest.raises(
+        onnxrt.capi.onnxruntime_pybind11_state.RuntimeException,
+        match=re.escape("ParseDateTime: Invalid attribute 'fmt'"),
+    ):
+        setup_session(shared_lib, model)
+
+
+def test_parse_datetime_fmt_wrong_type(shared_lib):
+    model = make_parse_datetime_model(fmt=1)
+    with pytest.raises(
+        onnxrt.capi.onnxruntime_pybind11_state.RuntimeException,
+        match=re.escape("ParseDateTime: Failed to read attribute 'fmt'"),
+    ):
+        setup_session(shared_lib, model)
+
+
 def test_attr_showcase(shared_lib, attr_showcase_model):
     sess = setup_session(shared_lib, attr_showcase_model)
     # Run with input data
@@ -403,6 +758,35 @@ def test_attr_showcase(shared_lib, attr_showcase_model):
     np.testing.assert_equal(c, ["foo + bar"])
 
 
+@pytest.mark.skipif(
+    not Path("/proc/self/statm").exists(), reason="Requires the proc filesystem"
+)
+def test_attr_showcase_no_leak(shared_lib, attr_showcase_model):
+    # A large tensor attribute makes a leak per kernel creation obvious
+    (u8_tensor,) = [
+        attr
+        for attr in attr_showcase_model.graph.node[0].attribute
+        if attr.name == "u8_tensor"
+    ]
+    attribute_mib = 1
+    u8_tensor.t.CopyFrom(numpy_helper.from_array(np.zeros(2**20, np.uint8)))
+
+    def resident_mib():
+        pages = int(Path("/proc/self/statm").read_text().split()[1])
+        return pages * os.sysconf("SC_PAGE_SIZE") / 2**20
+
+    # Warm up allocator arenas and caches
+    for _ in range(100):
+        setup_session(shared_lib, attr_showcase_model)
+    n_sessions = 2_000
+    before = resident_mib()
+    for _ in range(n_sessions):
+        setup_session(shared_lib, attr_showcase_model)
+    # Leaking the attribute would amount to 2 GiB in total
+    growth_per_session = (resident_mib() - before) / n_sessions
+    assert growth_per_session < attribute_mib / 20
+
+
 @pytest.mark.skip(reason="Crashes the interpreter but prints a decent error message.")
 def test_attr_showcase_missing_attrs(
     shared_lib,
@@ -435,6 +819,54 @@ def test_variadic_identity(shared_lib, variadic_identity_model):
     np.testing.assert_equal(b, d)
 
 
+def test_concat_sequence(shared_lib, concat_sequence_model):
+    sess = setup_session(shared_lib, concat_sequence_model)
+    a = np.array([[1, 2], [3, 4]], np.int64)
+    b = np.array([5, 6, 7], np.int64)
+    (c,) = sess.run(None, {"A": a, "B": b})
+    np.testing.assert_equal(c, np.arange(1, 8))
+
+
+def test_map_arg_max(shared_lib, map_arg_max_model):
+    sess = setup_session(shared_lib, map_arg_max_model)
+    (res,) = sess.run(None, {"A": {"cat": 0.2, "dog": 0.7, "bird": 0.1}})
+    assert res == "dog"
+
+
+def test_fill_nan_with_mask(shared_lib):
+    sess = setup_session(shared_lib, fill_nan_model(with_mask=True))
+    a = np.array([1, np.nan, 3], np.float32)
+    b, mask = sess.run(None, {"A": a})
+    np.testing.assert_equal(b, [1, 0, 3])
+    np.testing.assert_equal(mask, [False, True, False])
+
+
+@pytest.mark.parametrize("a", [[1, np.nan, 3], [1, 2, 3]])
+def test_fill_nan_without_mask(shared_lib, a):
+    sess = setup_session(shared_lib, fill_nan_model(with_mask=False))
+    (b,) = sess.run(None, {"A": np.array(a, np.float32)})
+    np.testing.assert_equal(b, np.nan_to_num(a))
+
+
+def test_concat_to_string(shared_lib, concat_to_string_model):
+    sess = setup_session(shared_lib, concat_to_string_model)
+    input_feed = {
+        "A": np.array([1, 2], np.int64),
+        "B": np.array([True], np.bool_),
+        "C": np.array(["foo"], np.str_),
+    }
+    (d,) = sess.run(None, input_feed)
+    np.testing.assert_equal(d, ["1", "2", "true", "foo"])
+
+
+@pytest.mark.parametrize("domain_version, factor", [(1, 2), (2, 3), (5, 3)])
+def test_versioned_op(shared_lib, domain_version, factor):
+    sess = setup_session(shared_lib, scale_model(domain_version))
+    a = np.array([1, 2], np.float32)
+    (b,) = sess.run(None, {"A": a})
+    np.testing.assert_equal(b, a * factor)
+
+
 def test_fail_create_kernel_missing_attr(shared_lib):
     model = fallible_model(with_attr=False)
 
@@ -459,6 +891,48 @@ def test_fail_compute(shared_lib):
     sess.run(None, {"fail": np.array(False)})
 
 
+def test_fallible_op_logs_to_session_logger(shared_lib, capfd):
+    model = fallible_model(with_attr=True)
+    sess = setup_session(shared_lib, model)
+
+    with pytest.raises(onnxrt.capi.onnxruntime_pybind11_state.RuntimeException):
+        sess.run(None, {"fail": np.array(True)})
+
+    _, err = capfd.readouterr()
+    # Message logged via `ort_warn!` and the `log` crate respectively
+    assert "Failing due to non-zero input" in err
+    assert "Input was" in err
+
+
+def test_describe_node(shared_lib):
+    sess = setup_session(shared_lib, describe_node_model())
+    names, shape = sess.run(None, {"A": np.zeros((2, 3, 4), np.float32)})
+    np.testing.assert_equal(names, np.array(["describe", "A", "B"]))
+    np.testing.assert_equal(shape, np.array([-1, 3, -1]))
+
+
+@pytest.mark.parametrize("constant_vocabulary", [True, False])
+def test_lookup_index(shared_lib, constant_vocabulary: bool):
+    sess = setup_session(shared_lib, lookup_index_model(constant_vocabulary))
+    input_feed = {"queries": np.array(["c", "x", "a"])}
+    if not constant_vocabulary:
+        input_feed["vocabulary"] = np.array(["a", "b", "c"])
+    (res,) = sess.run(None, input_feed)
+    np.testing.assert_equal(res, np.array([2, -1, 0]))
+
+
+def test_decode_labels(shared_lib):
+    sess = setup_session(shared_lib, decode_labels_model())
+    (res,) = sess.run(None, {"A": np.array([2, 0, 1, 0])})
+    np.testing.assert_equal(res, np.array(["bird", "cat", "dog", "cat"]))
+
+    with pytest.raises(
+        onnxrt.capi.onnxruntime_pybind11_state.RuntimeException,
+        match=re.escape("DecodeLabels:"),
+    ):
+        sess.run(None, {"A": np.array([3])})
+
+
 def test_zero_size_input_numeric(shared_lib, variadic_identity_model):
     sess = setup_session(shared_lib, variadic_identity_model)
     # Run with input data
@@ -482,3 +956,55 @@ def test_zero_size_input_strings(shared_lib, parse_datetime_model):
     res = sess.run([output_name], input_feed)
     output_expected = np.array([])
     np.testing.assert_equal(output_expected, res[0])
+
+
+def test_add_in_place(shared_lib, add_in_place_model):
+    sess = setup_session(shared_lib, add_in_place_model)
+    input_feed = {
+        "A": np.random.randn(3, 5).astype(np.float32),
+        "B": np.random.randn(3, 5).astype(np.float32),
+    }
+    (res,) = sess.run(None, input_feed)
+    np.testing.assert_equal(input_feed["A"] + input_feed["B"], res)
+
+
+def test_add_in_place_infers_output_shape(shared_lib):
+    node = helper.make_node("AddInPlace", ["A", "B"], ["C"], domain="my.domain")
+    value_infos_input = [
+        helper.make_value_info(
+            name, helper.make_tensor_type_proto(TensorProto.FLOAT, ["N", 5])
+        )
+        for name in ["A", "B"]
+    ]
+    # The output is declared without a shape
+    value_infos_output = [
+        helper.make_value_info(
+            "C", helper.make_tensor_type_proto(TensorProto.FLOAT, None)
+        ),
+    ]
+    graph = helper.make_graph(
+        [node],
+        "graph",
+        value_infos_input,
+        value_infos_output,
+    )
+    model = helper.make_model(
+        graph,
+        opset_imports=[helper.make_opsetid("my.domain", 1)],
+        ir_version=IR_VERSION_2023_5_5,
+    )
+    sess = setup_session(shared_lib, model)
+    assert sess.get_outputs()[0].shape == ["N", 5]
+
+
+def test_add_in_place_shape_mismatch(shared_lib, add_in_place_model):
+    sess = setup_session(shared_lib, add_in_place_model)
+    input_feed = {
+        "A": np.ones((3, 5), np.float32),
+        "B": np.ones((5, 3), np.float32),
+    }
+    with pytest.raises(
+        onnxrt.capi.onnxruntime_pybind11_state.RuntimeException,
+        match=re.escape("AddInPlace:"),
+    ):
+        sess.run(None, input_feed)